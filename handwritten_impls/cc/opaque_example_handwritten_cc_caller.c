@@ -29,4 +29,5 @@ void do_test(void) {
     FINISHED_VAL(CALLER_OUTPUTS);
 
     FINISHED_FUNC(CALLER_INPUTS, CALLER_OUTPUTS);
+    FINISHED_FUNC(CALLER_POSTCALL, CALLEE_POSTCALL);
 }
\ No newline at end of file
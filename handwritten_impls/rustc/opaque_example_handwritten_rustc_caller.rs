@@ -34,5 +34,6 @@ pub extern fn do_test() {
         FINISHED_VAL.unwrap()(CALLER_OUTPUTS);
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
 }
\ No newline at end of file
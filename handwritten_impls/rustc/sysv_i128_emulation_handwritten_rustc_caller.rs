@@ -63,6 +63,7 @@ extern {
         callee_native_layout(&arg0, &arg1, &arg2);
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
     unsafe {
         let arg0: i128 = 34784419711585546284254720952638769794;
@@ -79,6 +80,7 @@ extern {
         callee_emulated_layout(&arg0, &arg1, &arg2);
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
     unsafe {
         let arg0: i128 = 34784419711585546284254720952638769794;
@@ -95,6 +97,7 @@ extern {
         callee_unaligned_emulated_layout(&arg0, &arg1, &arg2);
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
     unsafe {
         // native -> native (expected fail)
@@ -121,6 +124,7 @@ extern {
         native_to_native(arg0, arg1, arg2, arg3, arg4, arg5, );
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
     unsafe {
         // native -> emulated (expected fail)
@@ -147,6 +151,7 @@ extern {
         native_to_emulated(arg0, arg1, arg2, arg3, arg4, arg5, );
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
     unsafe {
         // native -> unaligned_emulated (expected pass?)
@@ -173,6 +178,7 @@ extern {
         native_to_unaligned_emulated(arg0, arg1, arg2, arg3, arg4, arg5, );
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
     unsafe {
         // emulated -> native (expected fail?)
@@ -199,6 +205,7 @@ extern {
         emulated_to_native(arg0, arg1, arg2, arg3, arg4, arg5, );
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
     unsafe {
         // emulated -> emulated (expected pass?)
@@ -225,6 +232,7 @@ extern {
         emulated_to_emulated(arg0, arg1, arg2, arg3, arg4, arg5, );
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
     unsafe {
         // emulated -> unaligned_emulated (expected fail?)
@@ -251,6 +259,7 @@ extern {
         emulated_to_unaligned_emulated(arg0, arg1, arg2, arg3, arg4, arg5, );
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
     unsafe {
         // unaligned_emulated -> native (expected fail?)
@@ -277,6 +286,7 @@ extern {
         unaligned_emulated_to_native(arg0, arg1, arg2, arg3, arg4, arg5, );
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
     unsafe {
         // unaligned_emulated -> emulated (expected fail?)
@@ -303,6 +313,7 @@ extern {
         unaligned_emulated_to_emulated(arg0, arg1, arg2, arg3, arg4, arg5, );
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
     unsafe {
         // unaligned_emulated -> unaligned_emulated (expected fail?)
@@ -329,5 +340,6 @@ extern {
         unaligned_emulated_to_unaligned_emulated(arg0, arg1, arg2, arg3, arg4, arg5, );
 
         FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);
+        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);
     }
 }
\ No newline at end of file
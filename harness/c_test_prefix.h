@@ -1,15 +1,31 @@
 #include <inttypes.h>
+#include <stddef.h>
+#include <stdlib.h>
 #include <string.h>
 #include <stdio.h>
 #include <stdbool.h>
 
 #define WriteBuffer void*
 
+#ifdef __cplusplus
+// Compound literals are temporaries in C++, so they need this to have an address
+// (which lasts until the end of the statement, which is all we need).
+template <typename T> static T& LVALUE(T&& val) { return val; }
+
+extern "C" {
+#endif
+
 extern WriteBuffer CALLER_INPUTS;
 extern WriteBuffer CALLER_OUTPUTS;
 extern WriteBuffer CALLEE_INPUTS;
 extern WriteBuffer CALLEE_OUTPUTS;
+extern WriteBuffer CALLER_POSTCALL;
+extern WriteBuffer CALLEE_POSTCALL;
 extern void (*WRITE_FIELD)(WriteBuffer, char*, uint32_t);
 extern void (*FINISHED_VAL)(WriteBuffer);
 extern void (*FINISHED_FUNC)(WriteBuffer, WriteBuffer);
 
+#ifdef __cplusplus
+}
+#endif
+
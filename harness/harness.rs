@@ -17,6 +17,10 @@ pub static mut CALLEE_INPUTS: WriteBuffer = WriteBuffer(core::ptr::null_mut());
 #[no_mangle]
 pub static mut CALLEE_OUTPUTS: WriteBuffer = WriteBuffer(core::ptr::null_mut());
 #[no_mangle]
+pub static mut CALLER_POSTCALL: WriteBuffer = WriteBuffer(core::ptr::null_mut());
+#[no_mangle]
+pub static mut CALLEE_POSTCALL: WriteBuffer = WriteBuffer(core::ptr::null_mut());
+#[no_mangle]
 pub static mut WRITE_FIELD: Option<WriteCallback> = None;
 #[no_mangle]
 pub static mut FINISHED_VAL: Option<FinishedValCallback> = None;
@@ -36,12 +40,16 @@ pub extern fn test_start(
     caller_outputs: WriteBuffer, 
     callee_inputs: WriteBuffer, 
     callee_outputs: WriteBuffer,
+    caller_postcall: WriteBuffer,
+    callee_postcall: WriteBuffer,
 ) {
     unsafe {
         CALLER_INPUTS = caller_inputs;
         CALLER_OUTPUTS = caller_outputs;
         CALLEE_INPUTS = callee_inputs;
         CALLEE_OUTPUTS = callee_outputs;
+        CALLER_POSTCALL = caller_postcall;
+        CALLEE_POSTCALL = callee_postcall;
         WRITE_FIELD = Some(write_callback);
         FINISHED_VAL = Some(finished_val_callback);
         FINISHED_FUNC = Some(finished_func_callback);
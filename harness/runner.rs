@@ -0,0 +1,84 @@
+// The harness as an executable, for tests built for a target that abi-cafe can't
+// load them into (like windows-msvc off of Windows, where they run under Wine).
+//
+// Instead of handing abi-cafe's own WriteBuffers to the test, this keeps its own,
+// and prints them all to stdout when it's done for abi-cafe to read back:
+// "buffer", "func" and "val" start a new one of each, and "field" is followed by
+// the bytes of one field in hex.
+
+include!("harness.rs");
+
+type Buffer = Vec<Vec<Vec<Vec<u8>>>>;
+
+unsafe fn buffer<'a>(output: WriteBuffer) -> &'a mut Buffer {
+    &mut *(output.0 as *mut Buffer)
+}
+
+unsafe extern fn write_field(output: WriteBuffer, input: *const u8, size: u32) {
+    // Push the bytes of an individual field
+    let data = std::slice::from_raw_parts(input, size as usize);
+    buffer(output)
+        .last_mut() // values
+        .unwrap()
+        .last_mut() // fields
+        .unwrap()
+        .push(data.to_vec());
+}
+
+unsafe extern fn finished_val(output: WriteBuffer) {
+    // This value is finished, push a new entry
+    buffer(output)
+        .last_mut() // values
+        .unwrap()
+        .push(vec![]);
+}
+
+unsafe extern fn finished_func(output1: WriteBuffer, output2: WriteBuffer) {
+    // Remove the pending value
+    buffer(output1).last_mut().unwrap().pop().unwrap();
+    buffer(output2).last_mut().unwrap().pop().unwrap();
+
+    // Push a new pending function
+    buffer(output1).push(vec![vec![]]);
+    buffer(output2).push(vec![vec![]]);
+}
+
+fn main() {
+    // Preload the hierarchy for the first test, just like abi-cafe does
+    let mut buffers: Vec<Buffer> = (0..6).map(|_| vec![vec![vec![]]]).collect();
+    let ptrs: Vec<WriteBuffer> = buffers
+        .iter_mut()
+        .map(|buffer| WriteBuffer(buffer as *mut Buffer as *mut ()))
+        .collect();
+
+    test_start(
+        write_field,
+        finished_val,
+        finished_func,
+        ptrs[0],
+        ptrs[1],
+        ptrs[2],
+        ptrs[3],
+        ptrs[4],
+        ptrs[5],
+    );
+
+    let mut out = String::new();
+    for buffer in &buffers {
+        out.push_str("buffer\n");
+        for func in buffer {
+            out.push_str("func\n");
+            for val in func {
+                out.push_str("val\n");
+                for field in val {
+                    out.push_str("field ");
+                    for byte in field {
+                        out.push_str(&format!("{:02x}", byte));
+                    }
+                    out.push('\n');
+                }
+            }
+        }
+    }
+    print!("{}", out);
+}
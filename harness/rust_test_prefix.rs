@@ -13,6 +13,8 @@ extern {
     pub static mut CALLER_OUTPUTS: WriteBuffer;
     pub static mut CALLEE_INPUTS: WriteBuffer;
     pub static mut CALLEE_OUTPUTS: WriteBuffer;
+    pub static mut CALLER_POSTCALL: WriteBuffer;
+    pub static mut CALLEE_POSTCALL: WriteBuffer;
     pub static mut WRITE_FIELD: Option<WriteCallback>;
     pub static mut FINISHED_VAL: Option<FinishedValCallback>;
     pub static mut FINISHED_FUNC: Option<FinishedFuncCallback>;
@@ -0,0 +1,36 @@
+const std = @import("std");
+
+const WriteBuffer = ?*anyopaque;
+
+const WriteCallback = *const fn (WriteBuffer, [*]const u8, u32) callconv(.C) void;
+const FinishedValCallback = *const fn (WriteBuffer) callconv(.C) void;
+const FinishedFuncCallback = *const fn (WriteBuffer, WriteBuffer) callconv(.C) void;
+
+extern var CALLER_INPUTS: WriteBuffer;
+extern var CALLER_OUTPUTS: WriteBuffer;
+extern var CALLEE_INPUTS: WriteBuffer;
+extern var CALLEE_OUTPUTS: WriteBuffer;
+extern var CALLER_POSTCALL: WriteBuffer;
+extern var CALLEE_POSTCALL: WriteBuffer;
+extern var WRITE_FIELD: ?WriteCallback;
+extern var FINISHED_VAL: ?FinishedValCallback;
+extern var FINISHED_FUNC: ?FinishedFuncCallback;
+
+fn writeField(buffer: WriteBuffer, bytes: []const u8) void {
+    WRITE_FIELD.?(buffer, bytes.ptr, @intCast(bytes.len));
+}
+
+fn finishedVal(buffer: WriteBuffer) void {
+    FINISHED_VAL.?(buffer);
+}
+
+fn finishedFunc(inputs: WriteBuffer, outputs: WriteBuffer) void {
+    FINISHED_FUNC.?(inputs, outputs);
+}
+
+// Truncate a value to the width of a platform-dependent int type (like a cast in C).
+fn truncInt(comptime T: type, bits: u64) T {
+    const U = std.meta.Int(.unsigned, @bitSizeOf(T));
+    return @bitCast(@as(U, @truncate(bits)));
+}
+
@@ -2,20 +2,31 @@
 
 // Backends that can generate + compile an implementation's code into a staticlib.
 pub mod c;
+pub mod cxx;
 pub mod rust;
+pub mod zig;
 
 use super::report::BuildError;
+use std::collections::HashMap;
+use std::fmt::Debug;
 use std::io::Write;
 use std::path::Path;
 
-pub use c::CcAbiImpl;
+pub use c::{CcAbiImpl, CustomCCompiler};
+pub use cxx::CxxAbiImpl;
 pub use rust::RustcAbiImpl;
+pub use zig::ZigAbiImpl;
 
 pub static ABI_IMPL_RUSTC: &str = "rustc";
 pub static ABI_IMPL_CC: &str = "cc";
 pub static ABI_IMPL_GCC: &str = "gcc";
 pub static ABI_IMPL_CLANG: &str = "clang";
 pub static ABI_IMPL_MSVC: &str = "msvc";
+pub static ABI_IMPL_CXX: &str = "cxx";
+pub static ABI_IMPL_GXX: &str = "gxx";
+pub static ABI_IMPL_CLANGXX: &str = "clangxx";
+pub static ABI_IMPL_ZIG: &str = "zig";
+pub static ABI_IMPL_ZIGCC: &str = "zigcc";
 
 // pub static ALL_ABIS: &[AbiRef] = &[RUST_ABI, C_ABI];
 pub static ALL_CONVENTIONS: &[CallingConvention] = &[
@@ -25,6 +36,7 @@ pub static ALL_CONVENTIONS: &[CallingConvention] = &[
     CallingConvention::Stdcall,
     CallingConvention::Fastcall,
     CallingConvention::Vectorcall,
+    CallingConvention::Rust,
     // Note sure if these have a purpose, so omitting them for now
     // CallingConvention::System,
     // CallingConvention::Win64,
@@ -46,15 +58,93 @@ pub static FIELD_NAMES: &[&str] = &[
     "field17", "field18", "field19", "field20", "field21", "field22", "field23", "field24",
     "field25", "field26", "field27", "field28", "field29", "field30", "field31", "field32",
 ];
+pub static VARIANT_NAMES: &[&str] = &[
+    "Variant0",
+    "Variant1",
+    "Variant2",
+    "Variant3",
+    "Variant4",
+    "Variant5",
+    "Variant6",
+    "Variant7",
+    "Variant8",
+    "Variant9",
+    "Variant10",
+    "Variant11",
+    "Variant12",
+    "Variant13",
+    "Variant14",
+    "Variant15",
+    "Variant16",
+    "Variant17",
+    "Variant18",
+    "Variant19",
+    "Variant20",
+    "Variant21",
+    "Variant22",
+    "Variant23",
+    "Variant24",
+    "Variant25",
+    "Variant26",
+    "Variant27",
+    "Variant28",
+    "Variant29",
+    "Variant30",
+    "Variant31",
+    "Variant32",
+];
+pub static TAG_NAME: &str = "tag";
+pub static PAYLOAD_NAME: &str = "payload";
 pub static OUTPUT_NAME: &str = "output";
 pub static OUT_PARAM_NAME: &str = "out";
+pub static VARARGS_NAME: &str = "varargs";
+/// The byte `--poison` mode fills unused bits with.
+pub static POISON_BYTE: u8 = 0xa5;
+
+/// A pointer-sized (or smaller) word of [`POISON_BYTE`][]s.
+pub fn poison_word() -> u64 {
+    u64::from_ne_bytes([POISON_BYTE; 8])
+}
+
+/// The name of the var that holds a function's `idx`th output.
+pub fn output_name(idx: usize) -> String {
+    if idx == 0 {
+        OUTPUT_NAME.to_string()
+    } else {
+        format!("{OUTPUT_NAME}{idx}")
+    }
+}
+
+/// The name of the out-param a function's `idx`th output is passed back through.
+pub fn out_param_name(idx: usize) -> String {
+    if idx == 0 {
+        OUT_PARAM_NAME.to_string()
+    } else {
+        format!("{OUT_PARAM_NAME}{idx}")
+    }
+}
 
 /// ABI is probably a bad name for this... it's like, a language/compiler impl. idk.
 pub trait AbiImpl {
     fn name(&self) -> &'static str;
     fn lang(&self) -> &'static str;
     fn src_ext(&self) -> &'static str;
+    /// The target triple this impl builds for.
+    ///
+    /// Only impls that build for the same target can be paired up, and only
+    /// tests built for the host can actually be run.
+    fn target(&self) -> &'static str {
+        crate::built_info::TARGET
+    }
     fn supports_convention(&self, _convention: CallingConvention) -> bool;
+    /// Whether this impl can express the type of this value (ignoring any subvalues).
+    ///
+    /// Tests that use a type an impl doesn't support are skipped for that impl.
+    fn supports_val(&self, _val: &Val) -> bool;
+    /// Whether this impl can define this function (and not just call it).
+    ///
+    /// Tests with a function an impl can't define are skipped when it's the callee.
+    fn supports_callee(&self, _func: &Func) -> bool;
 
     fn generate_callee(
         &self,
@@ -84,7 +174,7 @@ pub enum GenerateError {
         width=.2.position.col.saturating_sub(1),
     )]
     ParseError(String, String, ron::error::Error),
-    #[error("Two structs had the name {name}, but different layout! \nExpected {old_decl} \nGot {new_decl}")]
+    #[error("Two structs/unions had the name {name}, but different layout! \nExpected {old_decl} \nGot {new_decl}")]
     InconsistentStructDefinition {
         name: String,
         old_decl: String,
@@ -98,17 +188,31 @@ pub enum GenerateError {
     RustUnsupported(String),
     #[error("Unsupported Signature For C: {0}")]
     CUnsupported(String),
+    #[error("Unsupported Signature For C++: {0}")]
+    CxxUnsupported(String),
+    #[error("Unsupported Signature For Zig: {0}")]
+    ZigUnsupported(String),
     #[error("ABI impl doesn't support this calling convention.")]
     UnsupportedConvention,
-    /// Used to signal we just skipped it
-    #[error("<skipped>")]
-    Skipped,
+    /// Used to signal we just skipped it, and why
+    #[error("{0}")]
+    Skipped(String),
+    #[error("Couldn't build a value from the test's types: {0}")]
+    TypeError(String),
+    #[error("{0} is variadic, so it can only use the C or cdecl conventions")]
+    VariadicConvention(String),
 }
 
 /// A test, containing several subtests, each its own function
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub struct Test {
     pub name: String,
+    /// Named types that values can refer to with [`Val::Named`][].
+    ///
+    /// These are resolved into normal values when the test is loaded
+    /// (see [`Test::resolve_types`][]), so nothing else has to care about them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<TypeDecl>,
     pub funcs: Vec<Func>,
 }
 
@@ -119,7 +223,63 @@ pub struct Func {
     pub name: String,
     pub conventions: Vec<CallingConvention>,
     pub inputs: Vec<Val>,
-    pub output: Option<Val>,
+    /// Extra inputs passed through C varargs (`...`), after the fixed `inputs`.
+    ///
+    /// If this isn't empty the function is variadic, which needs at least one
+    /// fixed input and a C/cdecl convention (`All` only expands to those for it,
+    /// and any other convention is rejected when the test is loaded). The values get C's default argument
+    /// promotions (see [`Val::va_promoted`][]) on the way in, but are reported
+    /// (and checked) as the type they were written as.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variadic_inputs: Vec<Val>,
+    /// The values the function returns.
+    ///
+    /// The first output is returned normally (or through an implicit out-param
+    /// if it's a [`Val::Ref`][]), and every output after it is passed back through
+    /// an out-param of its own (see [`Func::lowered_outputs`][]). Returning a
+    /// struct is just a single output.
+    ///
+    /// For compatibility this can also be written as an `Option`: `None` or `Some(val)`.
+    #[serde(default, deserialize_with = "deserialize_outputs")]
+    pub output: Vec<Val>,
+}
+
+/// Deserialize [`Func::output`][] from either a list of values or an `Option`.
+fn deserialize_outputs<'de, D>(deserializer: D) -> Result<Vec<Val>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct OutputsVisitor;
+    impl<'de> serde::de::Visitor<'de> for OutputsVisitor {
+        type Value = Vec<Val>;
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "a list of values, None, or Some(value)")
+        }
+        fn visit_unit<E>(self) -> Result<Vec<Val>, E> {
+            Ok(vec![])
+        }
+        fn visit_none<E>(self) -> Result<Vec<Val>, E> {
+            Ok(vec![])
+        }
+        fn visit_some<D>(self, deserializer: D) -> Result<Vec<Val>, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let val = serde::Deserialize::deserialize(deserializer)?;
+            Ok(vec![val])
+        }
+        fn visit_seq<A>(self, mut seq: A) -> Result<Vec<Val>, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            let mut vals = vec![];
+            while let Some(val) = seq.next_element()? {
+                vals.push(val);
+            }
+            Ok(vals)
+        }
+    }
+    deserializer.deserialize_any(OutputsVisitor)
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
@@ -154,6 +314,11 @@ pub enum CallingConvention {
     /// MSCV `__vectorcall`
     /// GCC/Clang `__attribute__((vectorcall))`
     Vectorcall,
+    /// Rust's own (unstable) convention, `extern "Rust"`
+    ///
+    /// Only Rust impls support this, it's for checking that different rustc
+    /// versions and codegen backends agree with each other.
+    Rust,
 }
 
 /// A typed value.
@@ -165,6 +330,13 @@ pub enum Val {
     /// If a Ref val is used as the return value for a function, it will
     /// implicitly introduce an outparam that the callee memcpy's to.
     Ref(Box<Val>),
+    /// A mutable pass-by-ref (only as an input), with the pointee before the call
+    /// and the value the callee writes through it (which must be the same type).
+    ///
+    /// The "before" value is checked like a `Ref`, and the caller then reports what
+    /// it sees after the call returns, which is checked against what the callee wrote.
+    /// This catches the callee getting a copy instead of the caller's memory.
+    MutRef(Box<Val>, Box<Val>),
     /// Some integer
     Int(IntVal),
     /// Some float
@@ -178,17 +350,73 @@ pub enum Val {
     /// course this is pointless when the other half of the equation pukes.
     ///
     /// FIXME: it isn't currently enforced that this is homogeneous, anything that needs
-    /// the type of the elements just grabs the type of element 0
+    /// the type of the elements just grabs the type of element 0 (arrays built from
+    /// the test's declared types always are, see [`Ty::Array`][]).
     ///
-    /// FIXME: it's illegal to have an array of length 0, because it's impossible to
-    /// attach a type to it
+    /// Arrays of length 0 can't have a type attached this way, use `EmptyArray` for those.
     Array(Vec<Val>),
+    /// An array of length 0, whose element type is taken from the given value
+    ///
+    /// The value itself is never passed or checked, it's just there for its type.
+    /// Zero-length arrays are a GNU C extension, but they're a common way to write
+    /// a trailing "flexible" member, and they still affect the alignment of their struct.
+    EmptyArray(Box<Val>),
     /// A named struct (heterogeneous type)
     ///
     /// Struct decls are implicitly derived from their usage as a value.
     /// If any two structs claim the same name but have different layouts,
     /// the ABI backends should notice this and return an error.
+    ///
+    /// Structs with no fields are allowed. They're size 0 in GNU C (and Rust),
+    /// but size 1 in C++ and not allowed at all in MSVC's C, so they're a classic
+    /// way to get two sides of an FFI boundary to disagree.
     Struct(String, Vec<Val>),
+    /// A named struct with explicit layout attributes, otherwise just like a `Struct`.
+    ///
+    /// Packed structs are the interesting case here, because their fields can't be
+    /// passed around in the registers they'd normally get, and over-aligned structs
+    /// tend to get passed on the stack (or indirectly) with their extra alignment.
+    ReprStruct(String, StructRepr, Vec<Val>),
+    /// A C bitfield of the given width (in bits), with its base integer type and value.
+    ///
+    /// These can only be the fields of a struct. C declares them natively, while Rust
+    /// has to emulate them like bindgen does: each run of consecutive bitfields becomes
+    /// a byte array with the bits wherever the C compiler should have put them (which
+    /// is only implemented for the SysV rules). They're checked as their base type.
+    Bitfield(IntVal, u32),
+    /// A named `#[repr(transparent)]` newtype around another value.
+    ///
+    /// Rust emits this as a tuple struct, while C just typedefs the inner type,
+    /// since being ABI-identical to the inner type is exactly what `repr(transparent)`
+    /// promises. It's checked as the inner value. Like structs, the name must be used
+    /// consistently. C can't wrap arrays this way (they'd decay to pointers).
+    Transparent(String, Box<Val>),
+    /// A named union, with a value for every variant, and the index of
+    /// the variant that is actually "active".
+    ///
+    /// Like structs, union decls are implicitly derived from their usage.
+    /// Every variant needs a value so that we know its type, but only the
+    /// active variant is used to initialize the union and only its bytes
+    /// are checked (the others would just be reading garbage).
+    Union(String, Vec<Val>, usize),
+    /// A named fieldless enum, with the discriminant of every variant, and the
+    /// index of the variant that is the actual value.
+    ///
+    /// The discriminants' IntVal type is the enum's explicit repr type
+    /// (e.g. `c_uint8_t` means `#[repr(u8)]`), taken from variant 0.
+    /// C has no way to specify the repr of an enum, so it just uses
+    /// the integer type directly.
+    Enum(String, Vec<IntVal>, usize),
+    /// A named data-carrying enum, with the discriminant and fields of every variant,
+    /// and the index of the variant that is the actual value.
+    ///
+    /// Rust emits this as a `#[repr(C, u8)]` (or whatever the discriminant type is) enum,
+    /// while C emits the equivalent layout as described by RFC 2195: a struct containing
+    /// the tag followed by a union of one struct per variant. Variants with no fields are
+    /// omitted from the union.
+    ///
+    /// Only the tag and the fields of the active variant are checked.
+    TaggedUnion(String, Vec<(IntVal, Vec<Val>)>, usize),
     /// An opaque pointer
     ///
     /// FIXME?: it's gross to just pick "u64" as the type here when ostensibly it would
@@ -196,14 +424,343 @@ pub enum Val {
     /// make sense either because we're slurping these values out of a static config file!
     /// I guess just truncating the pointer is "fine".
     Ptr(u64),
-    // TODO: unions. This is hard to do with the current design where
-    // types are implicit in their values. You could maybe hack it in
-    // by having dummy vals for all the different cases and then a
-    // "real" value for the variant that's actually used, but, kinda gross.
-    //
-    // TODO: simd vectors (they have special passing rules!)
-    //
-    // TODO: enums (enum classes?)
+    /// A SIMD vector
+    ///
+    /// These have their own special passing rules in most conventions, and the wider
+    /// ones can only be passed in registers if the relevant target feature is enabled,
+    /// so the generators will enable it on every function that uses them.
+    Vector(VectorVal),
+    /// A named function pointer type, with the values it gets called with,
+    /// and the value it returns.
+    ///
+    /// The caller generates a function with this signature and passes it to the
+    /// callee, which calls it with the given inputs (after reporting its own inputs).
+    /// The callback uses the same convention as the test, so both directions get
+    /// checked: the callback's inputs/outputs are reported as extra values of the
+    /// function it was passed to, see [`Func::callbacks`][].
+    ///
+    /// Like structs, the name is a nominal type that must be used consistently.
+    /// These can only be passed directly as arguments (not nested in other values).
+    FnPtr(String, Vec<Val>, Option<Box<Val>>),
+    /// A Rust `Option` that's guaranteed to be passed exactly like a nullable C type
+    /// (the "null pointer optimization"), and whether it's `Some`.
+    ///
+    /// The inner value must be one of:
+    ///
+    /// * `Ref(T)`: `Option<&T>`, a nullable `T*` in C (only as a direct input, like `Ref`)
+    /// * `Ptr(addr)`: `Option<NonNull<()>>`, a nullable `void*` in C
+    /// * `Int(val)`: `Option<NonZero<T>>`, a plain integer in C that's 0 for `None`
+    /// * `FnPtr(..)`: `Option<extern fn>`, a nullable function pointer in C
+    ///   (the callee only calls it if it's `Some`)
+    ///
+    /// If it's `None`, the inner value is only used for its type (and for `Some`
+    /// it can't be null/zero). Both sides report the raw pointer/integer, except
+    /// that a `Some` ref or function pointer is reported like the non-nullable version.
+    Nullable(Box<Val>, bool),
+
+    // These are Rust-only types (with no C equivalent), for checking that
+    // different rustc versions and codegen backends agree on `extern "Rust"`.
+    // Most of their layouts are unspecified, so they're checked by their parts.
+    /// A `char`, checked as its `u32` value.
+    Char(char),
+    /// The unit type `()`, which has nothing to check.
+    Unit,
+    /// A tuple, checked field by field like a struct.
+    Tuple(Vec<Val>),
+    /// A `&'static [T]` of the elements of the given `Array` (or `EmptyArray`),
+    /// checked as its length followed by its elements.
+    Slice(Box<Val>),
+    /// A `&'static str`, checked as its length followed by its bytes.
+    Str(String),
+    /// A `Box<T>`, checked as its pointee.
+    ///
+    /// These aren't `Copy`, so they can't be fields of structs, unions, or enums.
+    Boxed(Box<Val>),
+    /// An `Option<T>` and whether it's `Some`, checked as a bool and then the value
+    /// if it's `Some` (otherwise the value is only used for its type).
+    ///
+    /// Unlike [`Val::Nullable`][], this can be anything, it's mostly interesting for
+    /// types with a niche (`bool`, `char`, `&str`, `Box<T>`, fieldless enums) where the
+    /// `Option` is guaranteed to be the same size but is otherwise unspecified.
+    RustOption(Box<Val>, bool),
+
+    // These are C++-only types (with no C equivalent), for checking that C++ compilers
+    // agree on the parts of their ABIs that leak through `extern "C"` functions.
+    /// A C++ reference (`T&`), which is passed exactly like a pointer (only as a direct input).
+    ///
+    /// Other impls just see a [`Val::Ref`][], so pairing C++ with them checks that
+    /// a `T&` param really is interchangeable with a pointer.
+    CxxRef(Box<Val>),
+    /// A named C++ class, otherwise just like a `Struct`, but its leading fields can be
+    /// [`Val::Base`][]es and its fields can be [`Val::NoUniqueAddress`][].
+    ///
+    /// Like structs, the name must be used consistently.
+    Class(String, ClassRepr, Vec<Val>),
+    /// A base class of a [`Val::Class`][] (a struct or another class), given as one
+    /// of its leading fields, and checked like one.
+    ///
+    /// Empty bases take up no space (the "empty base optimization"), unlike empty fields.
+    Base(Box<Val>),
+    /// A field of a [`Val::Class`][] that's `[[no_unique_address]]`, which lets
+    /// it overlap other fields if it's empty (or reuse its own tail padding).
+    NoUniqueAddress(Box<Val>),
+
+    /// A value of a type declared in the test's `types` section, which only supplies
+    /// the data, see [`Data`][] for what that looks like for each type.
+    ///
+    /// These are replaced by the full value when the test is loaded.
+    Named(String, Data),
+}
+
+/// The layout attributes of a [`Val::ReprStruct`][].
+///
+/// A struct can't be both packed and over-aligned (Rust forbids it, and C
+/// compilers disagree about what it means).
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct StructRepr {
+    /// `#[repr(packed(N))]`, `#pragma pack(N)` in C: the max alignment of any field.
+    #[serde(default)]
+    pub packed: Option<u32>,
+    /// `#[repr(align(N))]`, `_Alignas(N)` in C: the min alignment of the struct.
+    #[serde(default)]
+    pub align: Option<u32>,
+}
+
+/// The special members of a [`Val::Class`][].
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ClassRepr {
+    /// Whether it has a user-provided destructor, which makes it "non-trivial for the
+    /// purposes of calls", so it's passed and returned by invisible reference even if
+    /// it would otherwise fit in registers (it's still an aggregate, though).
+    #[serde(default)]
+    pub non_trivial: bool,
+}
+
+/// A named type declared in a test's `types` section.
+///
+/// These are the same as the corresponding [`Val`][]s, but with types
+/// in place of values. Values of these types are given with [`Val::Named`][].
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub enum TypeDecl {
+    /// A struct with the given field types, see [`Val::Struct`][].
+    Struct(String, Vec<Ty>),
+    /// A struct with explicit layout attributes, see [`Val::ReprStruct`][].
+    ReprStruct(String, StructRepr, Vec<Ty>),
+    /// A `#[repr(transparent)]` newtype, see [`Val::Transparent`][].
+    Transparent(String, Ty),
+    /// A union with the given variant types, see [`Val::Union`][].
+    Union(String, Vec<Ty>),
+    /// A fieldless enum with the given discriminants, see [`Val::Enum`][].
+    Enum(String, Vec<IntVal>),
+    /// A data-carrying enum with the given discriminants and field types,
+    /// see [`Val::TaggedUnion`][].
+    TaggedUnion(String, Vec<(IntVal, Vec<Ty>)>),
+    /// A C++ class with the given field types, see [`Val::Class`][].
+    Class(String, ClassRepr, Vec<Ty>),
+    /// Another name for a type, which is just replaced by that type.
+    Alias(String, Ty),
+}
+
+/// A type, for use in a [`TypeDecl`][].
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub enum Ty {
+    Int(IntTy),
+    Float(FloatTy),
+    Bool,
+    Ptr,
+    Vector(VectorTy),
+    /// A pass-by-ref of the given type, see [`Val::Ref`][].
+    Ref(Box<Ty>),
+    /// A mutable pass-by-ref of the given type, see [`Val::MutRef`][].
+    MutRef(Box<Ty>),
+    /// An array of the given type and length (which can be 0).
+    Array(Box<Ty>, usize),
+    /// A bitfield with the given base type and width, see [`Val::Bitfield`][].
+    Bitfield(IntTy, u32),
+    /// A nullable-pointer-optimized `Option` of the given type, see [`Val::Nullable`][].
+    Nullable(Box<Ty>),
+    /// A Rust `char`, see [`Val::Char`][].
+    Char,
+    /// The unit type, see [`Val::Unit`][].
+    Unit,
+    /// A tuple of the given types, see [`Val::Tuple`][].
+    Tuple(Vec<Ty>),
+    /// A slice of the given type, of any length, see [`Val::Slice`][].
+    Slice(Box<Ty>),
+    /// A `&str`, see [`Val::Str`][].
+    Str,
+    /// A `Box` of the given type, see [`Val::Boxed`][].
+    Boxed(Box<Ty>),
+    /// A Rust `Option` of the given type, see [`Val::RustOption`][].
+    RustOption(Box<Ty>),
+    /// A C++ reference to the given type, see [`Val::CxxRef`][].
+    CxxRef(Box<Ty>),
+    /// A base class of a C++ class, see [`Val::Base`][].
+    Base(Box<Ty>),
+    /// A `[[no_unique_address]]` field of a C++ class, see [`Val::NoUniqueAddress`][].
+    NoUniqueAddress(Box<Ty>),
+    /// A type declared in the test's `types` section.
+    Named(String),
+}
+
+/// The type of an [`IntVal`][].
+#[derive(Copy, Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub enum IntTy {
+    c__int128,
+    c_int64_t,
+    c_int32_t,
+    c_int16_t,
+    c_int8_t,
+    c__uint128,
+    c_uint64_t,
+    c_uint32_t,
+    c_uint16_t,
+    c_uint8_t,
+    c_char,
+    c_schar,
+    c_uchar,
+    c_short,
+    c_ushort,
+    c_int,
+    c_uint,
+    c_long,
+    c_ulong,
+    c_longlong,
+    c_ulonglong,
+    c_size_t,
+    c_ptrdiff_t,
+    c_intptr_t,
+    c_uintptr_t,
+    c_wchar_t,
+}
+
+/// The type of a [`FloatVal`][].
+#[derive(Copy, Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub enum FloatTy {
+    c_double,
+    c_float,
+    c__Float16,
+    c__float128,
+    c_long_double,
+    c__Complex_float,
+    c__Complex_double,
+}
+
+/// The type of a [`VectorVal`][].
+#[derive(Copy, Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub enum VectorTy {
+    c__m128,
+    c__m128d,
+    c__m128i,
+    c__m256,
+    c__m256d,
+    c__m256i,
+}
+
+/// The data for a [`Val::Named`][], which is just a plain ron literal.
+///
+/// * ints, floats, bools, and pointers are just numbers (or `true`/`false`)
+/// * the floats that are given as bit patterns (`long double`, etc.) are given the same way
+/// * structs, arrays, and vectors are a list of their fields/elements: `(1, 2.0, (3, 4))`
+/// * complex floats are their real and imaginary parts: `(1.5, -2.0)`
+/// * refs and transparent newtypes are the data of what they wrap
+/// * mutable refs are the data before and after the call: `(1, 2)`
+/// * nullable options (and Rust options) are `Some(data)` or `None`
+/// * tuples and slices are a list of their elements (slices can be any length)
+/// * chars and strs are a string: `"a"`, `"hello"`
+/// * boxes are the data of what they wrap, and the unit type is `()`
+/// * fieldless enums are the index of the variant
+/// * unions are the index of the active variant and its data: `(1, 2.5)`
+/// * data-carrying enums are the index of the variant and a list of its fields: `(1, (2, 3))`
+///
+/// Lists can be written with either `()` or `[]`.
+#[derive(Clone, Debug)]
+pub enum Data {
+    Bool(bool),
+    Int(i128),
+    UInt(u128),
+    Float(f64),
+    List(Vec<Data>),
+    Option(Option<Box<Data>>),
+    Str(String),
+}
+
+impl<'de> serde::Deserialize<'de> for Data {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct DataVisitor;
+        impl<'de> serde::de::Visitor<'de> for DataVisitor {
+            type Value = Data;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a number, bool, string, or list")
+            }
+            fn visit_bool<E>(self, v: bool) -> Result<Data, E> {
+                Ok(Data::Bool(v))
+            }
+            fn visit_i64<E>(self, v: i64) -> Result<Data, E> {
+                Ok(Data::Int(v as i128))
+            }
+            fn visit_i128<E>(self, v: i128) -> Result<Data, E> {
+                Ok(Data::Int(v))
+            }
+            fn visit_u64<E>(self, v: u64) -> Result<Data, E> {
+                Ok(Data::UInt(v as u128))
+            }
+            fn visit_u128<E>(self, v: u128) -> Result<Data, E> {
+                Ok(Data::UInt(v))
+            }
+            fn visit_f64<E>(self, v: f64) -> Result<Data, E> {
+                Ok(Data::Float(v))
+            }
+            fn visit_str<E>(self, v: &str) -> Result<Data, E> {
+                Ok(Data::Str(v.to_string()))
+            }
+            fn visit_unit<E>(self) -> Result<Data, E> {
+                Ok(Data::List(vec![]))
+            }
+            fn visit_none<E>(self) -> Result<Data, E> {
+                Ok(Data::Option(None))
+            }
+            fn visit_some<D>(self, deserializer: D) -> Result<Data, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                let data = serde::Deserialize::deserialize(deserializer)?;
+                Ok(Data::Option(Some(Box::new(data))))
+            }
+            fn visit_seq<A>(self, mut seq: A) -> Result<Data, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut items = vec![];
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(Data::List(items))
+            }
+        }
+        deserializer.deserialize_any(DataVisitor)
+    }
+}
+
+impl serde::Serialize for Data {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Data::Bool(v) => serializer.serialize_bool(*v),
+            Data::Int(v) => serializer.serialize_i128(*v),
+            Data::UInt(v) => serializer.serialize_u128(*v),
+            Data::Float(v) => serializer.serialize_f64(*v),
+            Data::List(items) => serializer.collect_seq(items),
+            Data::Option(Some(data)) => serializer.serialize_some(data),
+            Data::Option(None) => serializer.serialize_none(),
+            Data::Str(v) => serializer.serialize_str(v),
+        }
+    }
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
@@ -219,15 +776,74 @@ pub enum IntVal {
     c_uint32_t(u32),
     c_uint16_t(u16),
     c_uint8_t(u8),
-    // TODO: nastier platform-specific-layout c-types?
-    // i.e. c_int(i64), c_long(i32), char(i8), ...
+
+    // The platform-specific-layout c-types. These hold a value wide enough for
+    // any platform, and the generators truncate it to the platform's width
+    // (so a value that doesn't fit in a 32-bit `long` will be caught by a
+    // Windows implementation that disagrees with the other side about it).
+    c_char(i64),
+    c_schar(i64),
+    c_uchar(u64),
+    c_short(i64),
+    c_ushort(u64),
+    c_int(i64),
+    c_uint(u64),
+    c_long(i64),
+    c_ulong(u64),
+    c_longlong(i64),
+    c_ulonglong(u64),
+    c_size_t(u64),
+    c_ptrdiff_t(i64),
+    c_intptr_t(i64),
+    c_uintptr_t(u64),
+    c_wchar_t(i64),
+}
+
+/// The lanes of a SIMD vector
+///
+/// These are just the x86 vendor types for now (`core::arch` in Rust, `immintrin.h` in C).
+/// The integer vectors don't really have a lane type, so we arbitrarily pick u32 lanes.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub enum VectorVal {
+    c__m128([f32; 4]),
+    c__m128d([f64; 2]),
+    c__m128i([u32; 4]),
+    c__m256([f32; 8]),
+    c__m256d([f64; 4]),
+    c__m256i([u32; 8]),
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub enum FloatVal {
     c_double(f64),
     c_float(f32),
-    // Is there a reason to mess with `long double`? Surely not.
+    /// `_Float16` (`f16` in Rust), as its exact bit pattern.
+    c__Float16(u16),
+    /// `__float128` (`f128` in Rust), as its exact bit pattern.
+    c__float128(u128),
+    /// `long double`, as its exact bit pattern in the platform's [`LongDoubleFormat`][].
+    ///
+    /// Only the low bytes that the format actually uses are kept, so pick a pattern that
+    /// means something in all of them (x87 wants the explicit integer bit, bit 63, set).
+    c_long_double(u128),
+    /// `_Complex float`, as its real and imaginary parts.
+    ///
+    /// Rust doesn't have complex numbers, so it uses the conventional
+    /// `#[repr(C)] struct { re: f32, im: f32 }`, which isn't always passed the same way.
+    c__Complex_float(f32, f32),
+    /// `_Complex double`, as its real and imaginary parts (see `c__Complex_float`).
+    c__Complex_double(f64, f64),
+}
+
+/// The layout of the platform's `long double`, which is all over the place.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LongDoubleFormat {
+    /// Just another name for `double` (MSVC, Apple ARM64, 32-bit ARM, ...)
+    Double,
+    /// x87's 80-bit extended precision, padded out to 12 or 16 bytes
+    X87,
+    /// IEEE binary128, the same as `__float128` (most other 64-bit linux targets)
+    Quad,
 }
 
 impl CallingConvention {
@@ -246,6 +862,7 @@ impl CallingConvention {
             CallingConvention::Stdcall => "stdcall",
             CallingConvention::Fastcall => "fastcall",
             CallingConvention::Vectorcall => "vectorcall",
+            CallingConvention::Rust => "rust",
         }
     }
     pub fn from_str(input: &str) -> Option<Self> {
@@ -261,17 +878,692 @@ impl CallingConvention {
             "stdcall" => CallingConvention::Stdcall,
             "fastcall" => CallingConvention::Fastcall,
             "vectorcall" => CallingConvention::Vectorcall,
+            "rust" => CallingConvention::Rust,
             _ => return None,
         })
     }
 }
 
+/// The compiler settings an impl is instantiated with (see `--flag-matrix`),
+/// which each impl maps onto its own compiler's flags as best it can.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FlagProfile {
+    /// `-C opt-level`, `-O` in C: one of 0, 1, 2, 3, s, or z.
+    pub opt_level: String,
+    /// `-C target-cpu`, `-march` in C: the cpu whose features the code can use.
+    pub target_cpu: Option<String>,
+    /// Whether to build position-independent code (`-fPIC`).
+    pub pic: bool,
+    /// `-C panic`: the panic strategy (only Rust has one).
+    pub panic: Option<String>,
+}
+
+impl Default for FlagProfile {
+    /// What everything was always built with: unoptimized, generic, and PIC.
+    fn default() -> Self {
+        Self {
+            opt_level: "0".to_string(),
+            target_cpu: None,
+            pic: true,
+            panic: None,
+        }
+    }
+}
+
+impl FlagProfile {
+    /// Parse a profile like `O2,cpu=native,no-pic,panic=abort`.
+    ///
+    /// Anything that isn't mentioned keeps its default.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut profile = Self::default();
+        for setting in input.split(',').map(str::trim) {
+            match setting.split_once('=') {
+                None if setting.starts_with('O') => {
+                    let opt_level = &setting[1..];
+                    if !["0", "1", "2", "3", "s", "z"].contains(&opt_level) {
+                        return Err(format!("unknown opt level {setting}"));
+                    }
+                    profile.opt_level = opt_level.to_string();
+                }
+                None if setting == "pic" => profile.pic = true,
+                None if setting == "no-pic" => profile.pic = false,
+                Some(("cpu", cpu)) if !cpu.is_empty() => profile.target_cpu = Some(cpu.to_string()),
+                Some(("panic", panic @ ("unwind" | "abort"))) => {
+                    profile.panic = Some(panic.to_string())
+                }
+                _ => return Err(format!("unknown setting {setting}")),
+            }
+        }
+        Ok(profile)
+    }
+
+    /// The profile's name, safe to put in library and file names.
+    pub fn id(&self) -> String {
+        self.to_string()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect()
+    }
+}
+
+impl serde::Serialize for FlagProfile {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl std::fmt::Display for FlagProfile {
+    /// The same syntax [`FlagProfile::parse`][] takes, with the defaults left out.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "O{}", self.opt_level)?;
+        if let Some(cpu) = &self.target_cpu {
+            write!(f, ",cpu={cpu}")?;
+        }
+        if !self.pic {
+            write!(f, ",no-pic")?;
+        }
+        if let Some(panic) = &self.panic {
+            write!(f, ",panic={panic}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The parts of a target triple that decide the platform-specific
+/// details of C's types and calling conventions.
+///
+/// The arch is normalized to what `cfg(target_arch)` would say for it,
+/// so `i686` is `x86`, `armv7` is `arm`, and so on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TargetTriple<'a> {
+    pub arch: &'static str,
+    pub big_endian: bool,
+    pub triple: &'a str,
+}
+
+impl<'a> TargetTriple<'a> {
+    pub fn new(triple: &'a str) -> Self {
+        let raw_arch = triple.split('-').next().unwrap_or_default();
+        let little = raw_arch.ends_with("le") || raw_arch.ends_with("el");
+        let (arch, big_endian) = match raw_arch {
+            "x86_64" => ("x86_64", false),
+            "i386" | "i586" | "i686" | "x86" => ("x86", false),
+            "aarch64" | "arm64" | "arm64e" => ("aarch64", false),
+            "aarch64_be" => ("aarch64", true),
+            a if a.starts_with("arm") || a.starts_with("thumb") => ("arm", a.contains("eb")),
+            a if a.starts_with("powerpc64") => ("powerpc64", !little),
+            a if a.starts_with("powerpc") => ("powerpc", true),
+            a if a.starts_with("riscv64") => ("riscv64", false),
+            a if a.starts_with("riscv32") => ("riscv32", false),
+            a if a.starts_with("mips64") => ("mips64", !little),
+            a if a.starts_with("mips") => ("mips", !little),
+            "s390x" => ("s390x", true),
+            "sparc64" => ("sparc64", true),
+            "loongarch64" => ("loongarch64", false),
+            "wasm32" => ("wasm32", false),
+            _ => ("unknown", cfg!(target_endian = "big")),
+        };
+        TargetTriple {
+            arch,
+            big_endian,
+            triple,
+        }
+    }
+
+    pub fn is_windows(&self) -> bool {
+        self.triple.contains("-windows")
+    }
+
+    pub fn is_msvc(&self) -> bool {
+        self.triple.ends_with("-msvc")
+    }
+
+    pub fn is_apple(&self) -> bool {
+        self.triple.contains("-apple-")
+    }
+
+    pub fn is_linux_like(&self) -> bool {
+        self.triple.contains("-linux") || self.triple.contains("-android")
+    }
+
+    /// The size of a pointer (and `size_t`) in bytes.
+    pub fn pointer_size(&self) -> u64 {
+        match self.arch {
+            "x86" | "arm" | "riscv32" | "powerpc" | "mips" | "wasm32" => 4,
+            // x32 is x86_64 with 32-bit pointers
+            _ if self.triple.ends_with("x32") => 4,
+            _ => 8,
+        }
+    }
+
+    /// The size of `long` in bytes: Windows is LLP64, so it's only 4 there.
+    pub fn long_size(&self) -> u64 {
+        if self.is_windows() {
+            4
+        } else {
+            self.pointer_size()
+        }
+    }
+}
+
+/// How `--poison` mode fills the bits of an input that don't hold its value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Poison {
+    /// An aggregate, whose padding is filled in by poisoning the whole thing
+    /// and then writing its fields in one at a time.
+    Padding,
+    /// A narrow int or bool, which is passed as a pointer-sized int
+    /// with everything above its own `bits` poisoned.
+    HighBits { bits: u32, signed: bool },
+}
+
+/// Which side of a call is responsible for extending narrow int/bool args
+/// to a full register, which is what `--check-extension` mode checks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Extender {
+    /// The caller extends them to (at least) `bits`, and the callee can rely on that.
+    Caller { bits: u32 },
+    /// The callee can't assume anything about the bits above the value,
+    /// and has to extend it itself.
+    Callee,
+}
+
+impl Extender {
+    /// The rule for the given target triple.
+    pub fn for_target(target: &str) -> Self {
+        let triple = TargetTriple::new(target);
+        if triple.arch == "aarch64" && triple.is_apple() {
+            // Apple deviates from AAPCS64 here, and only to 32 bits
+            Extender::Caller { bits: 32 }
+        } else if matches!(
+            triple.arch,
+            "riscv64" | "powerpc64" | "s390x" | "mips64" | "loongarch64" | "sparc64"
+        ) {
+            Extender::Caller { bits: 64 }
+        } else if matches!(triple.arch, "riscv32" | "arm" | "powerpc" | "mips") {
+            Extender::Caller { bits: 32 }
+        } else {
+            // This includes x86_64, where the psABI leaves the high bits undefined
+            // but clang (and so rustc) has always extended them to 32 bits and
+            // relied on that. That's exactly the kind of thing we want to catch.
+            Extender::Callee
+        }
+    }
+
+    /// The side this puts in charge, for error messages.
+    pub fn side(&self) -> &'static str {
+        match self {
+            Extender::Caller { .. } => "caller",
+            Extender::Callee => "callee",
+        }
+    }
+}
+
+impl Val {
+    /// If this is an array, get its length and a value with the type of its elements.
+    ///
+    /// `Val::Array`s are never empty (see [`Val::validate`][]).
+    pub fn array_elem(&self) -> Option<(usize, &Val)> {
+        match self {
+            Val::Array(vals) => Some((vals.len(), &vals[0])),
+            Val::EmptyArray(elem) => Some((0, elem)),
+            _ => None,
+        }
+    }
+
+    /// Check that this can be the inner value of a [`Val::Nullable`][].
+    pub fn check_nullable(&self, is_some: bool) -> Result<(), String> {
+        match self {
+            Val::Ptr(0) if is_some => Err("Some can't hold a null pointer".to_string()),
+            Val::Int(int_val) if is_some && int_val.bits() == 0 => {
+                Err("Some can't hold a zero".to_string())
+            }
+            Val::Int(IntVal::c__int128(_) | IntVal::c__uint128(_)) => {
+                Err("128-bit ints can't be nullable".to_string())
+            }
+            Val::Ref(_) | Val::Ptr(_) | Val::Int(_) | Val::FnPtr(..) => Ok(()),
+            _ => {
+                Err("only refs, pointers, ints, and function pointers can be nullable".to_string())
+            }
+        }
+    }
+
+    /// Check this value (and every value nested inside of it) is one the impls
+    /// can actually generate, so they can index into it without checking.
+    pub fn validate(&self) -> Result<(), GenerateError> {
+        let mut error = None;
+        self.visit(&mut |val| {
+            let problem = match val {
+                Val::Union(name, variants, active) if *active >= variants.len() => {
+                    Some(format!("{name} doesn't have a variant {active}"))
+                }
+                // Every impl needs an element to know the array's type
+                Val::Array(vals) if vals.is_empty() => Some(
+                    "an empty Array has no element type, use EmptyArray(elem) instead".to_string(),
+                ),
+                Val::Enum(name, discriminants, active) if *active >= discriminants.len() => {
+                    Some(format!("{name} doesn't have a variant {active}"))
+                }
+                Val::TaggedUnion(name, variants, active) if *active >= variants.len() => {
+                    Some(format!("{name} doesn't have a variant {active}"))
+                }
+                // Every field and variant needs a name from the tables
+                Val::Enum(name, discriminants, _) if discriminants.len() > VARIANT_NAMES.len() => {
+                    Some(format!(
+                        "{name} has more than {} variants",
+                        VARIANT_NAMES.len()
+                    ))
+                }
+                Val::TaggedUnion(name, variants, _) if variants.len() > VARIANT_NAMES.len() => {
+                    Some(format!(
+                        "{name} has more than {} variants",
+                        VARIANT_NAMES.len()
+                    ))
+                }
+                Val::TaggedUnion(name, variants, _)
+                    if variants
+                        .iter()
+                        .any(|(_, fields)| fields.len() > FIELD_NAMES.len()) =>
+                {
+                    Some(format!(
+                        "{name} has a variant with more than {} fields",
+                        FIELD_NAMES.len()
+                    ))
+                }
+                Val::Struct(name, fields)
+                | Val::ReprStruct(name, _, fields)
+                | Val::Union(name, fields, _)
+                | Val::Class(name, _, fields)
+                    if fields.len() > FIELD_NAMES.len() =>
+                {
+                    Some(format!("{name} has more than {} fields", FIELD_NAMES.len()))
+                }
+                _ => None,
+            };
+            error = error.take().or(problem);
+        });
+        match error {
+            Some(error) => Err(GenerateError::TypeError(error)),
+            None => Ok(()),
+        }
+    }
+
+    /// Call `f` on this value and every value nested inside of it.
+    pub fn visit(&self, f: &mut impl FnMut(&Val)) {
+        f(self);
+        match self {
+            Val::Ref(pointee)
+            | Val::EmptyArray(pointee)
+            | Val::Transparent(_, pointee)
+            | Val::Nullable(pointee, _)
+            | Val::Slice(pointee)
+            | Val::Boxed(pointee)
+            | Val::RustOption(pointee, _)
+            | Val::CxxRef(pointee)
+            | Val::Base(pointee)
+            | Val::NoUniqueAddress(pointee) => pointee.visit(f),
+            Val::MutRef(before, after) => {
+                before.visit(f);
+                after.visit(f);
+            }
+            Val::Array(vals)
+            | Val::Struct(_, vals)
+            | Val::ReprStruct(_, _, vals)
+            | Val::Union(_, vals, _)
+            | Val::Tuple(vals)
+            | Val::Class(_, _, vals) => {
+                for val in vals {
+                    val.visit(f);
+                }
+            }
+            Val::TaggedUnion(_, variants, _) => {
+                for (_discriminant, fields) in variants {
+                    for field in fields {
+                        field.visit(f);
+                    }
+                }
+            }
+            Val::FnPtr(_, inputs, output) => {
+                for val in inputs.iter().chain(output.as_deref()) {
+                    val.visit(f);
+                }
+            }
+            Val::Int(_)
+            | Val::Float(_)
+            | Val::Bool(_)
+            | Val::Ptr(_)
+            | Val::Enum(..)
+            | Val::Bitfield(..)
+            | Val::Vector(_)
+            | Val::Char(_)
+            | Val::Unit
+            | Val::Str(_) => {}
+            Val::Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
+        }
+    }
+
+    /// The value this is actually passed as when passed through C varargs (`...`),
+    /// if C's "default argument promotions" make it something else: `float` becomes
+    /// `double`, and anything narrower than an `int` becomes an `int`.
+    pub fn va_promoted(&self, target: &str) -> Option<Val> {
+        match self {
+            Val::Float(FloatVal::c_float(val)) => Some(Val::Float(FloatVal::c_double(*val as f64))),
+            Val::Bool(val) => Some(Val::Int(IntVal::c_int(*val as i64))),
+            Val::Int(val) => val.va_promoted(target).map(Val::Int),
+            Val::Enum(_, discriminants, active) => {
+                discriminants[*active].va_promoted(target).map(Val::Int)
+            }
+            _ => None,
+        }
+    }
+
+    /// How `--poison` mode fills this input's unused bits, if it has any
+    /// (on the given target, which decides whether `char` and `wchar_t` are signed).
+    pub fn poison(&self, target: &str) -> Option<Poison> {
+        match self {
+            Val::Bool(_) => Some(Poison::HighBits {
+                bits: 8,
+                signed: false,
+            }),
+            Val::Int(int_val) => int_val.narrow_bits().map(|bits| Poison::HighBits {
+                bits,
+                signed: int_val.is_signed(target),
+            }),
+            Val::Struct(..) | Val::ReprStruct(..) | Val::Union(..) => {
+                // Rust lays out bitfields by hand, so they can't be written one at a time
+                let mut has_bitfields = false;
+                self.visit(&mut |val| has_bitfields |= matches!(val, Val::Bitfield(..)));
+                (!has_bitfields).then_some(Poison::Padding)
+            }
+            Val::Transparent(_, inner) => inner.poison(target).filter(|&p| p == Poison::Padding),
+            _ => None,
+        }
+    }
+}
+
+impl IntVal {
+    /// The width of this int, if it's narrower than a register (an `int`, really).
+    pub fn narrow_bits(&self) -> Option<u32> {
+        use IntVal::*;
+        match self {
+            c_int8_t(_) | c_uint8_t(_) | c_char(_) | c_schar(_) | c_uchar(_) => Some(8),
+            c_int16_t(_) | c_uint16_t(_) | c_short(_) | c_ushort(_) => Some(16),
+            _ => None,
+        }
+    }
+
+    /// The value's two's complement bit pattern, sign-extended to 128 bits.
+    pub fn bits(&self) -> u128 {
+        use IntVal::*;
+        match *self {
+            c__int128(val) => val as u128,
+            c_int64_t(val) => val as i128 as u128,
+            c_int32_t(val) => val as i128 as u128,
+            c_int16_t(val) => val as i128 as u128,
+            c_int8_t(val) => val as i128 as u128,
+            c__uint128(val) => val,
+            c_uint64_t(val) => val as u128,
+            c_uint32_t(val) => val as u128,
+            c_uint16_t(val) => val as u128,
+            c_uint8_t(val) => val as u128,
+            c_char(val) | c_schar(val) | c_short(val) | c_int(val) | c_long(val)
+            | c_longlong(val) | c_ptrdiff_t(val) | c_intptr_t(val) | c_wchar_t(val) => {
+                val as i128 as u128
+            }
+            c_uchar(val) | c_ushort(val) | c_uint(val) | c_ulong(val) | c_ulonglong(val)
+            | c_size_t(val) | c_uintptr_t(val) => val as u128,
+        }
+    }
+
+    /// Whether this is a signed integer type (on the given target, for `char` and `wchar_t`).
+    pub fn is_signed(&self, target: &str) -> bool {
+        use IntVal::*;
+        let triple = TargetTriple::new(target);
+        match self {
+            c__int128(_) | c_int64_t(_) | c_int32_t(_) | c_int16_t(_) | c_int8_t(_) => true,
+            c_schar(_) | c_short(_) | c_int(_) | c_long(_) | c_longlong(_) => true,
+            c_ptrdiff_t(_) | c_intptr_t(_) => true,
+            c_char(_) => {
+                !(matches!(
+                    triple.arch,
+                    "aarch64" | "arm" | "powerpc" | "powerpc64" | "riscv64" | "s390x"
+                ) && !triple.is_apple()
+                    && !triple.is_windows())
+            }
+            c_wchar_t(_) => {
+                !(triple.is_windows()
+                    || (triple.is_linux_like() && matches!(triple.arch, "aarch64" | "arm")))
+            }
+            _ => false,
+        }
+    }
+
+    /// The `int` this gets promoted to when passed through C varargs, if it's narrower.
+    pub fn va_promoted(&self, target: &str) -> Option<IntVal> {
+        use IntVal::*;
+        let val = match *self {
+            c_int16_t(val) => val as i64,
+            c_int8_t(val) => val as i64,
+            c_uint16_t(val) => val as i64,
+            c_uint8_t(val) => val as i64,
+            c_char(val) | c_schar(val) | c_short(val) => val,
+            c_uchar(val) | c_ushort(val) => val as i64,
+            // wchar_t is only narrower than int on Windows
+            c_wchar_t(val) if TargetTriple::new(target).is_windows() => val,
+            _ => return None,
+        };
+        Some(c_int(val))
+    }
+}
+
+impl VectorVal {
+    /// The target feature that needs to be enabled for this to be passed properly.
+    ///
+    /// The 128-bit vectors only need SSE2, which is baseline for every x86_64 target.
+    pub fn target_feature(&self) -> Option<&'static str> {
+        match self {
+            VectorVal::c__m128(_) | VectorVal::c__m128d(_) | VectorVal::c__m128i(_) => None,
+            VectorVal::c__m256(_) | VectorVal::c__m256d(_) | VectorVal::c__m256i(_) => Some("avx"),
+        }
+    }
+}
+
+impl FloatVal {
+    /// The exact bytes of this value (in the target's endianness), for the types
+    /// that are specified by bit pattern. `long double`'s also depend on the target.
+    pub fn bytes(&self, target: &str) -> Option<Vec<u8>> {
+        let big_endian = TargetTriple::new(target).big_endian;
+        match *self {
+            FloatVal::c_double(_)
+            | FloatVal::c_float(_)
+            | FloatVal::c__Complex_float(..)
+            | FloatVal::c__Complex_double(..) => None,
+            FloatVal::c__Float16(bits) if big_endian => Some(bits.to_be_bytes().to_vec()),
+            FloatVal::c__Float16(bits) => Some(bits.to_le_bytes().to_vec()),
+            FloatVal::c__float128(bits) if big_endian => Some(bits.to_be_bytes().to_vec()),
+            FloatVal::c__float128(bits) => Some(bits.to_le_bytes().to_vec()),
+            FloatVal::c_long_double(bits) => {
+                let size = LongDoubleFormat::for_target(target).value_size();
+                if big_endian {
+                    let bytes = bits.to_be_bytes();
+                    Some(bytes[bytes.len() - size..].to_vec())
+                } else {
+                    Some(bits.to_le_bytes()[..size].to_vec())
+                }
+            }
+        }
+    }
+}
+
+impl LongDoubleFormat {
+    /// The format for the given target triple.
+    pub fn for_target(target: &str) -> Self {
+        let triple = TargetTriple::new(target);
+        if triple.is_msvc() {
+            LongDoubleFormat::Double
+        } else if matches!(triple.arch, "x86" | "x86_64") {
+            LongDoubleFormat::X87
+        } else if matches!(triple.arch, "aarch64" | "riscv64" | "s390x" | "loongarch64")
+            && !triple.is_apple()
+            && !triple.is_windows()
+        {
+            LongDoubleFormat::Quad
+        } else {
+            LongDoubleFormat::Double
+        }
+    }
+
+    /// How many bytes actually hold the value (the rest is padding).
+    pub fn value_size(self) -> usize {
+        match self {
+            LongDoubleFormat::Double => 8,
+            LongDoubleFormat::X87 => 10,
+            LongDoubleFormat::Quad => 16,
+        }
+    }
+}
+
 impl Func {
+    /// Every value in this function's signature.
+    pub fn vals(&self) -> impl Iterator<Item = &Val> {
+        self.inputs
+            .iter()
+            .chain(&self.variadic_inputs)
+            .chain(&self.output)
+    }
+
+    /// The outputs as they're actually passed back to the caller.
+    ///
+    /// The first output is returned as-is, and every output after it is
+    /// wrapped in a [`Val::Ref`][] (if it isn't one already) so that it gets
+    /// an out-param. Out-params come after all the fixed inputs, in order.
+    pub fn lowered_outputs(&self) -> Vec<Val> {
+        self.output
+            .iter()
+            .enumerate()
+            .map(|(idx, output)| match output {
+                Val::Ref(_) => output.clone(),
+                _ if idx == 0 => output.clone(),
+                _ => Val::Ref(Box::new(output.clone())),
+            })
+            .collect()
+    }
+
+    /// The signature `--poison` mode calls this function through, if it has any
+    /// narrow fixed inputs: they're all widened to pointer-sized ints (see
+    /// [`Poison::HighBits`][]), which only works because they're passed in a whole
+    /// register or stack slot either way. `--check-extension` mode also uses this
+    /// to define the callee, if it's the caller's job to extend them.
+    ///
+    /// Calling a function through a signature it wasn't defined with is UB in all
+    /// of our languages, so this deliberately relies on each compiler just lowering
+    /// the call as written. That's only dependable without optimizations (which
+    /// could inline through the cast, or assume the args are already extended),
+    /// so a side built with any other opt-level passes its narrow args normally.
+    pub fn poison_widened(&self, target: &str) -> Option<Func> {
+        let is_narrow = |input: &Val| matches!(input.poison(target), Some(Poison::HighBits { .. }));
+        if !self.inputs.iter().any(is_narrow) {
+            return None;
+        }
+        let mut widened = self.clone();
+        for input in &mut widened.inputs {
+            if is_narrow(input) {
+                *input = Val::Int(IntVal::c_uintptr_t(0));
+            }
+        }
+        Some(widened)
+    }
+
+    /// Whether this function takes varargs (`...`).
+    pub fn is_variadic(&self) -> bool {
+        !self.variadic_inputs.is_empty()
+    }
+
+    /// All the inputs, fixed and variadic, in the order they're passed.
+    pub fn all_inputs(&self) -> impl Iterator<Item = &Val> {
+        self.inputs.iter().chain(&self.variadic_inputs)
+    }
+
+    /// The inputs that are mutable refs, as their index and the value
+    /// the callee writes through them.
+    pub fn mut_refs(&self) -> impl Iterator<Item = (usize, &Val)> {
+        self.all_inputs()
+            .enumerate()
+            .filter_map(|(idx, input)| match input {
+                Val::MutRef(_before, after) => Some((idx, &**after)),
+                _ => None,
+            })
+    }
+
+    /// The target features that need to be enabled for this function's signature.
+    pub fn target_features(&self) -> Vec<&'static str> {
+        let mut features = vec![];
+        for val in self.vals() {
+            val.visit(&mut |val| {
+                if let Val::Vector(vector) = val {
+                    if let Some(feature) = vector.target_feature() {
+                        if !features.contains(&feature) {
+                            features.push(feature);
+                        }
+                    }
+                }
+            });
+        }
+        features
+    }
+
+    /// The name of the function the caller generates for the callback passed as arg `arg_idx`.
+    pub fn callback_name(&self, arg_idx: usize) -> String {
+        format!("{}_{}_callback", self.name, ARG_NAMES[arg_idx])
+    }
+
+    /// The callbacks this function is passed (see [`Val::FnPtr`][]), along with
+    /// the index of the arg they were passed as.
+    ///
+    /// The callee calls these in order, after reporting its own inputs, so their
+    /// inputs come after this function's inputs, and their outputs come before
+    /// this function's output.
+    pub fn callbacks(&self) -> Vec<(usize, Func)> {
+        self.inputs
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, input)| {
+                // A null function pointer just doesn't get called
+                let input = match input {
+                    Val::Nullable(inner, true) => inner,
+                    _ => input,
+                };
+                if let Val::FnPtr(_name, inputs, output) = input {
+                    Some((
+                        idx,
+                        Func {
+                            name: self.callback_name(idx),
+                            conventions: self.conventions.clone(),
+                            inputs: inputs.clone(),
+                            variadic_inputs: vec![],
+                            output: output.as_deref().cloned().into_iter().collect(),
+                        },
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
     pub fn has_convention(&self, convention: CallingConvention) -> bool {
-        self.conventions.iter().any(|&func_cc| {
-            (func_cc == CallingConvention::All && convention != CallingConvention::Handwritten)
-                || func_cc == convention
-        })
+        // Variadic functions only exist in the C conventions, so that's all All means for them
+        let in_all = if self.is_variadic() {
+            matches!(convention, CallingConvention::C | CallingConvention::Cdecl)
+        } else {
+            convention != CallingConvention::Handwritten
+        };
+        self.conventions
+            .iter()
+            .any(|&func_cc| (func_cc == CallingConvention::All && in_all) || func_cc == convention)
     }
 }
 
@@ -281,4 +1573,533 @@ impl Test {
             .iter()
             .any(|func| func.has_convention(convention))
     }
+
+    /// The target features that need to be enabled to call every function in this test.
+    pub fn target_features(&self) -> Vec<&'static str> {
+        let mut features = vec![];
+        for func in &self.funcs {
+            for feature in func.target_features() {
+                if !features.contains(&feature) {
+                    features.push(feature);
+                }
+            }
+        }
+        features
+    }
+
+    /// Whether any value (or subvalue) in this test satisfies `pred`.
+    pub fn any_val(&self, mut pred: impl FnMut(&Val) -> bool) -> bool {
+        let mut found = false;
+        for func in &self.funcs {
+            for val in func.vals() {
+                val.visit(&mut |val| found |= pred(val));
+            }
+        }
+        found
+    }
+
+    /// Whether any function in this test uses SIMD vectors.
+    pub fn has_vectors(&self) -> bool {
+        self.any_val(|val| matches!(val, Val::Vector(_)))
+    }
+
+    /// This test as seen by impls that don't have C++ references (anything but C++),
+    /// which just get the pointers they're passed as.
+    pub fn without_cxx_refs(&self) -> Test {
+        let mut test = self.clone();
+        for func in &mut test.funcs {
+            for input in &mut func.inputs {
+                if let Val::CxxRef(pointee) = input {
+                    *input = Val::Ref(pointee.clone());
+                }
+            }
+        }
+        test
+    }
+
+    /// Replace every [`Val::Named`][] in this test with the full value,
+    /// built from its declaration in `types`, and check every value makes sense
+    /// (see [`Val::validate`][]), whether it was named or written out inline.
+    pub fn resolve_types(&mut self) -> Result<(), GenerateError> {
+        let mut resolver = TypeResolver {
+            types: HashMap::new(),
+        };
+        for decl in &self.types {
+            if resolver.types.insert(decl.name(), decl).is_some() {
+                return Err(GenerateError::TypeError(format!(
+                    "two types are named {}",
+                    decl.name()
+                )));
+            }
+        }
+        for func in &mut self.funcs {
+            if func.is_variadic()
+                && func.conventions.iter().any(|cc| {
+                    !matches!(
+                        cc,
+                        CallingConvention::All
+                            | CallingConvention::Handwritten
+                            | CallingConvention::C
+                            | CallingConvention::Cdecl
+                    )
+                })
+            {
+                return Err(GenerateError::VariadicConvention(func.name.clone()));
+            }
+            for val in func
+                .inputs
+                .iter_mut()
+                .chain(&mut func.variadic_inputs)
+                .chain(&mut func.output)
+            {
+                resolver.resolve(val)?;
+                val.validate()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TypeDecl {
+    pub fn name(&self) -> &str {
+        match self {
+            TypeDecl::Struct(name, _)
+            | TypeDecl::ReprStruct(name, _, _)
+            | TypeDecl::Transparent(name, _)
+            | TypeDecl::Union(name, _)
+            | TypeDecl::Enum(name, _)
+            | TypeDecl::TaggedUnion(name, _)
+            | TypeDecl::Class(name, _, _)
+            | TypeDecl::Alias(name, _) => name,
+        }
+    }
+}
+
+/// How deep types can nest before we assume one contains itself.
+const MAX_TYPE_DEPTH: usize = 64;
+
+/// Builds the values for [`Val::Named`][] from a test's `types`.
+///
+/// Missing data (for the inactive variants of a union, or the element type
+/// of an empty array) is filled in with zeroes.
+struct TypeResolver<'a> {
+    types: HashMap<&'a str, &'a TypeDecl>,
+}
+
+impl TypeResolver<'_> {
+    /// Replace every `Val::Named` in this value (and its subvalues).
+    fn resolve(&self, val: &mut Val) -> Result<(), GenerateError> {
+        match val {
+            Val::Named(name, data) => {
+                let named_val = self.named_val(name, Some(data), 0)?;
+                *val = named_val;
+            }
+            Val::Ref(pointee)
+            | Val::EmptyArray(pointee)
+            | Val::Transparent(_, pointee)
+            | Val::Nullable(pointee, _)
+            | Val::Slice(pointee)
+            | Val::Boxed(pointee)
+            | Val::RustOption(pointee, _)
+            | Val::CxxRef(pointee)
+            | Val::Base(pointee)
+            | Val::NoUniqueAddress(pointee) => self.resolve(pointee)?,
+            Val::MutRef(before, after) => {
+                self.resolve(before)?;
+                self.resolve(after)?;
+            }
+            Val::Array(vals)
+            | Val::Struct(_, vals)
+            | Val::ReprStruct(_, _, vals)
+            | Val::Union(_, vals, _)
+            | Val::Tuple(vals)
+            | Val::Class(_, _, vals) => {
+                for val in vals {
+                    self.resolve(val)?;
+                }
+            }
+            Val::TaggedUnion(_, variants, _) => {
+                for (_discriminant, fields) in variants {
+                    for field in fields {
+                        self.resolve(field)?;
+                    }
+                }
+            }
+            Val::FnPtr(_, inputs, output) => {
+                for val in inputs.iter_mut().chain(output.as_deref_mut()) {
+                    self.resolve(val)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// A value of the type declared with this name.
+    fn named_val(
+        &self,
+        name: &str,
+        data: Option<&Data>,
+        depth: usize,
+    ) -> Result<Val, GenerateError> {
+        if depth > MAX_TYPE_DEPTH {
+            return Err(GenerateError::TypeError(format!(
+                "{name} nests too deep, does it contain itself?"
+            )));
+        }
+        let depth = depth + 1;
+        let decl = self
+            .types
+            .get(name)
+            .ok_or_else(|| GenerateError::TypeError(format!("no type named {name}")))?;
+        let val = match decl {
+            TypeDecl::Struct(name, fields) => {
+                Val::Struct(name.clone(), self.field_vals(name, fields, data, depth)?)
+            }
+            TypeDecl::ReprStruct(name, repr, fields) => Val::ReprStruct(
+                name.clone(),
+                repr.clone(),
+                self.field_vals(name, fields, data, depth)?,
+            ),
+            TypeDecl::Transparent(name, inner) => {
+                Val::Transparent(name.clone(), Box::new(self.val(inner, data, depth)?))
+            }
+            TypeDecl::Union(name, variants) => {
+                let (active, active_data) = self.variant_data(name, variants.len(), data)?;
+                let mut vals = vec![];
+                for (idx, ty) in variants.iter().enumerate() {
+                    let data = if idx == active { active_data } else { None };
+                    vals.push(self.val(ty, data, depth)?);
+                }
+                Val::Union(name.clone(), vals, active)
+            }
+            TypeDecl::Enum(name, discriminants) => {
+                let active = match data {
+                    Some(data) => data_int(format_args!("{name}"), Some(data))?,
+                    None => 0,
+                };
+                if active >= discriminants.len() {
+                    return Err(GenerateError::TypeError(format!(
+                        "{name} doesn't have a variant {active}"
+                    )));
+                }
+                Val::Enum(name.clone(), discriminants.clone(), active)
+            }
+            TypeDecl::TaggedUnion(name, variants) => {
+                let (active, active_data) = self.variant_data(name, variants.len(), data)?;
+                let mut vals = vec![];
+                for (idx, (discriminant, fields)) in variants.iter().enumerate() {
+                    let data = if idx == active { active_data } else { None };
+                    let fields = self.field_vals(name, fields, data, depth)?;
+                    vals.push((discriminant.clone(), fields));
+                }
+                Val::TaggedUnion(name.clone(), vals, active)
+            }
+            TypeDecl::Class(name, repr, fields) => Val::Class(
+                name.clone(),
+                repr.clone(),
+                self.field_vals(name, fields, data, depth)?,
+            ),
+            TypeDecl::Alias(_, ty) => self.val(ty, data, depth)?,
+        };
+        Ok(val)
+    }
+
+    /// A value of this type.
+    fn val(&self, ty: &Ty, data: Option<&Data>, depth: usize) -> Result<Val, GenerateError> {
+        let val = match ty {
+            Ty::Int(int_ty) => Val::Int(int_ty.val(data)?),
+            Ty::Float(float_ty) => Val::Float(float_ty.val(data)?),
+            Ty::Bool => match data {
+                Some(Data::Bool(val)) => Val::Bool(*val),
+                Some(data) => return Err(mismatch(ty, data)),
+                None => Val::Bool(false),
+            },
+            Ty::Ptr => Val::Ptr(data_int(ty, data)?),
+            Ty::Vector(vector_ty) => Val::Vector(vector_ty.val(data)?),
+            Ty::Ref(pointee) => Val::Ref(Box::new(self.val(pointee, data, depth)?)),
+            Ty::MutRef(pointee) => {
+                let [before, after] = list_data(ty, 2, data)?.try_into().unwrap();
+                Val::MutRef(
+                    Box::new(self.val(pointee, before, depth)?),
+                    Box::new(self.val(pointee, after, depth)?),
+                )
+            }
+            Ty::Array(elem, 0) => {
+                list_data(ty, 0, data)?;
+                Val::EmptyArray(Box::new(self.val(elem, None, depth)?))
+            }
+            Ty::Array(elem, len) => {
+                let mut vals = vec![];
+                for data in list_data(ty, *len, data)? {
+                    vals.push(self.val(elem, data, depth)?);
+                }
+                Val::Array(vals)
+            }
+            Ty::Bitfield(int_ty, width) => Val::Bitfield(int_ty.val(data)?, *width),
+            Ty::Nullable(inner) => match data {
+                Some(Data::Option(Some(data))) => {
+                    Val::Nullable(Box::new(self.val(inner, Some(data), depth)?), true)
+                }
+                Some(Data::Option(None)) | None => {
+                    Val::Nullable(Box::new(self.val(inner, None, depth)?), false)
+                }
+                Some(data) => return Err(mismatch(ty, data)),
+            },
+            Ty::Char => match data {
+                Some(Data::Str(val)) if val.chars().count() == 1 => {
+                    Val::Char(val.chars().next().unwrap())
+                }
+                Some(data) => return Err(mismatch(ty, data)),
+                None => Val::Char('\0'),
+            },
+            Ty::Unit => {
+                list_data(ty, 0, data)?;
+                Val::Unit
+            }
+            Ty::Tuple(fields) => {
+                let mut vals = vec![];
+                for (field, data) in fields.iter().zip(list_data(ty, fields.len(), data)?) {
+                    vals.push(self.val(field, data, depth)?);
+                }
+                Val::Tuple(vals)
+            }
+            Ty::Slice(elem) => {
+                let len = match data {
+                    Some(Data::List(items)) => items.len(),
+                    Some(data) => return Err(mismatch(ty, data)),
+                    None => 0,
+                };
+                let array_ty = Ty::Array(elem.clone(), len);
+                Val::Slice(Box::new(self.val(&array_ty, data, depth)?))
+            }
+            Ty::Str => match data {
+                Some(Data::Str(val)) => Val::Str(val.clone()),
+                Some(data) => return Err(mismatch(ty, data)),
+                None => Val::Str(String::new()),
+            },
+            Ty::Boxed(pointee) => Val::Boxed(Box::new(self.val(pointee, data, depth)?)),
+            Ty::RustOption(inner) => match data {
+                Some(Data::Option(Some(data))) => {
+                    Val::RustOption(Box::new(self.val(inner, Some(data), depth)?), true)
+                }
+                Some(Data::Option(None)) | None => {
+                    Val::RustOption(Box::new(self.val(inner, None, depth)?), false)
+                }
+                Some(data) => return Err(mismatch(ty, data)),
+            },
+            Ty::CxxRef(pointee) => Val::CxxRef(Box::new(self.val(pointee, data, depth)?)),
+            Ty::Base(base) => Val::Base(Box::new(self.val(base, data, depth)?)),
+            Ty::NoUniqueAddress(field) => {
+                Val::NoUniqueAddress(Box::new(self.val(field, data, depth)?))
+            }
+            Ty::Named(name) => self.named_val(name, data, depth)?,
+        };
+        Ok(val)
+    }
+
+    /// The values of some fields, given a list of their data.
+    fn field_vals(
+        &self,
+        name: &str,
+        fields: &[Ty],
+        data: Option<&Data>,
+        depth: usize,
+    ) -> Result<Vec<Val>, GenerateError> {
+        let mut vals = vec![];
+        for (ty, data) in fields
+            .iter()
+            .zip(list_data(format_args!("{name}"), fields.len(), data)?)
+        {
+            vals.push(self.val(ty, data, depth)?);
+        }
+        Ok(vals)
+    }
+
+    /// Split the data of a union/enum into the index of its variant and that variant's data.
+    ///
+    /// Just the index is also accepted, which leaves the variant's data zeroed.
+    fn variant_data<'d>(
+        &self,
+        name: &str,
+        num_variants: usize,
+        data: Option<&'d Data>,
+    ) -> Result<(usize, Option<&'d Data>), GenerateError> {
+        let (active, active_data) = match data {
+            Some(Data::List(items)) if items.len() == 2 => (
+                data_int(format_args!("{name}"), Some(&items[0]))?,
+                Some(&items[1]),
+            ),
+            Some(data @ (Data::Int(_) | Data::UInt(_))) => {
+                (data_int(format_args!("{name}"), Some(data))?, None)
+            }
+            Some(data) => return Err(mismatch(format_args!("{name}"), data)),
+            None => (0, None),
+        };
+        if active >= num_variants {
+            return Err(GenerateError::TypeError(format!(
+                "{name} doesn't have a variant {active}"
+            )));
+        }
+        Ok((active, active_data))
+    }
+}
+
+/// The items of some list data, which must have exactly `len` items.
+fn list_data(
+    ty: impl Debug,
+    len: usize,
+    data: Option<&Data>,
+) -> Result<Vec<Option<&Data>>, GenerateError> {
+    match data {
+        Some(Data::List(items)) if items.len() == len => Ok(items.iter().map(Some).collect()),
+        Some(data) => Err(GenerateError::TypeError(format!(
+            "{ty:?} needs a list of {len} values, but got {data}"
+        ))),
+        None => Ok(vec![None; len]),
+    }
+}
+
+/// The integer in this data, if it fits in `T`.
+fn data_int<T>(ty: impl Debug, data: Option<&Data>) -> Result<T, GenerateError>
+where
+    T: TryFrom<i128> + TryFrom<u128> + Default,
+{
+    let int = match data {
+        Some(Data::Int(val)) => T::try_from(*val).ok(),
+        Some(Data::UInt(val)) => T::try_from(*val).ok(),
+        Some(_) => None,
+        None => Some(T::default()),
+    };
+    int.ok_or_else(|| mismatch(ty, data.unwrap()))
+}
+
+/// The float in this data (ints are fine too).
+fn data_float(ty: impl Debug, data: Option<&Data>) -> Result<f64, GenerateError> {
+    match data {
+        Some(Data::Float(val)) => Ok(*val),
+        Some(Data::Int(val)) => Ok(*val as f64),
+        Some(Data::UInt(val)) => Ok(*val as f64),
+        Some(data) => Err(mismatch(ty, data)),
+        None => Ok(0.0),
+    }
+}
+
+/// The lanes of a vector, which must have exactly `N` of them.
+fn data_lanes<T: Debug, const N: usize>(
+    ty: impl Debug,
+    data: Option<&Data>,
+    lane: impl Fn(Option<&Data>) -> Result<T, GenerateError>,
+) -> Result<[T; N], GenerateError> {
+    let lanes = list_data(ty, N, data)?
+        .into_iter()
+        .map(lane)
+        .collect::<Result<Vec<T>, _>>()?;
+    Ok(lanes.try_into().unwrap())
+}
+
+fn mismatch(ty: impl Debug, data: &Data) -> GenerateError {
+    GenerateError::TypeError(format!("{data} isn't a valid {ty:?}"))
+}
+
+impl IntTy {
+    /// A value of this type.
+    pub fn val(self, data: Option<&Data>) -> Result<IntVal, GenerateError> {
+        use IntTy::*;
+        let val = match self {
+            c__int128 => IntVal::c__int128(data_int(self, data)?),
+            c_int64_t => IntVal::c_int64_t(data_int(self, data)?),
+            c_int32_t => IntVal::c_int32_t(data_int(self, data)?),
+            c_int16_t => IntVal::c_int16_t(data_int(self, data)?),
+            c_int8_t => IntVal::c_int8_t(data_int(self, data)?),
+            c__uint128 => IntVal::c__uint128(data_int(self, data)?),
+            c_uint64_t => IntVal::c_uint64_t(data_int(self, data)?),
+            c_uint32_t => IntVal::c_uint32_t(data_int(self, data)?),
+            c_uint16_t => IntVal::c_uint16_t(data_int(self, data)?),
+            c_uint8_t => IntVal::c_uint8_t(data_int(self, data)?),
+            c_char => IntVal::c_char(data_int(self, data)?),
+            c_schar => IntVal::c_schar(data_int(self, data)?),
+            c_uchar => IntVal::c_uchar(data_int(self, data)?),
+            c_short => IntVal::c_short(data_int(self, data)?),
+            c_ushort => IntVal::c_ushort(data_int(self, data)?),
+            c_int => IntVal::c_int(data_int(self, data)?),
+            c_uint => IntVal::c_uint(data_int(self, data)?),
+            c_long => IntVal::c_long(data_int(self, data)?),
+            c_ulong => IntVal::c_ulong(data_int(self, data)?),
+            c_longlong => IntVal::c_longlong(data_int(self, data)?),
+            c_ulonglong => IntVal::c_ulonglong(data_int(self, data)?),
+            c_size_t => IntVal::c_size_t(data_int(self, data)?),
+            c_ptrdiff_t => IntVal::c_ptrdiff_t(data_int(self, data)?),
+            c_intptr_t => IntVal::c_intptr_t(data_int(self, data)?),
+            c_uintptr_t => IntVal::c_uintptr_t(data_int(self, data)?),
+            c_wchar_t => IntVal::c_wchar_t(data_int(self, data)?),
+        };
+        Ok(val)
+    }
+}
+
+impl FloatTy {
+    /// A value of this type.
+    pub fn val(self, data: Option<&Data>) -> Result<FloatVal, GenerateError> {
+        use FloatTy::*;
+        let val = match self {
+            c_double => FloatVal::c_double(data_float(self, data)?),
+            c_float => FloatVal::c_float(data_float(self, data)? as f32),
+            c__Float16 => FloatVal::c__Float16(data_int(self, data)?),
+            c__float128 => FloatVal::c__float128(data_int(self, data)?),
+            c_long_double => FloatVal::c_long_double(data_int(self, data)?),
+            c__Complex_float => {
+                let [re, im] = data_lanes(self, data, |data| data_float(self, data))?;
+                FloatVal::c__Complex_float(re as f32, im as f32)
+            }
+            c__Complex_double => {
+                let [re, im] = data_lanes(self, data, |data| data_float(self, data))?;
+                FloatVal::c__Complex_double(re, im)
+            }
+        };
+        Ok(val)
+    }
+}
+
+impl VectorTy {
+    /// A value of this type.
+    pub fn val(self, data: Option<&Data>) -> Result<VectorVal, GenerateError> {
+        use VectorTy::*;
+        let f32_lane = |data: Option<&Data>| Ok(data_float(self, data)? as f32);
+        let f64_lane = |data: Option<&Data>| data_float(self, data);
+        let u32_lane = |data: Option<&Data>| data_int(self, data);
+        let val = match self {
+            c__m128 => VectorVal::c__m128(data_lanes(self, data, f32_lane)?),
+            c__m128d => VectorVal::c__m128d(data_lanes(self, data, f64_lane)?),
+            c__m128i => VectorVal::c__m128i(data_lanes(self, data, u32_lane)?),
+            c__m256 => VectorVal::c__m256(data_lanes(self, data, f32_lane)?),
+            c__m256d => VectorVal::c__m256d(data_lanes(self, data, f64_lane)?),
+            c__m256i => VectorVal::c__m256i(data_lanes(self, data, u32_lane)?),
+        };
+        Ok(val)
+    }
+}
+
+impl std::fmt::Display for Data {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Data::Bool(val) => write!(f, "{val}"),
+            Data::Int(val) => write!(f, "{val}"),
+            Data::UInt(val) => write!(f, "{val}"),
+            Data::Float(val) => write!(f, "{val:?}"),
+            Data::List(items) => {
+                write!(f, "(")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, ")")
+            }
+            Data::Option(Some(data)) => write!(f, "Some({data})"),
+            Data::Option(None) => write!(f, "None"),
+            Data::Str(val) => write!(f, "{val:?}"),
+        }
+    }
 }
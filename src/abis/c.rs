@@ -5,10 +5,64 @@ use super::*;
 
 pub static C_TEST_PREFIX: &str = include_str!("../../harness/c_test_prefix.h");
 
+/// Where the headers are in a Windows SDK and CRT splatted out by xwin.
+static XWIN_INCLUDE_DIRS: &[&str] = &[
+    "crt/include",
+    "sdk/include/ucrt",
+    "sdk/include/um",
+    "sdk/include/shared",
+];
+
+/// Where the import libs are in a Windows SDK and CRT splatted out by xwin,
+/// for the given windows-msvc target.
+pub fn xwin_lib_dirs(xwin_dir: &Path, target: &str) -> Vec<PathBuf> {
+    let arch = match target.split('-').next().unwrap_or_default() {
+        "i686" | "i586" => "x86",
+        arch => arch,
+    };
+    ["crt/lib", "sdk/lib/um", "sdk/lib/ucrt"]
+        .iter()
+        .map(|dir| xwin_dir.join(dir).join(arch))
+        .collect()
+}
+
+/// The windows-msvc target the msvc impl builds for: the host itself if that's
+/// already windows-msvc, and otherwise the same arch cross-compiled from here.
+pub fn msvc_target() -> &'static str {
+    if cfg!(target_env = "msvc") {
+        built_info::TARGET
+    } else if cfg!(target_arch = "x86") {
+        "i686-pc-windows-msvc"
+    } else if cfg!(target_arch = "aarch64") {
+        "aarch64-pc-windows-msvc"
+    } else {
+        "x86_64-pc-windows-msvc"
+    }
+}
+
+/// A C compiler added with `--add-c-compiler`, which gets driven by the CC crate
+/// just like the system one (so its flavor is detected the same way too).
+#[derive(Debug, Clone)]
+pub struct CustomCCompiler {
+    pub path: PathBuf,
+    /// Extra flags to pass on every compile.
+    pub flags: Vec<String>,
+}
+
 pub struct CcAbiImpl {
     cc_flavor: CCFlavor,
     platform: Platform,
     mode: &'static str,
+    poison: bool,
+    check_extension: bool,
+    /// Whether this actually emits C++ (for a [`CxxAbiImpl`][]).
+    cxx: bool,
+    target: &'static str,
+    /// Where xwin put the Windows SDK and CRT (for cross-compiling with clang-cl).
+    xwin_dir: Option<PathBuf>,
+    /// The compiler to use instead of the one `mode` names, if this was added by the user.
+    custom: Option<CustomCCompiler>,
+    profile: FlagProfile,
 }
 
 #[derive(PartialEq)]
@@ -34,60 +88,65 @@ impl AbiImpl for CcAbiImpl {
     fn src_ext(&self) -> &'static str {
         "c"
     }
+    fn target(&self) -> &'static str {
+        self.target
+    }
 
     fn supports_convention(&self, convention: CallingConvention) -> bool {
         self.c_convention_decl(convention).is_ok()
     }
 
+    fn supports_val(&self, val: &Val) -> bool {
+        match val {
+            // Arrays and bitfields are only "unsupported" by c_arg_type to catch misuse,
+            // their elements will be checked on their own.
+            Val::Array(_) | Val::Bitfield(..) => true,
+            // Likewise for the special fields of classes, which only C++ has
+            Val::Base(_) | Val::NoUniqueAddress(_) => self.cxx,
+            // Zero-length arrays are a GNU extension
+            Val::EmptyArray(_) => self.cc_flavor != CCFlavor::Msvc,
+            _ => self.c_arg_type(val).is_ok(),
+        }
+    }
+
+    fn supports_callee(&self, _func: &Func) -> bool {
+        true
+    }
+
     fn generate_callee(
         &self,
         f: &mut dyn Write,
         test: &Test,
         convention: CallingConvention,
     ) -> Result<(), GenerateError> {
-        self.write_c_prefix(f, test)?;
+        self.write_c_prefix(f, test, convention)?;
 
         // Generate the impls
         for function in &test.funcs {
             if !function.has_convention(convention) {
                 continue;
             }
-            self.write_c_signature(f, function, convention)?;
+            // If the caller is meant to extend the narrow args, take them as
+            // whole registers to see what it actually left in there
+            let widened = function
+                .poison_widened(self.target)
+                .filter(|_| self.widens_callee());
+            self.write_c_signature(f, widened.as_ref().unwrap_or(function), convention)?;
             writeln!(f, " {{")?;
-
-            writeln!(f)?;
-            for (idx, input) in function.inputs.iter().enumerate() {
-                writeln!(
-                    f,
-                    "{}",
-                    self.c_write_val(input, "CALLEE_INPUTS", ARG_NAMES[idx], false)?
-                )?;
-            }
-            writeln!(f)?;
-            if let Some(output) = &function.output {
-                writeln!(
-                    f,
-                    "    {} = {};",
-                    self.c_var_decl(output, OUTPUT_NAME)?,
-                    self.c_val(output)?
-                )?;
-                writeln!(
-                    f,
-                    "{}",
-                    self.c_write_val(output, "CALLEE_OUTPUTS", OUTPUT_NAME, true)?
-                )?;
-                writeln!(f, "    FINISHED_FUNC(CALLEE_INPUTS, CALLEE_OUTPUTS);")?;
-                writeln!(
-                    f,
-                    "    {}",
-                    self.c_var_return(output, OUTPUT_NAME, OUT_PARAM_NAME)?
-                )?;
-            } else {
-                writeln!(f, "    FINISHED_FUNC(CALLEE_INPUTS, CALLEE_OUTPUTS);")?;
-            }
+            self.write_c_body(
+                f,
+                function,
+                convention,
+                "CALLEE_INPUTS",
+                "CALLEE_OUTPUTS",
+                "CALLEE_POSTCALL",
+                true,
+                widened.is_some(),
+            )?;
             writeln!(f, "}}")?;
             writeln!(f)?;
         }
+        self.write_c_suffix(f)?;
 
         Ok(())
     }
@@ -98,113 +157,143 @@ impl AbiImpl for CcAbiImpl {
         test: &Test,
         convention: CallingConvention,
     ) -> Result<(), GenerateError> {
-        self.write_c_prefix(f, test)?;
+        self.write_c_prefix(f, test, convention)?;
 
         // Generate the extern block
         for function in &test.funcs {
             self.write_c_signature(f, function, convention)?;
             writeln!(f, ";")?;
         }
-
         writeln!(f)?;
-        writeln!(f, "void do_test(void) {{")?;
 
-        // Generate the impls
+        // Generate the callbacks the callee will call
         for function in &test.funcs {
             if !function.has_convention(convention) {
                 continue;
             }
-            // Add an extra scope to avoid clashes between subtests
-            writeln!(f, "{{")?;
-            // Inputs
-            for (idx, input) in function.inputs.iter().enumerate() {
-                writeln!(
+            for (_arg_idx, callback) in function.callbacks() {
+                write!(f, "static ")?;
+                self.write_c_signature(f, &callback, convention)?;
+                writeln!(f, " {{")?;
+                self.write_c_body(
                     f,
-                    "    {} = {};",
-                    self.c_var_decl(input, ARG_NAMES[idx])?,
-                    self.c_val(input)?
-                )?;
-                writeln!(
-                    f,
-                    "{}",
-                    self.c_write_val(input, "CALLER_INPUTS", ARG_NAMES[idx], true)?
+                    &callback,
+                    convention,
+                    "CALLER_INPUTS",
+                    "CALLER_OUTPUTS",
+                    "CALLER_POSTCALL",
+                    false,
+                    false,
                 )?;
+                writeln!(f, "}}")?;
+                writeln!(f)?;
             }
-            writeln!(f)?;
-
-            // Output
-            let pass_out = if let Some(output) = &function.output {
-                if let Some(out_param_var) = self.c_out_param_var(output, OUTPUT_NAME)? {
-                    writeln!(f, "    {};", out_param_var)?;
-                    write!(f, "    ")?;
-                    true
-                } else {
-                    write!(f, "    {} = ", self.c_var_decl(output, OUTPUT_NAME)?)?;
-                    false
-                }
-            } else {
-                write!(f, "    ")?;
-                false
-            };
+        }
 
-            // Do the actual call
-            write!(f, "{}(", function.name)?;
-            for (idx, input) in function.inputs.iter().enumerate() {
-                if idx != 0 {
-                    write!(f, ", ")?;
-                }
-                write!(f, "{}", self.c_arg_pass(input, ARG_NAMES[idx])?)?;
-            }
-            if pass_out {
-                let pass = self.c_arg_pass(function.output.as_ref().unwrap(), OUTPUT_NAME)?;
-                if function.inputs.is_empty() {
-                    write!(f, "{}", pass)?;
-                } else {
-                    write!(f, ", {}", pass)?;
-                }
-            }
-            writeln!(f, ");")?;
+        write!(
+            f,
+            "{}",
+            self.c_target_features_decl(&test.target_features())
+        )?;
+        writeln!(f, "void do_test(void) {{")?;
 
-            if let Some(output) = &function.output {
-                writeln!(
-                    f,
-                    "{}",
-                    self.c_write_val(output, "CALLER_OUTPUTS", OUTPUT_NAME, true)?
-                )?;
+        // Generate the impls
+        for function in &test.funcs {
+            if !function.has_convention(convention) {
+                continue;
             }
+            // Add an extra scope to avoid clashes between subtests
+            writeln!(f, "{{")?;
+            self.write_c_call(
+                f,
+                function,
+                convention,
+                &function.name,
+                "",
+                "CALLER_INPUTS",
+                "CALLER_OUTPUTS",
+                "CALLER_POSTCALL",
+            )?;
             writeln!(f, "    FINISHED_FUNC(CALLER_INPUTS, CALLER_OUTPUTS);")?;
+            writeln!(f, "    FINISHED_FUNC(CALLER_POSTCALL, CALLEE_POSTCALL);")?;
             writeln!(f, "}}")?;
             writeln!(f)?;
         }
         writeln!(f, "}}")?;
+        self.write_c_suffix(f)?;
         Ok(())
     }
 
     fn compile_callee(&self, src_path: &Path, lib_name: &str) -> Result<String, BuildError> {
+        if let Some(custom) = &self.custom {
+            return self.compile_custom(custom, src_path, lib_name);
+        }
         match self.mode {
             "cc" => self.compile_cc(src_path, lib_name),
             "gcc" => self.compile_gcc(src_path, lib_name),
             "clang" => self.compile_clang(src_path, lib_name),
             "msvc" => self.compile_msvc(src_path, lib_name),
+            "zigcc" => self.compile_zigcc(src_path, lib_name),
             _ => unimplemented!("unknown c compiler"),
         }
     }
 
     fn compile_caller(&self, src_path: &Path, lib_name: &str) -> Result<String, BuildError> {
+        if let Some(custom) = &self.custom {
+            return self.compile_custom(custom, src_path, lib_name);
+        }
         match self.mode {
             "cc" => self.compile_cc(src_path, lib_name),
             "gcc" => self.compile_gcc(src_path, lib_name),
             "clang" => self.compile_clang(src_path, lib_name),
             "msvc" => self.compile_msvc(src_path, lib_name),
+            "zigcc" => self.compile_zigcc(src_path, lib_name),
             _ => unimplemented!("unknown c compiler"),
         }
     }
 }
 
 impl CcAbiImpl {
-    pub fn new(_system_info: &Config, mode: &'static str) -> Self {
-        let compiler = cc::Build::new().get_compiler();
-        let cc_flavor = if compiler.is_like_msvc() {
+    pub fn new(system_info: &Config, profile: &FlagProfile, mode: &'static str) -> Self {
+        Self::new_for_lang(system_info, profile, mode, false, None)
+    }
+
+    /// A generator for C++ with C linkage instead, see [`CxxAbiImpl`][].
+    pub fn new_cxx(system_info: &Config, profile: &FlagProfile, mode: &'static str) -> Self {
+        Self::new_for_lang(system_info, profile, mode, true, None)
+    }
+
+    /// An impl named `name` that runs a compiler added with `--add-c-compiler`.
+    pub fn new_custom(
+        system_info: &Config,
+        profile: &FlagProfile,
+        name: &str,
+        custom: &CustomCCompiler,
+    ) -> Self {
+        // These live as long as the program anyway
+        let name = Box::leak(name.to_owned().into_boxed_str());
+        Self::new_for_lang(system_info, profile, name, false, Some(custom.clone()))
+    }
+
+    fn new_for_lang(
+        system_info: &Config,
+        profile: &FlagProfile,
+        mode: &'static str,
+        cxx: bool,
+        custom: Option<CustomCCompiler>,
+    ) -> Self {
+        let mut build = cc::Build::new();
+        build.cpp(cxx).opt_level_str(&profile.opt_level);
+        if let Some(custom) = &custom {
+            build.compiler(&custom.path);
+        }
+        let compiler = build.get_compiler();
+        // The msvc impl is always MSVC targeting Windows, even if it has to cross-compile
+        let is_msvc = mode == ABI_IMPL_MSVC;
+        let cc_flavor = if mode == ABI_IMPL_ZIGCC {
+            // zig cc is clang, whatever the system's CC is
+            CCFlavor::Clang
+        } else if is_msvc || compiler.is_like_msvc() {
             CCFlavor::Msvc
         } else if compiler.is_like_gnu() {
             CCFlavor::Gcc
@@ -214,7 +303,7 @@ impl CcAbiImpl {
             panic!("Unknown compiler flavour for CC");
         };
 
-        let platform = if cfg!(target_os = "windows") {
+        let platform = if is_msvc || cfg!(target_os = "windows") {
             Platform::Windows
         } else {
             Platform::Unixy
@@ -224,17 +313,97 @@ impl CcAbiImpl {
             cc_flavor,
             platform,
             mode,
+            poison: system_info.poison,
+            check_extension: system_info.check_extension,
+            cxx,
+            target: if is_msvc {
+                msvc_target()
+            } else {
+                built_info::TARGET
+            },
+            xwin_dir: system_info.xwin_dir.clone(),
+            custom,
+            profile: profile.clone(),
+        }
+    }
+
+    /// Apply the [`FlagProfile`][] to a build driven by the CC crate.
+    pub fn apply_profile(&self, build: &mut cc::Build) {
+        build
+            .opt_level_str(&self.profile.opt_level)
+            .pic(self.profile.pic);
+        if let Some(cpu_flag) = self.cpu_flag() {
+            build.flag(&cpu_flag);
+        }
+    }
+
+    /// The [`FlagProfile`][] as flags for a gcc-style compiler driver.
+    pub fn gnu_profile_flags(&self) -> Vec<String> {
+        let mut flags = vec![format!("-O{}", self.profile.opt_level)];
+        flags.extend(self.cpu_flag());
+        let pic_flag = if self.profile.pic {
+            "-fPIC"
+        } else {
+            "-fno-pic"
+        };
+        flags.push(pic_flag.to_string());
+        flags
+    }
+
+    /// How to ask for the profile's target cpu, if it has one.
+    fn cpu_flag(&self) -> Option<String> {
+        let cpu = self.profile.target_cpu.as_ref()?;
+        if self.cc_flavor == CCFlavor::Msvc {
+            // cl.exe's /arch only picks an instruction set extension, there's no cpu to pick
+            return None;
+        }
+        // Like rustc's target-cpu, gcc and clang only call it the "arch" on x86
+        if matches!(TargetTriple::new(self.target).arch, "x86" | "x86_64") {
+            Some(format!("-march={cpu}"))
+        } else {
+            Some(format!("-mcpu={cpu}"))
+        }
+    }
+
+    /// The profile's opt level as cl.exe's flag (it has nothing past 2).
+    fn msvc_opt_flag(&self) -> &'static str {
+        match &*self.profile.opt_level {
+            "0" => "/Od",
+            "1" | "s" | "z" => "/O1",
+            _ => "/O2",
         }
     }
 
     fn compile_cc(&self, src_path: &Path, lib_name: &str) -> Result<String, BuildError> {
-        cc::Build::new()
+        let mut build = cc::Build::new();
+        build
             .file(src_path)
-            .opt_level(0)
             .cargo_metadata(false)
-            .target(built_info::TARGET)
             // .warnings_into_errors(true)
-            .try_compile(lib_name)?;
+            .target(built_info::TARGET);
+        self.apply_profile(&mut build);
+        build.try_compile(lib_name)?;
+        Ok(String::from(lib_name))
+    }
+
+    fn compile_custom(
+        &self,
+        custom: &CustomCCompiler,
+        src_path: &Path,
+        lib_name: &str,
+    ) -> Result<String, BuildError> {
+        let mut build = cc::Build::new();
+        build
+            .file(src_path)
+            .compiler(&custom.path)
+            .cargo_metadata(false)
+            .target(built_info::TARGET);
+        self.apply_profile(&mut build);
+        // These come after the CC crate's own flags, so they can override them
+        for flag in &custom.flags {
+            build.flag(flag);
+        }
+        build.try_compile(lib_name)?;
         Ok(String::from(lib_name))
     }
 
@@ -242,16 +411,19 @@ impl CcAbiImpl {
         let base_path = PathBuf::from("target/temp/");
         let obj_path = base_path.join(format!("{lib_name}.o"));
         let lib_path = base_path.join(format!("lib{lib_name}.a"));
-        Command::new("clang")
-            .arg("-ffunction-sections")
+        let mut cmd = Command::new("clang");
+        cmd.arg("-ffunction-sections")
             .arg("-fdata-sections")
-            .arg("-fPIC")
+            .args(self.gnu_profile_flags())
             .arg("-o")
             .arg(&obj_path)
             .arg("-c")
-            .arg(&src_path)
-            .status()
-            .unwrap();
+            .arg(src_path);
+        eprintln!("running: {:?}", cmd);
+        let out = cmd.output()?;
+        if !out.status.success() {
+            return Err(BuildError::GnuCompile(out));
+        }
         Command::new("ar")
             .arg("cq")
             .arg(&lib_path)
@@ -266,16 +438,49 @@ impl CcAbiImpl {
         let base_path = PathBuf::from("target/temp/");
         let obj_path = base_path.join(format!("{lib_name}.o"));
         let lib_path = base_path.join(format!("lib{lib_name}.a"));
-        Command::new("gcc")
-            .arg("-ffunction-sections")
+        let mut cmd = Command::new("gcc");
+        cmd.arg("-ffunction-sections")
             .arg("-fdata-sections")
-            .arg("-fPIC")
+            .args(self.gnu_profile_flags())
             .arg("-o")
             .arg(&obj_path)
             .arg("-c")
-            .arg(&src_path)
+            .arg(src_path);
+        eprintln!("running: {:?}", cmd);
+        let out = cmd.output()?;
+        if !out.status.success() {
+            return Err(BuildError::GnuCompile(out));
+        }
+        Command::new("ar")
+            .arg("cq")
+            .arg(&lib_path)
+            .arg(&obj_path)
             .status()
             .unwrap();
+        Command::new("ar").arg("s").arg(&lib_path).status().unwrap();
+        Ok(String::from(lib_name))
+    }
+
+    fn compile_zigcc(&self, src_path: &Path, lib_name: &str) -> Result<String, BuildError> {
+        let base_path = PathBuf::from("target/temp/");
+        let obj_path = base_path.join(format!("{lib_name}.o"));
+        let lib_path = base_path.join(format!("lib{lib_name}.a"));
+        let mut cmd = Command::new("zig");
+        cmd.arg("cc")
+            // zig cc turns on UBSan's traps by default, which would need its runtime
+            .arg("-fno-sanitize=undefined")
+            .arg("-ffunction-sections")
+            .arg("-fdata-sections")
+            .args(self.gnu_profile_flags())
+            .arg("-o")
+            .arg(&obj_path)
+            .arg("-c")
+            .arg(src_path);
+        eprintln!("running: {:?}", cmd);
+        let out = cmd.output()?;
+        if !out.status.success() {
+            return Err(BuildError::ZigCompile(out));
+        }
         Command::new("ar")
             .arg("cq")
             .arg(&lib_path)
@@ -286,8 +491,56 @@ impl CcAbiImpl {
         Ok(String::from(lib_name))
     }
 
-    fn compile_msvc(&self, _src_path: &Path, _lib_name: &str) -> Result<String, BuildError> {
-        unimplemented!()
+    fn compile_msvc(&self, src_path: &Path, lib_name: &str) -> Result<String, BuildError> {
+        if self.target == built_info::TARGET {
+            // We're on windows-msvc, so cc knows where to find the real thing
+            let mut build = cc::Build::new();
+            build
+                .file(src_path)
+                .cargo_metadata(false)
+                .target(built_info::TARGET)
+                // _Alignas
+                .flag_if_supported("/std:c11");
+            self.apply_profile(&mut build);
+            build.try_compile(lib_name)?;
+            return Ok(String::from(lib_name));
+        }
+
+        // Otherwise cross-compile with clang-cl, which takes cl.exe's flags and
+        // produces the same objects, against a Windows SDK and CRT fetched by xwin
+        // (if there's no xwin_dir, clang-cl will look in %INCLUDE% like cl.exe does).
+        let base_path = PathBuf::from("target/temp/");
+        let obj_path = base_path.join(format!("{lib_name}.obj"));
+        let lib_path = base_path.join(format!("{lib_name}.lib"));
+        let mut cmd = Command::new("clang-cl");
+        cmd.arg(format!("--target={}", self.target))
+            .arg("/nologo")
+            .arg(self.msvc_opt_flag())
+            .arg("/std:c11");
+        if let Some(xwin_dir) = &self.xwin_dir {
+            for include_dir in XWIN_INCLUDE_DIRS {
+                cmd.arg("/imsvc").arg(xwin_dir.join(include_dir));
+            }
+        }
+        cmd.arg("/c")
+            .arg(format!("/Fo{}", obj_path.display()))
+            .arg(src_path);
+        eprintln!("running: {:?}", cmd);
+        let out = cmd.output()?;
+        if !out.status.success() {
+            return Err(BuildError::MsvcCompile(out));
+        }
+
+        let mut cmd = Command::new("llvm-lib");
+        cmd.arg("/nologo")
+            .arg(format!("/out:{}", lib_path.display()))
+            .arg(&obj_path);
+        eprintln!("running: {:?}", cmd);
+        let out = cmd.output()?;
+        if !out.status.success() {
+            return Err(BuildError::MsvcCompile(out));
+        }
+        Ok(String::from(lib_name))
     }
 
     fn c_convention_decl(
@@ -321,6 +574,10 @@ impl CcAbiImpl {
                 // all properly convered by other ABIs
                 return Err(GenerateError::UnsupportedConvention);
             }
+            Rust => {
+                // C obviously doesn't speak Rust's unstable ABI
+                return Err(GenerateError::UnsupportedConvention);
+            }
             C => "",
             Cdecl => {
                 if self.platform == Windows {
@@ -367,6 +624,27 @@ impl CcAbiImpl {
         Ok(val)
     }
 
+    /// Whether narrow fixed inputs get passed with garbage in their high bits.
+    ///
+    /// That means calling through a widened signature, which is only reliable
+    /// unoptimized (see [`Func::poison_widened`][]), so otherwise they're passed normally.
+    fn poisons_high_bits(&self) -> bool {
+        let poisons = if self.check_extension {
+            Extender::for_target(self.target) == Extender::Callee
+        } else {
+            self.poison
+        };
+        poisons && self.profile.opt_level == "0"
+    }
+
+    /// Whether callees take narrow fixed inputs as pointer-sized ints,
+    /// to check that the caller extended them (again, only unoptimized).
+    fn widens_callee(&self) -> bool {
+        self.check_extension
+            && Extender::for_target(self.target) != Extender::Callee
+            && self.profile.opt_level == "0"
+    }
+
     // Emit a function signature
     fn write_c_signature(
         &self,
@@ -375,25 +653,56 @@ impl CcAbiImpl {
         convention: CallingConvention,
     ) -> Result<(), GenerateError> {
         let convention_decl = self.c_convention_decl(convention)?;
+        write!(
+            f,
+            "{}",
+            self.c_target_features_decl(&function.target_features())
+        )?;
+        let declarator = format!("{convention_decl}{}", function.name);
+        self.write_c_fn_decl(f, function, &declarator)
+    }
 
-        // First figure out the return (by-ref requires an out-param)
-        let out_param = if let Some(output) = &function.output {
-            let out_param = self.c_out_param(output, OUT_PARAM_NAME)?;
-            if out_param.is_none() {
-                write!(f, "{} ", self.c_arg_type(output)?)?;
+    // Emit a function's return type, the given declarator, and then its args
+    // (this lets function pointers be declared with the same logic).
+    fn write_c_fn_decl(
+        &self,
+        f: &mut dyn Write,
+        function: &Func,
+        declarator: &str,
+    ) -> Result<(), GenerateError> {
+        // First figure out the return (by-ref and extra outputs require out-params)
+        for output in &function.output {
+            if let Val::MutRef(..) = output {
+                return Err(GenerateError::CUnsupported(
+                    "mutable refs can only be passed as inputs".to_string(),
+                ));
+            }
+            if let Val::CxxRef(..) = output {
+                return Err(GenerateError::CxxUnsupported(
+                    "references can only be passed as inputs".to_string(),
+                ));
+            }
+            if let Val::Nullable(inner, _) = output {
+                if let Val::Ref(_) = **inner {
+                    return Err(GenerateError::CUnsupported(
+                        "nullable refs can only be passed as inputs".to_string(),
+                    ));
+                }
+            }
+        }
+        let mut return_ty = "void".to_string();
+        let mut out_params = vec![];
+        for (idx, output) in function.lowered_outputs().iter().enumerate() {
+            if let Some(out_param) = self.c_out_param(output, &out_param_name(idx))? {
+                out_params.push(out_param);
             } else {
-                write!(f, "void ")?;
+                return_ty = self.c_arg_type(output)?;
             }
-            out_param
-        } else {
-            write!(f, "void ")?;
-            None
-        };
-
-        write!(f, "{}", convention_decl)?;
+        }
+        write!(f, "{return_ty} ")?;
 
         // Now write out the args
-        write!(f, "{}(", function.name)?;
+        write!(f, "{declarator}(")?;
         for (idx, input) in function.inputs.iter().enumerate() {
             if idx != 0 {
                 write!(f, ", ")?;
@@ -402,30 +711,325 @@ impl CcAbiImpl {
         }
 
         // Add extra implicit args
-        if let Some(out_param) = out_param {
-            if !function.inputs.is_empty() {
+        for (idx, out_param) in out_params.iter().enumerate() {
+            if idx != 0 || !function.inputs.is_empty() {
                 write!(f, ", ")?;
             }
             write!(f, "{out_param}")?;
-        } else if function.inputs.is_empty() {
+        }
+        if function.inputs.is_empty() && out_params.is_empty() {
+            if function.is_variadic() {
+                return Err(GenerateError::CUnsupported(
+                    "variadic functions need at least one fixed arg".to_string(),
+                ));
+            }
             write!(f, "void")?;
         }
+        if function.is_variadic() {
+            write!(f, ", ...")?;
+        }
         write!(f, ")")?;
 
         Ok(())
     }
 
+    /// Emit the body of a function that reports its inputs, calls any callbacks
+    /// it was passed, writes through its mutable refs, and then reports and
+    /// returns its output.
+    #[allow(clippy::too_many_arguments)]
+    fn write_c_body(
+        &self,
+        f: &mut dyn Write,
+        function: &Func,
+        convention: CallingConvention,
+        inputs: &str,
+        outputs: &str,
+        postcall: &str,
+        finish_func: bool,
+        widened_inputs: bool,
+    ) -> Result<(), GenerateError> {
+        writeln!(f)?;
+        if function.is_variadic() {
+            self.write_c_va_args(f, function)?;
+            writeln!(f)?;
+        }
+        for (idx, input) in function.all_inputs().enumerate() {
+            let write = if idx < function.inputs.len() {
+                self.c_write_input(input, inputs, ARG_NAMES[idx], false, widened_inputs)?
+            } else {
+                self.c_write_val(input, inputs, ARG_NAMES[idx], false)?
+            };
+            writeln!(f, "{write}")?;
+        }
+        writeln!(f)?;
+        for (arg_idx, callback) in function.callbacks() {
+            let arg_name = ARG_NAMES[arg_idx];
+            let var_prefix = format!("{arg_name}_");
+            self.write_c_call(
+                f,
+                &callback,
+                convention,
+                arg_name,
+                &var_prefix,
+                inputs,
+                outputs,
+                postcall,
+            )?;
+            writeln!(f)?;
+        }
+        for (arg_idx, after) in function.mut_refs() {
+            // Report it by reading it back through the ref, so a callee that
+            // got a copy will still think it wrote the value
+            let arg_name = ARG_NAMES[arg_idx];
+            let after_name = format!("{arg_name}_after");
+            writeln!(
+                f,
+                "    {} = {};",
+                self.c_var_decl(after, &after_name)?,
+                self.c_val(after)?
+            )?;
+            writeln!(
+                f,
+                "    memcpy({arg_name}, &{after_name}, sizeof({after_name}));"
+            )?;
+            let after_ref = Val::Ref(Box::new(after.clone()));
+            writeln!(
+                f,
+                "{}",
+                self.c_write_val(&after_ref, postcall, arg_name, false)?
+            )?;
+            writeln!(f)?;
+        }
+        let lowered_outputs = function.lowered_outputs();
+        for (idx, output) in lowered_outputs.iter().enumerate() {
+            let var_name = output_name(idx);
+            writeln!(
+                f,
+                "    {} = {};",
+                self.c_var_decl(output, &var_name)?,
+                self.c_val(output)?
+            )?;
+            writeln!(f, "{}", self.c_write_val(output, outputs, &var_name, true)?)?;
+        }
+        if finish_func {
+            writeln!(f, "    FINISHED_FUNC({inputs}, {outputs});")?;
+        }
+        // The first output may be a real return, so it has to go last
+        for (idx, output) in lowered_outputs.iter().enumerate().rev() {
+            writeln!(
+                f,
+                "    {}",
+                self.c_var_return(output, &output_name(idx), &out_param_name(idx))?
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Emit the unpacking of a variadic function's varargs into locals
+    /// that look just like the fixed args.
+    fn write_c_va_args(&self, f: &mut dyn Write, function: &Func) -> Result<(), GenerateError> {
+        // va_start wants the last fixed arg
+        let lowered_outputs = function.lowered_outputs();
+        let last_arg = if let Some(idx) = lowered_outputs
+            .iter()
+            .rposition(|output| matches!(output, Val::Ref(_)))
+        {
+            out_param_name(idx)
+        } else {
+            ARG_NAMES[function.inputs.len() - 1].to_string()
+        };
+        writeln!(f, "    va_list {VARARGS_NAME};")?;
+        writeln!(f, "    va_start({VARARGS_NAME}, {last_arg});")?;
+        for (idx, input) in function.variadic_inputs.iter().enumerate() {
+            let arg_name = ARG_NAMES[function.inputs.len() + idx];
+            let ty = self.c_arg_type(input)?;
+            // Anything that got promoted has to be read out as its promoted type
+            let val = if let Some(promoted) = input.va_promoted(self.target) {
+                let promoted_ty = self.c_arg_type(&promoted)?;
+                format!("({ty})va_arg({VARARGS_NAME}, {promoted_ty})")
+            } else {
+                format!("va_arg({VARARGS_NAME}, {ty})")
+            };
+            writeln!(f, "    {ty} {arg_name} = {val};")?;
+        }
+        writeln!(f, "    va_end({VARARGS_NAME});")?;
+        Ok(())
+    }
+
+    /// Emit a call to `function` (through `callee`, which may be a function pointer),
+    /// reporting the inputs it's passed, the output it returns, and what its
+    /// mutable refs point to afterwards.
+    ///
+    /// All the local vars will be prefixed with `var_prefix`.
+    #[allow(clippy::too_many_arguments)]
+    fn write_c_call(
+        &self,
+        f: &mut dyn Write,
+        function: &Func,
+        convention: CallingConvention,
+        callee: &str,
+        var_prefix: &str,
+        inputs: &str,
+        outputs: &str,
+        postcall: &str,
+    ) -> Result<(), GenerateError> {
+        // Inputs
+        for (idx, input) in function.all_inputs().enumerate() {
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[idx]);
+            let is_callback = match input {
+                Val::FnPtr(..) => true,
+                Val::Nullable(inner, true) => matches!(**inner, Val::FnPtr(..)),
+                _ => false,
+            };
+            let val = if is_callback {
+                function.callback_name(idx)
+            } else if let Val::MutRef(before, _) = input {
+                self.c_val(before)?
+            } else {
+                self.c_val(input)?
+            };
+            let is_fixed = idx < function.inputs.len();
+            if self.poison && is_fixed && input.poison(self.target) == Some(Poison::Padding) {
+                // Poison the whole thing and then write the fields in, so the padding
+                // is garbage (an initializer would let the compiler zero it)
+                let init_name = format!("{var_name}_init");
+                writeln!(f, "    {} = {};", self.c_var_decl(input, &init_name)?, val)?;
+                writeln!(f, "    {};", self.c_var_decl(input, &var_name)?)?;
+                writeln!(
+                    f,
+                    "    memset(&{var_name}, {POISON_BYTE:#x}, sizeof({var_name}));"
+                )?;
+                for write in self.c_poison_writes(input, &var_name, &init_name) {
+                    writeln!(f, "    {write}")?;
+                }
+            } else {
+                writeln!(f, "    {} = {};", self.c_var_decl(input, &var_name)?, val)?;
+            }
+            let write = if is_fixed {
+                self.c_write_input(input, inputs, &var_name, true, false)?
+            } else {
+                self.c_write_val(input, inputs, &var_name, true)?
+            };
+            writeln!(f, "{write}")?;
+        }
+        writeln!(f)?;
+
+        // Outputs (the out-params have to be declared before the call)
+        let lowered_outputs = function.lowered_outputs();
+        let mut out_params = vec![];
+        for (idx, output) in lowered_outputs.iter().enumerate() {
+            let output_name = format!("{var_prefix}{}", output_name(idx));
+            if let Some(out_param_var) = self.c_out_param_var(output, &output_name)? {
+                writeln!(f, "    {};", out_param_var)?;
+                out_params.push(self.c_arg_pass(output, &output_name)?);
+            }
+        }
+        match lowered_outputs.first() {
+            Some(output) if !matches!(output, Val::Ref(_)) => {
+                let output_name = format!("{var_prefix}{OUTPUT_NAME}");
+                write!(f, "    {} = ", self.c_var_decl(output, &output_name)?)?;
+            }
+            _ => write!(f, "    ")?,
+        }
+
+        // Do the actual call (through a signature with wider args, to poison their high bits)
+        match function
+            .poison_widened(self.target)
+            .filter(|_| self.poisons_high_bits())
+        {
+            Some(widened) => {
+                let mut signature = Vec::new();
+                let convention_decl = self.c_convention_decl(convention)?;
+                let declarator = format!("({convention_decl}*)");
+                self.write_c_fn_decl(&mut signature, &widened, &declarator)?;
+                let signature = String::from_utf8(signature).unwrap();
+                write!(f, "(({signature}){callee})(")?;
+            }
+            None => write!(f, "{}(", callee)?,
+        }
+        for (idx, input) in function.inputs.iter().enumerate() {
+            if idx != 0 {
+                write!(f, ", ")?;
+            }
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[idx]);
+            match input.poison(self.target) {
+                Some(Poison::HighBits { bits, .. }) if self.poisons_high_bits() => write!(
+                    f,
+                    "(((uintptr_t){:#x}ull << {bits}) | (uint{bits}_t){var_name})",
+                    poison_word()
+                )?,
+                _ => write!(f, "{}", self.c_arg_pass(input, &var_name)?)?,
+            }
+        }
+        for (idx, pass) in out_params.iter().enumerate() {
+            if idx != 0 || !function.inputs.is_empty() {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", pass)?;
+        }
+        // The compiler applies the default argument promotions for us
+        for (idx, input) in function.variadic_inputs.iter().enumerate() {
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[function.inputs.len() + idx]);
+            write!(f, ", {}", self.c_arg_pass(input, &var_name)?)?;
+        }
+        writeln!(f, ");")?;
+
+        for (idx, output) in lowered_outputs.iter().enumerate() {
+            let output_name = format!("{var_prefix}{}", output_name(idx));
+            writeln!(
+                f,
+                "{}",
+                self.c_write_val(output, outputs, &output_name, true)?
+            )?;
+        }
+        for (arg_idx, after) in function.mut_refs() {
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[arg_idx]);
+            let after_ref = Val::Ref(Box::new(after.clone()));
+            writeln!(
+                f,
+                "{}",
+                self.c_write_val(&after_ref, postcall, &var_name, true)?
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The attribute to put on a function to enable the given target features.
+    fn c_target_features_decl(&self, features: &[&str]) -> String {
+        // MSVC lets any function use any vector type/intrinsic.
+        if features.is_empty() || self.cc_flavor == CCFlavor::Msvc {
+            String::new()
+        } else {
+            format!("__attribute__((target(\"{}\"))) ", features.join(","))
+        }
+    }
+
     /// Every test should start by loading in the harness' "header"
     /// and forward-declaring any structs that will be used.
-    fn write_c_prefix(&self, f: &mut dyn Write, test: &Test) -> Result<(), GenerateError> {
+    fn write_c_prefix(
+        &self,
+        f: &mut dyn Write,
+        test: &Test,
+        convention: CallingConvention,
+    ) -> Result<(), GenerateError> {
         // Load test harness "headers"
         write!(f, "{}", C_TEST_PREFIX)?;
+        if test.has_vectors() {
+            writeln!(f, "#include <immintrin.h>")?;
+        }
+        if test.funcs.iter().any(Func::is_variadic) {
+            writeln!(f, "#include <stdarg.h>")?;
+        }
+        if self.cxx {
+            // Everything has to have C linkage to talk to the other side
+            writeln!(f, "extern \"C\" {{")?;
+        }
 
         // Forward-decl struct types
         let mut forward_decls = std::collections::HashMap::<String, String>::new();
         for function in &test.funcs {
-            for val in function.inputs.iter().chain(function.output.as_ref()) {
-                for (name, decl) in self.c_forward_decl(val)? {
+            for val in function.vals() {
+                for (name, decl) in self.c_forward_decl(val, convention)? {
                     match forward_decls.entry(name) {
                         std::collections::hash_map::Entry::Occupied(entry) => {
                             if entry.get() != &decl {
@@ -448,27 +1052,224 @@ impl CcAbiImpl {
         Ok(())
     }
 
-    fn c_forward_decl(&self, val: &Val) -> Result<Vec<(String, String)>, GenerateError> {
+    /// Close anything write_c_prefix left open.
+    fn write_c_suffix(&self, f: &mut dyn Write) -> Result<(), GenerateError> {
+        if self.cxx {
+            writeln!(f, "}} // extern \"C\"")?;
+        }
+        Ok(())
+    }
+
+    fn c_forward_decl(
+        &self,
+        val: &Val,
+        convention: CallingConvention,
+    ) -> Result<Vec<(String, String)>, GenerateError> {
         use Val::*;
         match val {
-            Struct(name, fields) => {
+            Struct(name, fields) | ReprStruct(name, _, fields) => {
                 let mut results = vec![];
                 for field in fields.iter() {
-                    results.extend(self.c_forward_decl(field)?);
+                    results.extend(self.c_forward_decl(field, convention)?);
+                }
+                let (packed, align) = match val {
+                    ReprStruct(_, repr, _) => (repr.packed, repr.align),
+                    _ => (None, None),
+                };
+                if packed.is_some() && align.is_some() {
+                    return Err(GenerateError::CUnsupported(format!(
+                        "{name} can't be both packed and over-aligned"
+                    )));
                 }
                 let mut output = String::new();
-                let ref_name = format!("struct {name}");
+                if let Some(packed) = packed {
+                    output.push_str(&format!("#pragma pack(push, {packed})\n"));
+                }
                 output.push_str(&format!("struct {name} {{\n"));
                 for (idx, field) in fields.iter().enumerate() {
-                    let line = format!("    {};\n", self.c_field_decl(field, FIELD_NAMES[idx])?);
+                    // Over-aligning the first field over-aligns the whole struct
+                    // (C can't put _Alignas on the type itself).
+                    let align_decl = match (align, field) {
+                        (Some(_), Val::Bitfield(..)) if idx == 0 => {
+                            return Err(GenerateError::CUnsupported(format!(
+                                "{name} can't be over-aligned if it starts with a bitfield"
+                            )));
+                        }
+                        (Some(align), _) if idx == 0 && self.cxx => format!("alignas({align}) "),
+                        (Some(align), _) if idx == 0 => format!("_Alignas({align}) "),
+                        _ => String::new(),
+                    };
+                    let line = format!(
+                        "    {align_decl}{};\n",
+                        self.c_field_decl(field, FIELD_NAMES[idx])?
+                    );
+                    output.push_str(&line);
+                }
+                output.push_str("};\n");
+                if packed.is_some() {
+                    output.push_str("#pragma pack(pop)\n");
+                }
+                results.push((name.clone(), output));
+                Ok(results)
+            }
+            Class(name, repr, fields) => {
+                let mut results = vec![];
+                for field in fields.iter() {
+                    results.extend(self.c_forward_decl(field, convention)?);
+                }
+                let mut bases = vec![];
+                let mut members = String::new();
+                for (idx, field) in fields.iter().enumerate() {
+                    let Base(base) = field else {
+                        let line =
+                            format!("    {};\n", self.c_field_decl(field, FIELD_NAMES[idx])?);
+                        members.push_str(&line);
+                        continue;
+                    };
+                    if idx != bases.len() {
+                        return Err(GenerateError::CxxUnsupported(format!(
+                            "{name}'s bases have to come before its fields"
+                        )));
+                    }
+                    match &**base {
+                        Struct(base_name, _)
+                        | ReprStruct(base_name, _, _)
+                        | Class(base_name, _, _) => bases.push(base_name.as_str()),
+                        _ => {
+                            return Err(GenerateError::CxxUnsupported(format!(
+                                "{name}'s bases have to be structs or classes"
+                            )));
+                        }
+                    }
+                }
+                let mut output = String::new();
+                if bases.is_empty() {
+                    output.push_str(&format!("struct {name} {{\n"));
+                } else {
+                    output.push_str(&format!("struct {name} : {} {{\n", bases.join(", ")));
+                }
+                output.push_str(&members);
+                if repr.non_trivial {
+                    output.push_str(&format!("    ~{name}() {{}}\n"));
+                }
+                output.push_str("};\n");
+                results.push((name.clone(), output));
+                Ok(results)
+            }
+            Union(name, variants, _active) => {
+                let mut results = vec![];
+                for variant in variants.iter() {
+                    results.extend(self.c_forward_decl(variant, convention)?);
+                }
+                let mut output = String::new();
+                output.push_str(&format!("union {name} {{\n"));
+                for (idx, variant) in variants.iter().enumerate() {
+                    let line = format!("    {};\n", self.c_field_decl(variant, FIELD_NAMES[idx])?);
                     output.push_str(&line);
                 }
                 output.push_str("};\n");
-                results.push((ref_name, output));
+                results.push((name.clone(), output));
+                Ok(results)
+            }
+            Transparent(name, inner) => {
+                // repr(transparent) means it's passed exactly like the inner type
+                let mut results = self.c_forward_decl(inner, convention)?;
+                let inner_ty = self.c_arg_type(inner)?;
+                results.push((name.clone(), format!("typedef {inner_ty} {name};\n")));
+                Ok(results)
+            }
+            Enum(name, discriminants, _active) => {
+                // C can't specify the repr of an enum, so just use the int type
+                let repr = self.c_arg_type(&Int(discriminants[0].clone()))?;
+                Ok(vec![(name.clone(), format!("typedef {repr} {name};\n"))])
+            }
+            TaggedUnion(name, variants, _active) => {
+                // Lower this to the layout specified by RFC 2195:
+                // a struct containing the tag and a union of the variants' fields.
+                let mut results = vec![];
+                for (_discriminant, fields) in variants.iter() {
+                    for field in fields.iter() {
+                        results.extend(self.c_forward_decl(field, convention)?);
+                    }
+                }
+
+                let tag_name = format!("{name}_Tag");
+                let repr = self.c_arg_type(&Int(variants[0].0.clone()))?;
+                results.push((tag_name.clone(), format!("typedef {repr} {tag_name};\n")));
+
+                let mut payload = String::new();
+                for (idx, (_discriminant, fields)) in variants.iter().enumerate() {
+                    // Fieldless variants are omitted from the union
+                    if fields.is_empty() {
+                        continue;
+                    }
+                    let variant_name = format!("{name}_{}", VARIANT_NAMES[idx]);
+                    let mut output = String::new();
+                    output.push_str(&format!("struct {variant_name} {{\n"));
+                    for (field_idx, field) in fields.iter().enumerate() {
+                        let line = format!(
+                            "    {};\n",
+                            self.c_field_decl(field, FIELD_NAMES[field_idx])?
+                        );
+                        output.push_str(&line);
+                    }
+                    output.push_str("};\n");
+                    results.push((variant_name.clone(), output));
+                    payload.push_str(&format!(
+                        "    struct {variant_name} {};\n",
+                        VARIANT_NAMES[idx]
+                    ));
+                }
+
+                let mut output = String::new();
+                output.push_str(&format!("struct {name} {{\n"));
+                output.push_str(&format!("    {tag_name} {TAG_NAME};\n"));
+                if !payload.is_empty() {
+                    let payload_name = format!("{name}_Payload");
+                    let payload_decl = format!("union {payload_name} {{\n{payload}}};\n");
+                    results.push((payload_name.clone(), payload_decl));
+                    output.push_str(&format!("    union {payload_name} {PAYLOAD_NAME};\n"));
+                }
+                output.push_str("};\n");
+                results.push((name.clone(), output));
+                Ok(results)
+            }
+            FnPtr(name, inputs, output) => {
+                let mut results = vec![];
+                for val in inputs.iter().chain(output.as_deref()) {
+                    results.extend(self.c_forward_decl(val, convention)?);
+                }
+                let signature = Func {
+                    name: name.clone(),
+                    conventions: vec![convention],
+                    inputs: inputs.clone(),
+                    variadic_inputs: vec![],
+                    output: output.as_deref().cloned().into_iter().collect(),
+                };
+                let convention_decl = self.c_convention_decl(convention)?;
+                let mut output = Vec::new();
+                write!(output, "typedef ")?;
+                self.write_c_fn_decl(
+                    &mut output,
+                    &signature,
+                    &format!("({convention_decl}*{name})"),
+                )?;
+                writeln!(output, ";")?;
+                results.push((name.clone(), String::from_utf8(output).unwrap()));
+                Ok(results)
+            }
+            Array(vals) => self.c_forward_decl(&vals[0], convention),
+            EmptyArray(elem) => self.c_forward_decl(elem, convention),
+            Ref(pointee)
+            | Nullable(pointee, _)
+            | CxxRef(pointee)
+            | Base(pointee)
+            | NoUniqueAddress(pointee) => self.c_forward_decl(pointee, convention),
+            MutRef(before, after) => {
+                let mut results = self.c_forward_decl(before, convention)?;
+                results.extend(self.c_forward_decl(after, convention)?);
                 Ok(results)
             }
-            Array(vals) => self.c_forward_decl(&vals[0]),
-            Ref(pointee) => self.c_forward_decl(pointee),
             _ => Ok(vec![]),
         }
     }
@@ -476,40 +1277,23 @@ impl CcAbiImpl {
     /// The decl to use for a local var (reference-ness stripped)
     fn c_var_decl(&self, val: &Val, var_name: &str) -> Result<String, GenerateError> {
         use Val::*;
-        let val = match val {
-            Ref(pointee) => self.c_var_decl(pointee, var_name)?,
-            Array(_) => {
-                let mut cur_val = val;
-                let mut array_levels = String::new();
-                while let Val::Array(vals) = cur_val {
-                    array_levels.push_str(&format!("[{}]", vals.len()));
-                    cur_val = &vals[0];
-                }
-                format!("{} {var_name}{array_levels}", self.c_arg_type(cur_val)?)
+        match val {
+            Ref(pointee) | MutRef(pointee, _) | CxxRef(pointee) => {
+                self.c_declare(pointee, var_name)
             }
-            normal_val => format!("{} {var_name}", self.c_arg_type(normal_val)?),
-        };
-        Ok(val)
+            // The var is the pointee, which may not be passed
+            Nullable(inner, _) if matches!(**inner, Ref(_)) => self.c_var_decl(inner, var_name),
+            _ => self.c_declare(val, var_name),
+        }
     }
 
     /// The decl to use for a function arg (apply referenceness)
     fn c_arg_decl(&self, val: &Val, arg_name: &str) -> Result<String, GenerateError> {
-        let out = if let Val::Ref(pointee) = val {
-            let mut cur_val = &**pointee;
-            let mut array_levels = String::new();
-            while let Val::Array(vals) = cur_val {
-                array_levels.push_str(&format!("[{}]", vals.len()));
-                cur_val = &vals[0];
-            }
-            if array_levels.is_empty() {
-                format!("{}* {arg_name}", self.c_arg_type(cur_val)?)
-            } else {
-                format!("{} {arg_name}{array_levels}", self.c_arg_type(cur_val)?)
-            }
+        if let Val::Ref(_) | Val::MutRef(..) | Val::CxxRef(_) = val {
+            self.c_declare(val, arg_name)
         } else {
-            format!("{} {arg_name}", self.c_arg_type(val)?)
-        };
-        Ok(out)
+            Ok(format!("{} {arg_name}", self.c_arg_type(val)?))
+        }
     }
 
     /// If the return type needs to be an out_param, this returns it
@@ -518,25 +1302,42 @@ impl CcAbiImpl {
         val: &Val,
         out_param_name: &str,
     ) -> Result<Option<String>, GenerateError> {
-        let out = if let Val::Ref(pointee) = val {
-            let mut cur_val = &**pointee;
-            let mut array_levels = String::new();
-            while let Val::Array(vals) = cur_val {
-                array_levels.push_str(&format!("[{}]", vals.len()));
-                cur_val = &vals[0];
-            }
-            if array_levels.is_empty() {
-                Some(format!("{}* {out_param_name}", self.c_arg_type(cur_val)?))
+        if let Val::Ref(_) = val {
+            Ok(Some(self.c_declare(val, out_param_name)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Declare `declarator` with this value's type, which is how C spells things
+    /// like pointers to arrays and arrays of pointers (an empty `declarator` gives
+    /// just the type).
+    ///
+    /// Refs to arrays are pointers to their first element, just like how arrays decay
+    /// (but C++ references to arrays really are references to the whole array).
+    fn c_declare(&self, val: &Val, declarator: &str) -> Result<String, GenerateError> {
+        if let Val::Ref(pointee) | Val::MutRef(pointee, _) = val {
+            let pointee = pointee.array_elem().map_or(&**pointee, |(_len, elem)| elem);
+            return self.c_declare(pointee, &format!("*{declarator}"));
+        }
+        if let Val::CxxRef(pointee) = val {
+            return self.c_declare(pointee, &format!("&{declarator}"));
+        }
+        if let Some((len, elem)) = val.array_elem() {
+            // Arrays bind tighter than pointers/references
+            let declarator = if declarator.starts_with(['*', '&']) {
+                format!("({declarator})")
             } else {
-                Some(format!(
-                    "{} {out_param_name}{array_levels}",
-                    self.c_arg_type(cur_val)?
-                ))
-            }
+                declarator.to_string()
+            };
+            return self.c_declare(elem, &format!("{declarator}[{len}]"));
+        }
+        let ty = self.c_arg_type(val)?;
+        if declarator.is_empty() {
+            Ok(ty)
         } else {
-            None
-        };
-        Ok(out)
+            Ok(format!("{ty} {declarator}"))
+        }
     }
 
     /// If the return type needs to be an out_param, this returns it
@@ -545,8 +1346,8 @@ impl CcAbiImpl {
         val: &Val,
         output_name: &str,
     ) -> Result<Option<String>, GenerateError> {
-        if let Val::Ref(pointee) = val {
-            Ok(Some(self.c_var_decl(pointee, output_name)?))
+        if let Val::Ref(_) = val {
+            Ok(Some(self.c_var_decl(val, output_name)?))
         } else {
             Ok(None)
         }
@@ -554,8 +1355,17 @@ impl CcAbiImpl {
 
     /// How to pass an argument
     fn c_arg_pass(&self, val: &Val, arg_name: &str) -> Result<String, GenerateError> {
-        if let Val::Ref(pointee) = val {
-            if let Val::Array(_) = &**pointee {
+        if let Val::Nullable(inner, is_some) = val {
+            if let Val::Ref(_) = **inner {
+                return Ok(if *is_some {
+                    format!("&{arg_name}")
+                } else {
+                    "NULL".to_string()
+                });
+            }
+        }
+        if let Val::Ref(pointee) | Val::MutRef(pointee, _) = val {
+            if pointee.array_elem().is_some() {
                 Ok(arg_name.to_string())
             } else {
                 Ok(format!("&{arg_name}"))
@@ -586,16 +1396,31 @@ impl CcAbiImpl {
         use IntVal::*;
         use Val::*;
         let val = match val {
-            Ref(pointee) => {
-                let mut cur_val = &**pointee;
-                while let Val::Array(vals) = cur_val {
-                    cur_val = &vals[0];
+            Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
+            Ref(_) => self.c_declare(val, "")?,
+            MutRef(before, after) => {
+                if self.c_field_decl(before, "")? != self.c_field_decl(after, "")? {
+                    return Err(GenerateError::CUnsupported(
+                        "a mutable ref's value after the call must have the same type".to_string(),
+                    ));
                 }
-                format!("{}*", self.c_arg_type(cur_val)?)
+                self.c_arg_type(&Ref(before.clone()))?
+            }
+            CxxRef(_) if self.cxx => self.c_declare(val, "")?,
+            Class(name, _, _) if self.cxx => format!("struct {name}"),
+            CxxRef(_) | Class(..) => {
+                return Err(GenerateError::CUnsupported(
+                    "C++-only types don't exist in C".to_string(),
+                ));
+            }
+            Base(_) | NoUniqueAddress(_) => {
+                return Err(GenerateError::CUnsupported(
+                    "bases and [[no_unique_address]] fields can only be class fields".to_string(),
+                ));
             }
             Ptr(_) => "void*".to_string(),
             Bool(_) => "bool".to_string(),
-            Array(_vals) => {
+            Array(_) | EmptyArray(_) => {
                 // C arrays are kinda fake due to how they decay in function arg
                 // position, so a ton of code needs to very delicately detect arrays
                 // and desugar them properly. Since most things eventually sink into
@@ -609,9 +1434,109 @@ impl CcAbiImpl {
                     "C Arrays can't be passed directly, wrap this in Ref".to_string(),
                 ));
             }
-            Struct(name, _) => format!("struct {name}"),
+            Struct(_, fields) | ReprStruct(_, _, fields)
+                if fields.is_empty() && self.cc_flavor == CCFlavor::Msvc =>
+            {
+                return Err(GenerateError::CUnsupported(
+                    "MSVC doesn't allow empty structs in C".to_string(),
+                ));
+            }
+            Struct(name, _) | ReprStruct(name, _, _) => format!("struct {name}"),
+            Transparent(name, inner) => {
+                if let Ref(_) = **inner {
+                    return Err(GenerateError::CUnsupported(
+                        "transparent wrappers around refs aren't supported".to_string(),
+                    ));
+                }
+                // Arrays will bail out here
+                self.c_arg_type(inner)?;
+                name.to_string()
+            }
+            Union(name, _, _) => format!("union {name}"),
+            Enum(name, _, _) => name.to_string(),
+            TaggedUnion(name, _, _) => format!("struct {name}"),
+            FnPtr(name, _, _) => name.to_string(),
+            Nullable(inner, is_some) => {
+                inner
+                    .check_nullable(*is_some)
+                    .map_err(GenerateError::CUnsupported)?;
+                if let Ref(pointee) = &**inner {
+                    if pointee.array_elem().is_some() {
+                        return Err(GenerateError::CUnsupported(
+                            "nullable refs to arrays aren't supported".to_string(),
+                        ));
+                    }
+                }
+                // Already nullable in C
+                self.c_arg_type(inner)?
+            }
+            Char(_) | Unit | Tuple(_) | Slice(_) | Str(_) | Boxed(_) | RustOption(..) => {
+                return Err(GenerateError::CUnsupported(
+                    "Rust-only types don't exist in C".to_string(),
+                ));
+            }
+            Bitfield(..) => {
+                return Err(GenerateError::CUnsupported(
+                    "bitfields can only be struct fields".to_string(),
+                ));
+            }
+            Vector(vector_val) => match vector_val {
+                VectorVal::c__m128(_) => "__m128".to_string(),
+                VectorVal::c__m128d(_) => "__m128d".to_string(),
+                VectorVal::c__m128i(_) => "__m128i".to_string(),
+                VectorVal::c__m256(_) => "__m256".to_string(),
+                VectorVal::c__m256d(_) => "__m256d".to_string(),
+                VectorVal::c__m256i(_) => "__m256i".to_string(),
+            },
             Float(FloatVal::c_double(_)) => "double".to_string(),
             Float(FloatVal::c_float(_)) => "float".to_string(),
+            Float(FloatVal::c_long_double(_)) => "long double".to_string(),
+            Float(FloatVal::c__Complex_float(..) | FloatVal::c__Complex_double(..)) if self.cxx => {
+                return Err(GenerateError::CxxUnsupported(
+                    "C++ doesn't have _Complex (and std::complex is a class)".to_string(),
+                ));
+            }
+            Float(FloatVal::c__Complex_float(..) | FloatVal::c__Complex_double(..))
+                if self.cc_flavor == CCFlavor::Msvc =>
+            {
+                return Err(GenerateError::CUnsupported(
+                    "MSVC doesn't have _Complex".to_string(),
+                ));
+            }
+            Float(FloatVal::c__Complex_float(..)) => "_Complex float".to_string(),
+            Float(FloatVal::c__Complex_double(..)) => "_Complex double".to_string(),
+            Float(FloatVal::c__Float16(_)) => {
+                let has_float16 = matches!(
+                    TargetTriple::new(self.target).arch,
+                    "x86" | "x86_64" | "aarch64" | "arm"
+                );
+                if self.cc_flavor == CCFlavor::Msvc || !has_float16 {
+                    return Err(GenerateError::CUnsupported(
+                        "_Float16 isn't available on this platform".to_string(),
+                    ));
+                }
+                "_Float16".to_string()
+            }
+            Float(FloatVal::c__float128(_)) => {
+                if self.cc_flavor == CCFlavor::Msvc {
+                    return Err(GenerateError::CUnsupported(
+                        "MSVC doesn't have a 128-bit float".to_string(),
+                    ));
+                } else if matches!(TargetTriple::new(self.target).arch, "x86" | "x86_64") {
+                    "__float128".to_string()
+                } else if LongDoubleFormat::for_target(self.target) == LongDoubleFormat::Quad {
+                    "long double".to_string()
+                } else {
+                    return Err(GenerateError::CUnsupported(
+                        "__float128 isn't available on this platform".to_string(),
+                    ));
+                }
+            }
+            Int(c__int128(_) | c__uint128(_)) if self.cc_flavor == CCFlavor::Msvc => {
+                return Err(GenerateError::CUnsupported(
+                    "MSVC doesn't have 128-bit ints".to_string(),
+                ));
+            }
             Int(int_val) => match int_val {
                 c__int128(_) => "__int128_t".to_string(),
                 c_int64_t(_) => "int64_t".to_string(),
@@ -623,6 +1548,22 @@ impl CcAbiImpl {
                 c_uint32_t(_) => "uint32_t".to_string(),
                 c_uint16_t(_) => "uint16_t".to_string(),
                 c_uint8_t(_) => "uint8_t".to_string(),
+                c_char(_) => "char".to_string(),
+                c_schar(_) => "signed char".to_string(),
+                c_uchar(_) => "unsigned char".to_string(),
+                c_short(_) => "short".to_string(),
+                c_ushort(_) => "unsigned short".to_string(),
+                c_int(_) => "int".to_string(),
+                c_uint(_) => "unsigned int".to_string(),
+                c_long(_) => "long".to_string(),
+                c_ulong(_) => "unsigned long".to_string(),
+                c_longlong(_) => "long long".to_string(),
+                c_ulonglong(_) => "unsigned long long".to_string(),
+                c_size_t(_) => "size_t".to_string(),
+                c_ptrdiff_t(_) => "ptrdiff_t".to_string(),
+                c_intptr_t(_) => "intptr_t".to_string(),
+                c_uintptr_t(_) => "uintptr_t".to_string(),
+                c_wchar_t(_) => "wchar_t".to_string(),
             },
         };
         Ok(val)
@@ -634,16 +1575,17 @@ impl CcAbiImpl {
     /// handling in this context to conform to a layout (i.e. how C arrays
     /// decay into pointers when used in function args).
     fn c_field_decl(&self, val: &Val, field_name: &str) -> Result<String, GenerateError> {
-        let mut cur_val = val;
-        let mut array_levels = String::new();
-        while let Val::Array(vals) = cur_val {
-            array_levels.push_str(&format!("[{}]", vals.len()));
-            cur_val = &vals[0];
+        if let Val::Bitfield(int_val, width) = val {
+            let ty = self.c_arg_type(&Val::Int(int_val.clone()))?;
+            return Ok(format!("{ty} {field_name} : {width}"));
+        }
+        if let Val::NoUniqueAddress(field) = val {
+            return Ok(format!(
+                "[[no_unique_address]] {}",
+                self.c_declare(field, field_name)?
+            ));
         }
-        Ok(format!(
-            "{} {field_name}{array_levels}",
-            self.c_arg_type(cur_val)?
-        ))
+        self.c_declare(val, field_name)
     }
 
     /// An expression that generates this value.
@@ -651,7 +1593,9 @@ impl CcAbiImpl {
         use IntVal::*;
         use Val::*;
         let val = match val {
-            Ref(pointee) => self.c_val(pointee)?,
+            Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
+            // The var is the pointee
+            Ref(pointee) | CxxRef(pointee) => self.c_nested_val(pointee)?,
             Ptr(addr) => format!("(void*){addr:#X}ull"),
             Bool(val) => format!("{val}"),
             Array(vals) => {
@@ -661,25 +1605,112 @@ impl CcAbiImpl {
                     if idx != 0 {
                         output.push_str(", ");
                     }
-                    let part = (self.c_val(elem)?).to_string();
+                    let part = self.c_nested_val(elem)?;
                     output.push_str(&part);
                 }
                 output.push_str(" }");
                 output
             }
-            Struct(_name, fields) => {
+            EmptyArray(_) => "{ }".to_string(),
+            Transparent(_name, inner) => self.c_val(inner)?,
+            Struct(_name, fields) | ReprStruct(_name, _, fields) => {
                 let mut output = String::new();
                 output.push_str("{ ");
                 for (idx, field) in fields.iter().enumerate() {
                     if idx != 0 {
                         output.push_str(", ");
                     }
-                    let part = format!(".{} = {}", FIELD_NAMES[idx], self.c_val(field)?);
+                    let part = format!(".{} = {}", FIELD_NAMES[idx], self.c_nested_val(field)?);
                     output.push_str(&part);
                 }
                 output.push_str(" }");
                 output
             }
+            Class(_name, _, fields) => {
+                // Bases can't be designated, so these are always initialized in order
+                let mut output = String::new();
+                output.push_str("{ ");
+                for (idx, field) in fields.iter().enumerate() {
+                    if idx != 0 {
+                        output.push_str(", ");
+                    }
+                    output.push_str(&self.c_nested_val(field)?);
+                }
+                output.push_str(" }");
+                output
+            }
+            Base(inner) | NoUniqueAddress(inner) => self.c_val(inner)?,
+            Union(_name, variants, active) => {
+                format!(
+                    "{{ .{} = {} }}",
+                    FIELD_NAMES[*active],
+                    self.c_nested_val(&variants[*active])?
+                )
+            }
+            Enum(_name, discriminants, active) => {
+                self.c_val(&Int(discriminants[*active].clone()))?
+            }
+            TaggedUnion(_name, variants, active) => {
+                let (discriminant, fields) = &variants[*active];
+                let mut output = String::new();
+                output.push_str(&format!(
+                    "{{ .{TAG_NAME} = {}",
+                    self.c_val(&Int(discriminant.clone()))?
+                ));
+                if !fields.is_empty() {
+                    output.push_str(&format!(
+                        ", .{PAYLOAD_NAME} = {{ .{} = {{ ",
+                        VARIANT_NAMES[*active]
+                    ));
+                    for (idx, field) in fields.iter().enumerate() {
+                        if idx != 0 {
+                            output.push_str(", ");
+                        }
+                        let part = format!(".{} = {}", FIELD_NAMES[idx], self.c_nested_val(field)?);
+                        output.push_str(&part);
+                    }
+                    output.push_str(" } }");
+                }
+                output.push_str(" }");
+                output
+            }
+            Bitfield(int_val, _width) => self.c_val(&Int(int_val.clone()))?,
+            Nullable(inner, true) => self.c_val(inner)?,
+            Nullable(inner, false) => match **inner {
+                Ref(ref pointee) => self.c_nested_val(pointee)?,
+                Int(_) => "0".to_string(),
+                _ => "NULL".to_string(),
+            },
+            FnPtr(..) => {
+                // The caller passes its generated callback, see Func::callbacks
+                return Err(GenerateError::CUnsupported(
+                    "function pointers can only be passed directly as arguments".to_string(),
+                ));
+            }
+            MutRef(..) => {
+                // The caller stores the value before the call, see write_c_call
+                return Err(GenerateError::CUnsupported(
+                    "mutable refs can only be passed directly as arguments".to_string(),
+                ));
+            }
+            Char(_) | Unit | Tuple(_) | Slice(_) | Str(_) | Boxed(_) | RustOption(..) => {
+                return Err(GenerateError::CUnsupported(
+                    "Rust-only types don't exist in C".to_string(),
+                ));
+            }
+            Vector(vector_val) => {
+                // Use the intrinsics instead of initializer lists, because
+                // msvc defines these types as unions of arrays.
+                let (constructor, lanes) = match vector_val {
+                    VectorVal::c__m128(lanes) => ("_mm_setr_ps", self.c_float_lanes(lanes)?),
+                    VectorVal::c__m128d(lanes) => ("_mm_setr_pd", self.c_double_lanes(lanes)?),
+                    VectorVal::c__m128i(lanes) => ("_mm_setr_epi32", self.c_int_lanes(lanes)),
+                    VectorVal::c__m256(lanes) => ("_mm256_setr_ps", self.c_float_lanes(lanes)?),
+                    VectorVal::c__m256d(lanes) => ("_mm256_setr_pd", self.c_double_lanes(lanes)?),
+                    VectorVal::c__m256i(lanes) => ("_mm256_setr_epi32", self.c_int_lanes(lanes)),
+                };
+                format!("{constructor}({})", lanes.join(", "))
+            }
             Float(FloatVal::c_double(val)) => {
                 if val.fract() == 0.0 {
                     format!("{val}.0")
@@ -694,6 +1725,30 @@ impl CcAbiImpl {
                     format!("{val}f")
                 }
             }
+            Float(FloatVal::c__Complex_float(re, im)) => format!(
+                "__builtin_complex({}, {})",
+                self.c_val(&Float(FloatVal::c_float(*re)))?,
+                self.c_val(&Float(FloatVal::c_float(*im)))?
+            ),
+            Float(FloatVal::c__Complex_double(re, im)) => format!(
+                "__builtin_complex({}, {})",
+                self.c_val(&Float(FloatVal::c_double(*re)))?,
+                self.c_val(&Float(FloatVal::c_double(*im)))?
+            ),
+            Float(float_val) => {
+                // There aren't portable literals for these, so pun the exact bytes
+                let bytes = float_val.bytes(self.target).unwrap();
+                let bytes = bytes
+                    .iter()
+                    .map(|byte| format!("{byte:#04X}"))
+                    .collect::<Vec<_>>();
+                format!(
+                    "((union {{ unsigned char bytes[{}]; {} val; }}){{ .bytes = {{ {} }} }}).val",
+                    bytes.len(),
+                    self.c_arg_type(val)?,
+                    bytes.join(", ")
+                )
+            }
             Int(int_val) => match *int_val {
                 c__int128(val) => {
                     let lower = (val as u128) & 0x0000_0000_0000_0000_FFFF_FFFF_FFFF_FFFF;
@@ -713,11 +1768,83 @@ impl CcAbiImpl {
                 c_uint32_t(val) => format!("{val:#X}"),
                 c_uint16_t(val) => format!("{val:#X}"),
                 c_uint8_t(val) => format!("{val:#X}"),
+                // Let the cast truncate the value to whatever width the platform uses
+                c_char(wide) | c_schar(wide) | c_short(wide) | c_int(wide) | c_long(wide)
+                | c_longlong(wide) | c_ptrdiff_t(wide) | c_intptr_t(wide) | c_wchar_t(wide) => {
+                    format!("({})({wide}ll)", self.c_arg_type(val)?)
+                }
+                c_uchar(wide) | c_ushort(wide) | c_uint(wide) | c_ulong(wide)
+                | c_ulonglong(wide) | c_size_t(wide) | c_uintptr_t(wide) => {
+                    format!("({})({wide:#X}ull)", self.c_arg_type(val)?)
+                }
             },
         };
         Ok(val)
     }
 
+    /// An expression that generates this value when it's nested inside another.
+    ///
+    /// Unlike a var, a nested ref has to point at something that outlives the
+    /// callee (it might be returning it), so it gets a leaked copy of its pointee.
+    fn c_nested_val(&self, val: &Val) -> Result<String, GenerateError> {
+        match val {
+            Val::Ref(pointee) => {
+                let ty = self.c_declare(pointee, "")?;
+                let ptr_ty = self.c_declare(val, "")?;
+                let literal = self.c_temp(&ty, &self.c_nested_val(pointee)?);
+                // Arrays decay on their own
+                let src = if pointee.array_elem().is_some() {
+                    literal
+                } else {
+                    format!("&{literal}")
+                };
+                Ok(format!(
+                    "({ptr_ty})memcpy(malloc(sizeof({ty})), {src}, sizeof({ty}))"
+                ))
+            }
+            Val::CxxRef(_) => Err(GenerateError::CxxUnsupported(
+                "references can only be passed directly as arguments".to_string(),
+            )),
+            Val::Nullable(inner, true) if matches!(**inner, Val::Ref(_)) => {
+                self.c_nested_val(inner)
+            }
+            Val::Nullable(inner, false) if matches!(**inner, Val::Ref(_)) => Ok("NULL".to_string()),
+            _ => self.c_val(val),
+        }
+    }
+
+    /// A temporary `ty` initialized with `init` (an expression or an initializer list),
+    /// for taking the address of things that don't have one.
+    ///
+    /// That's just a compound literal in C, but those are temporaries in C++, so there
+    /// the harness' `LVALUE` lets it have an address (until the end of the statement).
+    fn c_temp(&self, ty: &str, init: &str) -> String {
+        match (self.cxx, init.starts_with('{')) {
+            (false, true) => format!("(({ty}){init})"),
+            (false, false) => format!("(({ty}){{ {init} }})"),
+            (true, true) => format!("LVALUE(({ty}){init})"),
+            (true, false) => format!("LVALUE(({ty})({init}))"),
+        }
+    }
+
+    fn c_float_lanes(&self, lanes: &[f32]) -> Result<Vec<String>, GenerateError> {
+        lanes
+            .iter()
+            .map(|&lane| self.c_val(&Val::Float(FloatVal::c_float(lane))))
+            .collect()
+    }
+
+    fn c_double_lanes(&self, lanes: &[f64]) -> Result<Vec<String>, GenerateError> {
+        lanes
+            .iter()
+            .map(|&lane| self.c_val(&Val::Float(FloatVal::c_double(lane))))
+            .collect()
+    }
+
+    fn c_int_lanes(&self, lanes: &[u32]) -> Vec<String> {
+        lanes.iter().map(|lane| format!("(int){lane:#X}")).collect()
+    }
+
     /// Emit the WRITE calls and FINISHED_VAL for this value.
     /// This will WRITE every leaf subfield of the type.
     /// `to` is the BUFFER to use, `from` is the variable name of the value.
@@ -728,9 +1855,47 @@ impl CcAbiImpl {
         from: &str,
         is_var_root: bool,
     ) -> Result<String, GenerateError> {
+        let paths = self.c_var_paths(val, from, is_var_root)?;
+        Ok(self.c_write_paths(&paths, to))
+    }
+
+    /// Like c_write_val, but for a fixed input: in `--poison` mode, anything
+    /// that got poisoned also has its raw bytes reported, after its fields.
+    ///
+    /// If `widened`, narrow inputs were actually taken as pointer-sized ints
+    /// (see [`Func::poison_widened`][]), and their raw bytes are the whole thing.
+    fn c_write_input(
+        &self,
+        val: &Val,
+        to: &str,
+        from: &str,
+        is_var_root: bool,
+        widened: bool,
+    ) -> Result<String, GenerateError> {
+        let mut paths = self.c_var_paths(val, from, is_var_root)?;
+        match val.poison(self.target) {
+            Some(Poison::Padding) if self.poison => paths.push(from.to_string()),
+            Some(Poison::HighBits { bits, .. }) if widened => {
+                let ty = self.c_arg_type(val)?;
+                paths = vec![
+                    self.c_temp(&ty, &format!("(uint{bits}_t){from}")),
+                    from.to_string(),
+                ];
+            }
+            Some(Poison::HighBits { signed, .. }) if self.poison || self.check_extension => {
+                let ty = if signed { "intptr_t" } else { "uintptr_t" };
+                paths.push(self.c_temp(ty, from));
+            }
+            _ => {}
+        }
+        Ok(self.c_write_paths(&paths, to))
+    }
+
+    /// Write out every path in `paths` as one value.
+    fn c_write_paths(&self, paths: &[String], to: &str) -> String {
         use std::fmt::Write;
         let mut output = String::new();
-        for path in self.c_var_paths(val, from, is_var_root)? {
+        for path in paths {
             writeln!(
                 output,
                 "    WRITE_FIELD({to}, (char*)&{path}, (uint32_t)sizeof({path}));"
@@ -738,8 +1903,41 @@ impl CcAbiImpl {
             .unwrap();
         }
         write!(output, "    FINISHED_VAL({to});").unwrap();
+        output
+    }
 
-        Ok(output)
+    /// The assignments that copy every field of `from` into `to` one at a time,
+    /// which leaves `to`'s padding alone.
+    fn c_poison_writes(&self, val: &Val, to: &str, from: &str) -> Vec<String> {
+        match val {
+            Val::Struct(_name, fields) | Val::ReprStruct(_name, _, fields) => fields
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, field)| {
+                    let field_name = FIELD_NAMES[idx];
+                    let to = format!("{to}.{field_name}");
+                    let from = format!("{from}.{field_name}");
+                    self.c_poison_writes(field, &to, &from)
+                })
+                .collect(),
+            Val::Union(_name, variants, active) => {
+                let field_name = FIELD_NAMES[*active];
+                let to = format!("{to}.{field_name}");
+                let from = format!("{from}.{field_name}");
+                self.c_poison_writes(&variants[*active], &to, &from)
+            }
+            Val::Array(vals) => vals
+                .iter()
+                .enumerate()
+                .flat_map(|(i, elem)| {
+                    self.c_poison_writes(elem, &format!("{to}[{i}]"), &format!("{from}[{i}]"))
+                })
+                .collect(),
+            Val::EmptyArray(_) => vec![],
+            // It's just a typedef of the inner type
+            Val::Transparent(_name, inner) => self.c_poison_writes(inner, to, from),
+            _ => vec![format!("{to} = {from};")],
+        }
     }
 
     /// Compute the paths to every subfield of this value, with `from`
@@ -751,10 +1949,40 @@ impl CcAbiImpl {
         is_var_root: bool,
     ) -> Result<Vec<String>, GenerateError> {
         let paths = match val {
-            Val::Int(_) | Val::Float(_) | Val::Bool(_) | Val::Ptr(_) => {
+            Val::Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
+            Val::Float(FloatVal::c_long_double(_)) => {
+                // x87's long double has padding that's just whatever garbage, so only
+                // report the bytes that actually hold the value.
+                let size = LongDoubleFormat::for_target(self.target).value_size();
+                vec![format!("(*(unsigned char(*)[{size}])&{from})")]
+            }
+            Val::Int(_)
+            | Val::Float(_)
+            | Val::Bool(_)
+            | Val::Ptr(_)
+            | Val::Enum(..)
+            | Val::Vector(_) => {
                 vec![format!("{from}")]
             }
-            Val::Struct(_name, fields) => {
+            // The callback's own inputs/outputs are reported when it's called
+            Val::FnPtr(..) => vec![],
+            Val::Char(_)
+            | Val::Unit
+            | Val::Tuple(_)
+            | Val::Slice(_)
+            | Val::Str(_)
+            | Val::Boxed(_)
+            | Val::RustOption(..) => {
+                return Err(GenerateError::CUnsupported(
+                    "Rust-only types don't exist in C".to_string(),
+                ));
+            }
+            Val::Bitfield(int_val, _width) => {
+                // Bitfields don't have an address, so copy them into a temporary
+                let ty = self.c_arg_type(&Val::Int(int_val.clone()))?;
+                vec![self.c_temp(&ty, from)]
+            }
+            Val::Struct(_name, fields) | Val::ReprStruct(_name, _, fields) => {
                 let mut paths = vec![];
                 for (idx, field) in fields.iter().enumerate() {
                     let base = format!("{from}.{}", FIELD_NAMES[idx]);
@@ -762,10 +1990,46 @@ impl CcAbiImpl {
                 }
                 paths
             }
-            Val::Ref(pointee) => {
-                if is_var_root {
-                    self.c_var_paths(pointee, from, false)?
-                } else if let Val::Array(_) = &**pointee {
+            Val::Class(_name, _, fields) => {
+                let mut paths = vec![];
+                for (idx, field) in fields.iter().enumerate() {
+                    let base = match field {
+                        // The derived class might shadow the base's fields
+                        Val::Base(base) => {
+                            format!("static_cast<{}&>({from})", self.c_arg_type(base)?)
+                        }
+                        _ => format!("{from}.{}", FIELD_NAMES[idx]),
+                    };
+                    paths.extend(self.c_var_paths(field, &base, false)?);
+                }
+                paths
+            }
+            Val::Base(inner) | Val::NoUniqueAddress(inner) => {
+                self.c_var_paths(inner, from, false)?
+            }
+            // References are just another name for the pointee
+            Val::CxxRef(pointee) => self.c_var_paths(pointee, from, false)?,
+            Val::Union(_name, variants, active) => {
+                // Only the active variant has meaningful bytes
+                let base = format!("{from}.{}", FIELD_NAMES[*active]);
+                self.c_var_paths(&variants[*active], &base, false)?
+            }
+            Val::TaggedUnion(_name, variants, active) => {
+                // Only the tag and the active variant have meaningful bytes
+                let mut paths = vec![format!("{from}.{TAG_NAME}")];
+                let (_discriminant, fields) = &variants[*active];
+                for (idx, field) in fields.iter().enumerate() {
+                    let base = format!(
+                        "{from}.{PAYLOAD_NAME}.{}.{}",
+                        VARIANT_NAMES[*active], FIELD_NAMES[idx]
+                    );
+                    paths.extend(self.c_var_paths(field, &base, false)?);
+                }
+                paths
+            }
+            Val::Ref(pointee) | Val::MutRef(pointee, _) => {
+                // Arrays decay, so the var is already a pointer to their elements
+                if is_var_root || pointee.array_elem().is_some() {
                     self.c_var_paths(pointee, from, false)?
                 } else {
                     let base = format!("(*{from})");
@@ -780,6 +2044,18 @@ impl CcAbiImpl {
                 }
                 paths
             }
+            // There's nothing in here to check
+            Val::EmptyArray(_) => vec![],
+            // It's just a typedef of the inner type
+            Val::Transparent(_name, inner) => self.c_var_paths(inner, from, false)?,
+            Val::Nullable(inner, is_some) => match (&**inner, is_some) {
+                (Val::Ref(_) | Val::FnPtr(..), true) => {
+                    self.c_var_paths(inner, from, is_var_root)?
+                }
+                // The caller's var is the unused pointee, so report a null
+                (Val::Ref(_), false) if is_var_root => vec![self.c_temp("void*", "0")],
+                _ => vec![from.to_string()],
+            },
         };
 
         Ok(paths)
@@ -798,7 +2074,7 @@ impl CcAbiImpl {
             Array(_) => {
                 todo!()
             }
-            Struct(_name, _fields) => {
+            Struct(_name, _fields) | ReprStruct(_name, _, _fields) => {
                 todo!()
             }
             Float(FloatVal::c_double(_val)) => "\"f\"",
@@ -0,0 +1,134 @@
+use crate::Config;
+
+use super::super::*;
+use super::*;
+
+/// C++ is close enough to C that this just uses the C generator in its C++ mode,
+/// which adds the C++-only types, and gives everything C linkage (so that it can
+/// be paired with anything else that speaks C).
+pub struct CxxAbiImpl {
+    c: CcAbiImpl,
+    mode: &'static str,
+}
+
+impl AbiImpl for CxxAbiImpl {
+    fn name(&self) -> &'static str {
+        self.mode
+    }
+    fn lang(&self) -> &'static str {
+        "c++"
+    }
+    fn src_ext(&self) -> &'static str {
+        "cpp"
+    }
+    fn target(&self) -> &'static str {
+        self.c.target()
+    }
+
+    fn supports_convention(&self, convention: CallingConvention) -> bool {
+        self.c.supports_convention(convention)
+    }
+
+    fn supports_val(&self, val: &Val) -> bool {
+        self.c.supports_val(val)
+    }
+
+    fn supports_callee(&self, func: &Func) -> bool {
+        self.c.supports_callee(func)
+    }
+
+    fn generate_callee(
+        &self,
+        f: &mut dyn Write,
+        test: &Test,
+        convention: CallingConvention,
+    ) -> Result<(), GenerateError> {
+        self.c.generate_callee(f, test, convention)
+    }
+
+    fn generate_caller(
+        &self,
+        f: &mut dyn Write,
+        test: &Test,
+        convention: CallingConvention,
+    ) -> Result<(), GenerateError> {
+        self.c.generate_caller(f, test, convention)
+    }
+
+    fn compile_callee(&self, src_path: &Path, lib_name: &str) -> Result<String, BuildError> {
+        match self.mode {
+            "cxx" => self.compile_cxx(src_path, lib_name),
+            "gxx" => self.compile_with("g++", src_path, lib_name),
+            "clangxx" => self.compile_with("clang++", src_path, lib_name),
+            _ => unimplemented!("unknown c++ compiler"),
+        }
+    }
+
+    fn compile_caller(&self, src_path: &Path, lib_name: &str) -> Result<String, BuildError> {
+        // Currently no need to be different
+        self.compile_callee(src_path, lib_name)
+    }
+}
+
+impl CxxAbiImpl {
+    pub fn new(system_info: &Config, profile: &FlagProfile, mode: &'static str) -> Self {
+        Self {
+            c: CcAbiImpl::new_cxx(system_info, profile, mode),
+            mode,
+        }
+    }
+
+    fn compile_cxx(&self, src_path: &Path, lib_name: &str) -> Result<String, BuildError> {
+        let mut build = cc::Build::new();
+        build
+            .cpp(true)
+            .cpp_link_stdlib(None)
+            .file(src_path)
+            .cargo_metadata(false)
+            .target(built_info::TARGET)
+            // Designated initializers
+            .flag_if_supported("-std=c++20")
+            .flag_if_supported("/std:c++20")
+            // The harness isn't linked with the C++ runtime, so nothing can need it
+            .flag_if_supported("-fno-exceptions")
+            .flag_if_supported("-fno-rtti");
+        self.c.apply_profile(&mut build);
+        build.try_compile(lib_name)?;
+        Ok(String::from(lib_name))
+    }
+
+    fn compile_with(
+        &self,
+        compiler: &str,
+        src_path: &Path,
+        lib_name: &str,
+    ) -> Result<String, BuildError> {
+        let base_path = PathBuf::from("target/temp/");
+        let obj_path = base_path.join(format!("{lib_name}.o"));
+        let lib_path = base_path.join(format!("lib{lib_name}.a"));
+        let mut cmd = Command::new(compiler);
+        cmd.arg("-std=c++20")
+            .arg("-fno-exceptions")
+            .arg("-fno-rtti")
+            .arg("-ffunction-sections")
+            .arg("-fdata-sections")
+            .args(self.c.gnu_profile_flags())
+            .arg("-o")
+            .arg(&obj_path)
+            .arg("-c")
+            .arg(src_path);
+        eprintln!("running: {:?}", cmd);
+        let out = cmd.output()?;
+        if !out.status.success() {
+            return Err(BuildError::CxxCompile(out));
+        }
+        Command::new("ar")
+            .arg("cq")
+            .arg(&lib_path)
+            .arg(&obj_path)
+            .status()
+            .unwrap();
+        Command::new("ar").arg("s").arg(&lib_path).status().unwrap();
+        Ok(String::from(lib_name))
+    }
+}
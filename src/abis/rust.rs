@@ -5,10 +5,59 @@ pub static RUST_TEST_PREFIX: &str = include_str!("../../harness/rust_test_prefix
 
 static STRUCT_128: bool = false; // cfg!(target_arch="x86_64");
 
-#[allow(dead_code)]
+/// The storage for runs of bitfields (see [`RustStructLayout`][]).
+static BITFIELD_NAME: &str = "_bitfield";
+static BITFIELD_ALIGN_NAME: &str = "_bitfield_align";
+
+/// There's no `core::ffi::c_wchar_t`, so pick the type the target uses (same as libc).
+fn rust_wchar_t(target: &str) -> &'static str {
+    let triple = TargetTriple::new(target);
+    if triple.is_windows() {
+        "u16"
+    } else if triple.is_linux_like() && matches!(triple.arch, "aarch64" | "arm") {
+        "u32"
+    } else {
+        "i32"
+    }
+}
+
+/// How a struct's fields are stored in Rust.
+///
+/// This only differs from the C struct when it has bitfields, which Rust doesn't have:
+/// (like bindgen) every run of consecutive bitfields gets stored in a byte array, with
+/// their bits wherever the C compiler would have put them.
+struct RustStructLayout {
+    /// An unsigned int as aligned as the most aligned bitfield's base type, which
+    /// a zero-sized array of at the start of the struct will give it the same alignment.
+    bitfield_align: Option<&'static str>,
+    members: Vec<RustStructMember>,
+    /// The size and alignment of the whole struct in C.
+    size: u64,
+    align: u64,
+}
+
+enum RustStructMember {
+    /// A normal field, with its index.
+    Field(usize),
+    /// A `[u8; len]` holding a run of bitfields (named after its index among
+    /// the runs), with each bitfield's index, bit offset into it, and width.
+    Bitfields {
+        run_idx: usize,
+        len: u64,
+        bitfields: Vec<(usize, u64, u64)>,
+    },
+}
+
+fn align_up(offset: u64, align: u64) -> u64 {
+    offset.div_ceil(align) * align
+}
+
 pub struct RustcAbiImpl {
     is_nightly: bool,
     codegen_backend: Option<String>,
+    poison: bool,
+    check_extension: bool,
+    profile: FlagProfile,
 }
 
 impl AbiImpl for RustcAbiImpl {
@@ -42,15 +91,45 @@ impl AbiImpl for RustcAbiImpl {
             CallingConvention::Stdcall => true,
             CallingConvention::Fastcall => true,
             CallingConvention::Vectorcall => false, // too experimental even for nightly use?
+            CallingConvention::Rust => true,
+        }
+    }
+
+    fn supports_val(&self, val: &Val) -> bool {
+        match val {
+            // Bitfields are laid out by hand, and we only know the SysV-style
+            // rules (not MSVC's) for little-endian 64-bit targets.
+            Val::Bitfield(..) => {
+                let target = TargetTriple::new(self.target());
+                !target.big_endian && target.pointer_size() == 8 && !target.is_windows()
+            }
+            _ => self.rust_arg_type(val).is_ok(),
         }
     }
 
+    fn supports_callee(&self, func: &Func) -> bool {
+        // Defining a variadic function needs c_variadic, and even then
+        // only some primitives can be read out of the VaList.
+        !func.is_variadic()
+            || (self.is_nightly
+                && func
+                    .variadic_inputs
+                    .iter()
+                    .all(|val| self.rust_va_arg(val).is_ok()))
+    }
+
     fn generate_caller(
         &self,
         f: &mut dyn Write,
         test: &Test,
         convention: CallingConvention,
     ) -> Result<(), GenerateError> {
+        if test.has_vectors() && !self.is_nightly {
+            return Err(GenerateError::RustUnsupported(
+                "SIMD vectors can only be declared in extern blocks on nightly (simd_ffi)"
+                    .to_string(),
+            ));
+        }
         self.write_rust_prefix(f, test, convention)?;
         let convention_decl = self.rust_convention_decl(convention);
 
@@ -58,79 +137,76 @@ impl AbiImpl for RustcAbiImpl {
         writeln!(f, "extern \"{convention_decl}\" {{",)?;
         for function in &test.funcs {
             write!(f, "  ")?;
-            self.write_rust_signature(f, function)?;
+            self.write_rust_signature(f, function, "...")?;
             writeln!(f, ";")?;
         }
         writeln!(f, "}}")?;
         writeln!(f)?;
 
-        // Now generate the body
-        writeln!(f, "#[no_mangle] pub extern \"C\" fn do_test() {{")?;
-
+        // Generate the callbacks the callee will call
         for function in &test.funcs {
             if !function.has_convention(convention) {
                 continue;
             }
-            writeln!(f, "   unsafe {{")?;
-
-            // Inputs
-            for (idx, input) in function.inputs.iter().enumerate() {
-                writeln!(
+            for (_arg_idx, callback) in function.callbacks() {
+                write!(
                     f,
-                    "        {} = {};",
-                    self.rust_var_decl(input, ARG_NAMES[idx])?,
-                    self.rust_val(input)?
+                    "{}",
+                    self.rust_target_features_decl(&callback.target_features())
                 )?;
-            }
-            writeln!(f)?;
-            for (idx, input) in function.inputs.iter().enumerate() {
-                writeln!(
+                write!(f, "unsafe extern \"{convention_decl}\" ")?;
+                self.write_rust_signature(f, &callback, "...")?;
+                writeln!(f, " {{")?;
+                self.write_rust_body(
                     f,
-                    "{}",
-                    self.rust_write_val(input, "CALLER_INPUTS", ARG_NAMES[idx], true)?
+                    &callback,
+                    convention,
+                    "CALLER_INPUTS",
+                    "CALLER_OUTPUTS",
+                    "CALLER_POSTCALL",
+                    false,
+                    false,
                 )?;
+                writeln!(f, "}}")?;
+                writeln!(f)?;
             }
-            writeln!(f)?;
-
-            // Outputs
-            write!(f, "        ")?;
-            let pass_out = if let Some(output) = &function.output {
-                if let Some(decl) = self.rust_out_param_var(output, OUTPUT_NAME)? {
-                    writeln!(f, "        {}", decl)?;
-                    true
-                } else {
-                    write!(f, "        {} = ", self.rust_var_decl(output, OUTPUT_NAME)?)?;
-                    false
-                }
-            } else {
-                false
-            };
+        }
 
-            // Do the call
-            write!(f, "{}(", function.name)?;
-            for (idx, input) in function.inputs.iter().enumerate() {
-                write!(f, "{}, ", self.rust_arg_pass(input, ARG_NAMES[idx])?)?;
-            }
-            if pass_out {
-                writeln!(f, "&mut {OUTPUT_NAME}")?;
-            }
-            writeln!(f, ");")?;
-            writeln!(f)?;
+        // Now generate the body
+        let target_features = test.target_features();
+        if target_features.is_empty() {
+            writeln!(f, "#[no_mangle] pub extern \"C\" fn do_test() {{")?;
+        } else {
+            // Calling a function that needs a target feature requires us to have it too
+            write!(f, "{}", self.rust_target_features_decl(&target_features))?;
+            writeln!(f, "#[no_mangle] pub unsafe extern \"C\" fn do_test() {{")?;
+        }
 
-            // Report the output
-            if let Some(output) = &function.output {
-                writeln!(
-                    f,
-                    "{}",
-                    self.rust_write_val(output, "CALLER_OUTPUTS", OUTPUT_NAME, true)?
-                )?;
+        for function in &test.funcs {
+            if !function.has_convention(convention) {
+                continue;
             }
+            writeln!(f, "   unsafe {{")?;
+            self.write_rust_call(
+                f,
+                function,
+                convention,
+                &function.name,
+                "",
+                "CALLER_INPUTS",
+                "CALLER_OUTPUTS",
+                "CALLER_POSTCALL",
+            )?;
 
             // Finished
             writeln!(
                 f,
                 "        FINISHED_FUNC.unwrap()(CALLER_INPUTS, CALLER_OUTPUTS);"
             )?;
+            writeln!(
+                f,
+                "        FINISHED_FUNC.unwrap()(CALLER_POSTCALL, CALLEE_POSTCALL);"
+            )?;
             writeln!(f, "   }}")?;
         }
 
@@ -152,47 +228,35 @@ impl AbiImpl for RustcAbiImpl {
             }
             // Write the signature
             writeln!(f, "#[no_mangle]")?;
+            write!(
+                f,
+                "{}",
+                self.rust_target_features_decl(&function.target_features())
+            )?;
             write!(f, "pub unsafe extern \"{convention_decl}\" ")?;
-            self.write_rust_signature(f, function)?;
+            // If the caller is meant to extend the narrow args, take them as
+            // whole registers to see what it actually left in there
+            let widened = function
+                .poison_widened(self.target())
+                .filter(|_| self.widens_callee());
+            self.write_rust_signature(
+                f,
+                widened.as_ref().unwrap_or(function),
+                &format!("mut {VARARGS_NAME}: ..."),
+            )?;
             writeln!(f, " {{")?;
 
             // Now the body
-
-            // Report Inputs
-            for (idx, input) in function.inputs.iter().enumerate() {
-                writeln!(
-                    f,
-                    "{}",
-                    self.rust_write_val(input, "CALLEE_INPUTS", ARG_NAMES[idx], false)?
-                )?;
-            }
-            writeln!(f)?;
-
-            // Report outputs and return
-            if let Some(output) = &function.output {
-                let decl = self.rust_var_decl(output, OUTPUT_NAME)?;
-                let val = self.rust_val(output)?;
-                writeln!(f, "        {decl} = {val};")?;
-                writeln!(
-                    f,
-                    "{}",
-                    self.rust_write_val(output, "CALLEE_OUTPUTS", OUTPUT_NAME, true)?
-                )?;
-                writeln!(
-                    f,
-                    "        FINISHED_FUNC.unwrap()(CALLEE_INPUTS, CALLEE_OUTPUTS);"
-                )?;
-                writeln!(
-                    f,
-                    "        {}",
-                    self.rust_var_return(output, OUTPUT_NAME, OUT_PARAM_NAME)?
-                )?;
-            } else {
-                writeln!(
-                    f,
-                    "        FINISHED_FUNC.unwrap()(CALLEE_INPUTS, CALLEE_OUTPUTS);"
-                )?;
-            }
+            self.write_rust_body(
+                f,
+                function,
+                convention,
+                "CALLEE_INPUTS",
+                "CALLEE_OUTPUTS",
+                "CALLEE_POSTCALL",
+                true,
+                widened.is_some(),
+            )?;
             writeln!(f, "}}")?;
         }
 
@@ -203,6 +267,9 @@ impl AbiImpl for RustcAbiImpl {
         let mut cmd = Command::new("rustc");
         cmd.arg("--crate-type")
             .arg("staticlib")
+            // The lib is named after the profile too, unlike the source
+            .arg("--crate-name")
+            .arg(lib_name)
             .arg("--out-dir")
             .arg("target/temp/")
             .arg("--target")
@@ -212,6 +279,16 @@ impl AbiImpl for RustcAbiImpl {
         if let Some(codegen_backend) = &self.codegen_backend {
             cmd.arg(format!("-Zcodegen-backend={codegen_backend}"));
         }
+        cmd.arg(format!("-Copt-level={}", self.profile.opt_level));
+        if let Some(target_cpu) = &self.profile.target_cpu {
+            cmd.arg(format!("-Ctarget-cpu={target_cpu}"));
+        }
+        if !self.profile.pic {
+            cmd.arg("-Crelocation-model=static");
+        }
+        if let Some(panic) = &self.profile.panic {
+            cmd.arg(format!("-Cpanic={panic}"));
+        }
         eprintln!("running: {:?}", cmd);
         let out = cmd.output()?;
 
@@ -228,13 +305,41 @@ impl AbiImpl for RustcAbiImpl {
 }
 
 impl RustcAbiImpl {
-    pub fn new(_system_info: &Config, codegen_backend: Option<String>) -> Self {
+    pub fn new(
+        system_info: &Config,
+        profile: &FlagProfile,
+        codegen_backend: Option<String>,
+    ) -> Self {
         Self {
             is_nightly: built_info::RUSTC_VERSION.contains("nightly"),
             codegen_backend,
+            poison: system_info.poison,
+            check_extension: system_info.check_extension,
+            profile: profile.clone(),
         }
     }
 
+    /// Whether narrow fixed inputs get passed with garbage in their high bits.
+    ///
+    /// That means calling through a widened signature, which is only reliable
+    /// unoptimized (see [`Func::poison_widened`][]), so otherwise they're passed normally.
+    fn poisons_high_bits(&self) -> bool {
+        let poisons = if self.check_extension {
+            Extender::for_target(self.target()) == Extender::Callee
+        } else {
+            self.poison
+        };
+        poisons && self.profile.opt_level == "0"
+    }
+
+    /// Whether callees take narrow fixed inputs as pointer-sized ints,
+    /// to check that the caller extended them (again, only unoptimized).
+    fn widens_callee(&self) -> bool {
+        self.check_extension
+            && Extender::for_target(self.target()) != Extender::Callee
+            && self.profile.opt_level == "0"
+    }
+
     fn rust_convention_decl(&self, convention: CallingConvention) -> &'static str {
         match convention {
             CallingConvention::All => {
@@ -252,6 +357,7 @@ impl RustcAbiImpl {
             CallingConvention::Stdcall => "stdcall",
             CallingConvention::Fastcall => "fastcall",
             CallingConvention::Vectorcall => "vectorcall",
+            CallingConvention::Rust => "Rust",
         }
     }
 
@@ -266,14 +372,42 @@ impl RustcAbiImpl {
         if convention == CallingConvention::Vectorcall {
             writeln!(f, "#![feature(abi_vectorcall)]")?;
         }
+        let has_vectors = test.has_vectors();
+        if has_vectors && self.is_nightly {
+            writeln!(f, "#![feature(simd_ffi)]")?;
+        }
+        // Only needed to define variadic functions, and supports_callee checks we're on nightly
+        if test.funcs.iter().any(Func::is_variadic) && self.is_nightly {
+            writeln!(f, "#![feature(c_variadic)]")?;
+        }
+        // If these are used we must be on nightly, or supports_val would have bailed
+        let long_double_is_f128 =
+            LongDoubleFormat::for_target(self.target()) == LongDoubleFormat::Quad;
+        if test.any_val(|val| matches!(val, Val::Float(FloatVal::c__Float16(_)))) {
+            writeln!(f, "#![feature(f16)]")?;
+        }
+        if test.any_val(|val| match val {
+            Val::Float(FloatVal::c__float128(_)) => true,
+            Val::Float(FloatVal::c_long_double(_)) => long_double_is_f128,
+            _ => false,
+        }) {
+            writeln!(f, "#![feature(f128)]")?;
+        }
         // Load test harness "headers"
         write!(f, "{}", RUST_TEST_PREFIX)?;
+        if has_vectors {
+            writeln!(f)?;
+            writeln!(f, "#[cfg(target_arch = \"x86\")]")?;
+            writeln!(f, "use std::arch::x86::*;")?;
+            writeln!(f, "#[cfg(target_arch = \"x86_64\")]")?;
+            writeln!(f, "use std::arch::x86_64::*;")?;
+        }
 
         // Forward-decl struct types
         let mut forward_decls = std::collections::HashMap::<String, String>::new();
         for function in &test.funcs {
-            for val in function.inputs.iter().chain(function.output.as_ref()) {
-                for (name, decl) in self.rust_forward_decl(val)? {
+            for val in function.vals() {
+                for (name, decl) in self.rust_forward_decl(val, convention)? {
                     match forward_decls.entry(name) {
                         std::collections::hash_map::Entry::Occupied(entry) => {
                             if entry.get() != &decl {
@@ -296,30 +430,545 @@ impl RustcAbiImpl {
         Ok(())
     }
 
+    /// The attribute to put on a function to enable the given target features.
+    fn rust_target_features_decl(&self, features: &[&str]) -> String {
+        if features.is_empty() {
+            String::new()
+        } else {
+            format!("#[target_feature(enable = \"{}\")]\n", features.join(","))
+        }
+    }
+
+    /// Emit the body of a function that reports its inputs, calls any callbacks
+    /// it was passed, writes through its mutable refs, and then reports and
+    /// returns its output.
+    #[allow(clippy::too_many_arguments)]
+    fn write_rust_body(
+        &self,
+        f: &mut dyn Write,
+        function: &Func,
+        convention: CallingConvention,
+        inputs: &str,
+        outputs: &str,
+        postcall: &str,
+        finish_func: bool,
+        widened_inputs: bool,
+    ) -> Result<(), GenerateError> {
+        // Unpack the varargs so they look just like the fixed args
+        for (idx, input) in function.variadic_inputs.iter().enumerate() {
+            let arg_name = ARG_NAMES[function.inputs.len() + idx];
+            writeln!(
+                f,
+                "        let {} = {};",
+                self.rust_arg_decl(input, arg_name)?,
+                self.rust_va_arg(input)?
+            )?;
+        }
+
+        // Report Inputs
+        for (idx, input) in function.all_inputs().enumerate() {
+            let write = if idx < function.inputs.len() {
+                self.rust_write_input(input, inputs, ARG_NAMES[idx], false, widened_inputs)?
+            } else {
+                self.rust_write_val(input, inputs, ARG_NAMES[idx], false)?
+            };
+            writeln!(f, "{write}")?;
+        }
+        writeln!(f)?;
+
+        // Call the callbacks
+        for (arg_idx, callback) in function.callbacks() {
+            let arg_name = ARG_NAMES[arg_idx];
+            let var_prefix = format!("{arg_name}_");
+            let callee = if let Val::Nullable(..) = function.inputs[arg_idx] {
+                format!("{arg_name}.unwrap()")
+            } else {
+                arg_name.to_string()
+            };
+            self.write_rust_call(
+                f,
+                &callback,
+                convention,
+                &callee,
+                &var_prefix,
+                inputs,
+                outputs,
+                postcall,
+            )?;
+        }
+        for (arg_idx, after) in function.mut_refs() {
+            // Report it by reading it back through the ref, so a callee that
+            // got a copy will still think it wrote the value
+            let arg_name = ARG_NAMES[arg_idx];
+            writeln!(f, "        *{arg_name} = {};", self.rust_val(after)?)?;
+            let after_ref = Val::Ref(Box::new(after.clone()));
+            writeln!(
+                f,
+                "{}",
+                self.rust_write_val(&after_ref, postcall, arg_name, false)?
+            )?;
+        }
+
+        // Report outputs and return
+        let lowered_outputs = function.lowered_outputs();
+        for (idx, output) in lowered_outputs.iter().enumerate() {
+            let var_name = output_name(idx);
+            let decl = self.rust_var_decl(output, &var_name)?;
+            let val = self.rust_val(output)?;
+            writeln!(f, "        {decl} = {val};")?;
+            writeln!(
+                f,
+                "{}",
+                self.rust_write_val(output, outputs, &var_name, true)?
+            )?;
+        }
+        if finish_func {
+            writeln!(f, "        FINISHED_FUNC.unwrap()({inputs}, {outputs});")?;
+        }
+        // The first output may be a real return, so it has to go last
+        for (idx, output) in lowered_outputs.iter().enumerate().rev() {
+            writeln!(
+                f,
+                "        {}",
+                self.rust_var_return(output, &output_name(idx), &out_param_name(idx))?
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Emit a call to `function` (through `callee`, which may be a function pointer),
+    /// reporting the inputs it's passed, the output it returns, and what its
+    /// mutable refs point to afterwards.
+    ///
+    /// All the local vars will be prefixed with `var_prefix`.
+    #[allow(clippy::too_many_arguments)]
+    fn write_rust_call(
+        &self,
+        f: &mut dyn Write,
+        function: &Func,
+        convention: CallingConvention,
+        callee: &str,
+        var_prefix: &str,
+        inputs: &str,
+        outputs: &str,
+        postcall: &str,
+    ) -> Result<(), GenerateError> {
+        // Inputs
+        for (idx, input) in function.all_inputs().enumerate() {
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[idx]);
+            let val = match input {
+                Val::FnPtr(..) => function.callback_name(idx),
+                Val::Nullable(inner, true) if matches!(**inner, Val::FnPtr(..)) => {
+                    format!("Some({})", function.callback_name(idx))
+                }
+                Val::MutRef(before, _) => self.rust_val(before)?,
+                _ => self.rust_val(input)?,
+            };
+            let is_fixed = idx < function.inputs.len();
+            let val = if self.poison
+                && is_fixed
+                && input.poison(self.target()) == Some(Poison::Padding)
+            {
+                // Poison the whole thing and then write the fields in, so the padding
+                // is garbage (a struct literal would let the compiler zero it)
+                let ty = self.rust_arg_type(input)?;
+                let mut block = String::from("{\n");
+                block.push_str(&format!("            let init: {ty} = {val};\n"));
+                block.push_str(&format!(
+                    "            let mut poisoned = core::mem::MaybeUninit::<{ty}>::uninit();\n"
+                ));
+                block.push_str("            let ptr = poisoned.as_mut_ptr();\n");
+                block.push_str(&format!(
+                    "            core::ptr::write_bytes(ptr as *mut u8, {POISON_BYTE:#x}, core::mem::size_of::<{ty}>());\n"
+                ));
+                for write in self.rust_poison_writes(input, "(*ptr)", "init") {
+                    block.push_str(&format!("            {write}\n"));
+                }
+                block.push_str("            poisoned.assume_init()\n");
+                block.push_str("        }");
+                block
+            } else {
+                val
+            };
+            writeln!(
+                f,
+                "        {} = {};",
+                self.rust_var_decl(input, &var_name)?,
+                val
+            )?;
+        }
+        writeln!(f)?;
+        for (idx, input) in function.all_inputs().enumerate() {
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[idx]);
+            let write = if idx < function.inputs.len() {
+                self.rust_write_input(input, inputs, &var_name, true, false)?
+            } else {
+                self.rust_write_val(input, inputs, &var_name, true)?
+            };
+            writeln!(f, "{write}")?;
+        }
+        writeln!(f)?;
+
+        // Outputs (the out-params have to be declared before the call)
+        let lowered_outputs = function.lowered_outputs();
+        let mut out_params = vec![];
+        for (idx, output) in lowered_outputs.iter().enumerate() {
+            let output_name = format!("{var_prefix}{}", output_name(idx));
+            if let Some(decl) = self.rust_out_param_var(output, &output_name)? {
+                writeln!(f, "        {}", decl)?;
+                out_params.push(output_name);
+            }
+        }
+        write!(f, "        ")?;
+        if let Some(output) = lowered_outputs.first() {
+            if !matches!(output, Val::Ref(_)) {
+                let output_name = format!("{var_prefix}{OUTPUT_NAME}");
+                write!(f, "{} = ", self.rust_var_decl(output, &output_name)?)?;
+            }
+        }
+
+        // Do the call (through a signature with wider args, to poison their high bits)
+        match function
+            .poison_widened(self.target())
+            .filter(|_| self.poisons_high_bits())
+        {
+            Some(widened) => {
+                let convention_decl = self.rust_convention_decl(convention);
+                let mut signature = Vec::new();
+                write!(signature, "unsafe extern \"{convention_decl}\" fn")?;
+                self.write_rust_params(&mut signature, &widened, "...")?;
+                let signature = String::from_utf8(signature).unwrap();
+                write!(
+                    f,
+                    "core::mem::transmute::<*const (), {signature}>({callee} as *const ())("
+                )?;
+            }
+            None => write!(f, "{}(", callee)?,
+        }
+        for (idx, input) in function.inputs.iter().enumerate() {
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[idx]);
+            match input.poison(self.target()) {
+                Some(Poison::HighBits { bits, .. }) if self.poisons_high_bits() => write!(
+                    f,
+                    "(({:#x}u64 as usize) << {bits}) | ({var_name} as u{bits} as usize), ",
+                    poison_word()
+                )?,
+                _ => write!(f, "{}, ", self.rust_arg_pass(input, &var_name)?)?,
+            }
+        }
+        for output_name in &out_params {
+            write!(f, "&mut {output_name}, ")?;
+        }
+        // Unlike C, Rust makes us apply the default argument promotions ourselves
+        for (idx, input) in function.variadic_inputs.iter().enumerate() {
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[function.inputs.len() + idx]);
+            let pass = self.rust_arg_pass(input, &var_name)?;
+            if let Some(promoted) = input.va_promoted(self.target()) {
+                write!(f, "{pass} as {}, ", self.rust_arg_type(&promoted)?)?;
+            } else {
+                write!(f, "{pass}, ")?;
+            }
+        }
+        writeln!(f, ");")?;
+        writeln!(f)?;
+
+        // Report the outputs
+        for (idx, output) in lowered_outputs.iter().enumerate() {
+            let output_name = format!("{var_prefix}{}", output_name(idx));
+            writeln!(
+                f,
+                "{}",
+                self.rust_write_val(output, outputs, &output_name, true)?
+            )?;
+        }
+        for (arg_idx, after) in function.mut_refs() {
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[arg_idx]);
+            let after_ref = Val::Ref(Box::new(after.clone()));
+            writeln!(
+                f,
+                "{}",
+                self.rust_write_val(&after_ref, postcall, &var_name, true)?
+            )?;
+        }
+        Ok(())
+    }
+
     fn write_rust_signature(
         &self,
         f: &mut dyn Write,
         function: &Func,
+        varargs: &str,
     ) -> Result<(), GenerateError> {
-        write!(f, "fn {}(", function.name)?;
+        write!(f, "fn {}", function.name)?;
+        self.write_rust_params(f, function, varargs)
+    }
+
+    /// Emit a function's args and return type (also used for function pointer types).
+    ///
+    /// If the function is variadic, `varargs` is emitted as the last arg
+    /// (`...` in declarations, but definitions need to bind it to a name).
+    fn write_rust_params(
+        &self,
+        f: &mut dyn Write,
+        function: &Func,
+        varargs: &str,
+    ) -> Result<(), GenerateError> {
+        write!(f, "(")?;
         for (idx, input) in function.inputs.iter().enumerate() {
             write!(f, "{}, ", self.rust_arg_decl(input, ARG_NAMES[idx])?)?;
         }
-        if let Some(output) = &function.output {
-            if let Some(out_param) = self.rust_out_param(output, OUT_PARAM_NAME)? {
-                write!(f, "{}", out_param)?;
-                write!(f, ")")?;
+        for output in &function.output {
+            if let Val::MutRef(..) = output {
+                return Err(GenerateError::RustUnsupported(
+                    "mutable refs can only be passed as inputs".to_string(),
+                ));
+            }
+            if let Val::Nullable(inner, _) = output {
+                if let Val::Ref(_) = **inner {
+                    return Err(GenerateError::RustUnsupported(
+                        "nullable refs can only be passed as inputs".to_string(),
+                    ));
+                }
+            }
+        }
+        let mut ret = None;
+        for (idx, output) in function.lowered_outputs().iter().enumerate() {
+            if let Some(out_param) = self.rust_out_param(output, &out_param_name(idx))? {
+                write!(f, "{}, ", out_param)?;
             } else {
-                write!(f, ")")?;
-                let ty = self.rust_arg_type(output)?;
-                write!(f, " -> {ty}")?;
+                ret = Some(self.rust_arg_type(output)?);
             }
-        } else {
-            write!(f, ")")?;
+        }
+        if function.is_variadic() {
+            if function.inputs.is_empty() {
+                return Err(GenerateError::RustUnsupported(
+                    "variadic functions need at least one fixed arg".to_string(),
+                ));
+            }
+            write!(f, "{varargs}")?;
+        }
+        write!(f, ")")?;
+        if let Some(ty) = ret {
+            write!(f, " -> {ty}")?;
         }
         Ok(())
     }
 
+    /// Lay out a struct's fields the way C would, to figure out where its bitfields go.
+    ///
+    /// Consecutive bitfields share a run of bytes, with each one placed at the next bit
+    /// unless that would make it straddle a boundary of its base type, in which case it
+    /// gets bumped up to the next one (the SysV rules).
+    fn rust_struct_layout(&self, val: &Val) -> Result<RustStructLayout, GenerateError> {
+        let (name, fields, packed, min_align) = match val {
+            Val::Struct(name, fields) => (name, fields, None, None),
+            Val::ReprStruct(name, repr, fields) => (name, fields, repr.packed, repr.align),
+            _ => unreachable!("only structs have a struct layout"),
+        };
+        let has_bitfields = fields
+            .iter()
+            .any(|field| matches!(field, Val::Bitfield(..)));
+        if has_bitfields && packed.is_some() {
+            return Err(GenerateError::RustUnsupported(format!(
+                "{name} can't have bitfields if it's packed"
+            )));
+        }
+        if has_bitfields && TargetTriple::new(self.target()).is_windows() {
+            return Err(GenerateError::RustUnsupported(format!(
+                "{name}'s bitfields would need MSVC's layout rules, which aren't implemented"
+            )));
+        }
+
+        let mut members = Vec::<RustStructMember>::new();
+        let mut bit_offset = 0;
+        let mut run_start = 0;
+        let mut num_runs = 0;
+        let mut struct_align = min_align.unwrap_or(1) as u64;
+        let mut bitfield_align = None;
+        for (idx, field) in fields.iter().enumerate() {
+            if let Val::Bitfield(int_val, width) = field {
+                let (size, align) = self.c_layout(&Val::Int(int_val.clone()))?;
+                let width = *width as u64;
+                if width == 0 || width > size * 8 {
+                    return Err(GenerateError::RustUnsupported(format!(
+                        "{name}'s bitfields must be between 1 and their base type's bits wide"
+                    )));
+                }
+                if !matches!(members.last(), Some(RustStructMember::Bitfields { .. })) {
+                    // Runs start right after the previous field (which ends on a byte)
+                    run_start = bit_offset;
+                    members.push(RustStructMember::Bitfields {
+                        run_idx: num_runs,
+                        len: 0,
+                        bitfields: vec![],
+                    });
+                    num_runs += 1;
+                }
+                if bit_offset % (size * 8) + width > size * 8 {
+                    bit_offset = align_up(bit_offset, align * 8);
+                }
+                let Some(RustStructMember::Bitfields { len, bitfields, .. }) = members.last_mut()
+                else {
+                    unreachable!()
+                };
+                bitfields.push((idx, bit_offset - run_start, width));
+                bit_offset += width;
+                *len = align_up(bit_offset - run_start, 8) / 8;
+                if *len > 16 {
+                    return Err(GenerateError::RustUnsupported(format!(
+                        "{name}'s bitfields can't be more than 128 bits in a row"
+                    )));
+                }
+                struct_align = struct_align.max(align);
+                bitfield_align = bitfield_align.max(Some(align));
+            } else {
+                // Only bitfields need to know where the other fields go, so fields
+                // without a C layout (some Rust-only types) are fine without them
+                let (size, align) = match self.c_layout(field) {
+                    Err(_) if !has_bitfields => (0, 1),
+                    layout => layout?,
+                };
+                let align = packed.map_or(align, |packed| align.min(packed as u64));
+                let offset = align_up(align_up(bit_offset, 8) / 8, align);
+                bit_offset = (offset + size) * 8;
+                struct_align = struct_align.max(align);
+                members.push(RustStructMember::Field(idx));
+            }
+        }
+
+        let bitfield_align = bitfield_align.map(|align| match align {
+            1 => "u8",
+            2 => "u16",
+            4 => "u32",
+            8 => "u64",
+            _ => "u128",
+        });
+        let size = align_up(align_up(bit_offset, 8) / 8, struct_align);
+        Ok(RustStructLayout {
+            bitfield_align,
+            members,
+            size,
+            align: struct_align,
+        })
+    }
+
+    /// The size and alignment this value's type has in C (for laying out bitfields).
+    fn c_layout(&self, val: &Val) -> Result<(u64, u64), GenerateError> {
+        use IntVal::*;
+        use Val::*;
+        let target = TargetTriple::new(self.target());
+        let ptr_size = target.pointer_size();
+        let layout = match val {
+            Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
+            Int(int_val) => {
+                let size = match int_val {
+                    c__int128(_) | c__uint128(_) => 16,
+                    c_int64_t(_) | c_uint64_t(_) => 8,
+                    c_int32_t(_) | c_uint32_t(_) => 4,
+                    c_int16_t(_) | c_uint16_t(_) => 2,
+                    c_int8_t(_) | c_uint8_t(_) => 1,
+                    c_char(_) | c_schar(_) | c_uchar(_) => 1,
+                    c_short(_) | c_ushort(_) => 2,
+                    c_wchar_t(_) if target.is_windows() => 2,
+                    c_int(_) | c_uint(_) | c_wchar_t(_) => 4,
+                    c_long(_) | c_ulong(_) => target.long_size(),
+                    c_longlong(_) | c_ulonglong(_) => 8,
+                    c_size_t(_) | c_ptrdiff_t(_) | c_intptr_t(_) | c_uintptr_t(_) => ptr_size,
+                };
+                (size, size)
+            }
+            Float(float_val) => {
+                let size = match float_val {
+                    FloatVal::c_double(_) => 8,
+                    FloatVal::c_float(_) => 4,
+                    FloatVal::c__Float16(_) => 2,
+                    FloatVal::c__float128(_) => 16,
+                    FloatVal::c_long_double(_) => {
+                        match LongDoubleFormat::for_target(self.target()) {
+                            LongDoubleFormat::Double => 8,
+                            LongDoubleFormat::X87 | LongDoubleFormat::Quad => 16,
+                        }
+                    }
+                    // Laid out like an array of the two parts
+                    FloatVal::c__Complex_float(..) => return Ok((8, 4)),
+                    FloatVal::c__Complex_double(..) => return Ok((16, 8)),
+                };
+                (size, size)
+            }
+            Bool(_) => (1, 1),
+            Ptr(_) | Ref(_) | MutRef(..) | FnPtr(..) => (ptr_size, ptr_size),
+            Enum(_, discriminants, _) => self.c_layout(&Int(discriminants[0].clone()))?,
+            Vector(vector_val) => {
+                let size = match vector_val {
+                    VectorVal::c__m128(_) | VectorVal::c__m128d(_) | VectorVal::c__m128i(_) => 16,
+                    VectorVal::c__m256(_) | VectorVal::c__m256d(_) | VectorVal::c__m256i(_) => 32,
+                };
+                (size, size)
+            }
+            Transparent(_, inner) => self.c_layout(inner)?,
+            Nullable(inner, _) => self.c_layout(inner)?,
+            Array(_) | EmptyArray(_) => {
+                let (len, elem) = val.array_elem().unwrap();
+                let (size, align) = self.c_layout(elem)?;
+                (size * len as u64, align)
+            }
+            Struct(_, fields) | ReprStruct(_, _, fields) => {
+                // rust_struct_layout lets these slide when it doesn't need them
+                for field in fields {
+                    if !matches!(field, Bitfield(..)) {
+                        self.c_layout(field)?;
+                    }
+                }
+                let layout = self.rust_struct_layout(val)?;
+                (layout.size, layout.align)
+            }
+            Union(_, variants, _) => {
+                let (mut size, mut align) = (0, 1);
+                for variant in variants {
+                    let (variant_size, variant_align) = self.c_layout(variant)?;
+                    size = size.max(variant_size);
+                    align = align.max(variant_align);
+                }
+                (align_up(size, align), align)
+            }
+            TaggedUnion(name, variants, _) => {
+                // The tag followed by a union of a struct for each variant
+                let (tag_size, tag_align) = self.c_layout(&Int(variants[0].0.clone()))?;
+                let (mut payload_size, mut payload_align) = (0, 1);
+                for (_discriminant, fields) in variants {
+                    let variant = Struct(name.clone(), fields.clone());
+                    let (variant_size, variant_align) = self.c_layout(&variant)?;
+                    payload_size = payload_size.max(variant_size);
+                    payload_align = payload_align.max(variant_align);
+                }
+                let align = tag_align.max(payload_align);
+                let size = align_up(tag_size, payload_align) + payload_size;
+                (align_up(size, align), align)
+            }
+            // Not guaranteed, but these are what everyone does
+            Char(_) => (4, 4),
+            Unit => (0, 1),
+            Str(_) | Slice(_) => (ptr_size * 2, ptr_size),
+            Boxed(_) => (ptr_size, ptr_size),
+            Tuple(_) | RustOption(..) => {
+                return Err(GenerateError::RustUnsupported(
+                    "tuples and Options don't have a layout to put bitfields around".to_string(),
+                ));
+            }
+            CxxRef(_) | Class(..) | Base(_) | NoUniqueAddress(_) => {
+                return Err(GenerateError::RustUnsupported(
+                    "C++-only types don't exist in Rust".to_string(),
+                ));
+            }
+            Bitfield(..) => {
+                return Err(GenerateError::RustUnsupported(
+                    "bitfields can only be struct fields".to_string(),
+                ));
+            }
+        };
+        Ok(layout)
+    }
+
     /// If this value defines a nominal type, this will spit out:
     ///
     /// * The type name
@@ -328,23 +977,101 @@ impl RustcAbiImpl {
     /// To catch buggy test definitions, you should validate that all
     /// structs that claim a particular name have the same declaration.
     /// This is done in write_rust_prefix.
-    fn rust_forward_decl(&self, val: &Val) -> Result<Vec<(String, String)>, GenerateError> {
+    fn rust_forward_decl(
+        &self,
+        val: &Val,
+        convention: CallingConvention,
+    ) -> Result<Vec<(String, String)>, GenerateError> {
         use Val::*;
         match val {
-            Struct(name, fields) => {
+            Struct(name, fields) | ReprStruct(name, _, fields) => {
                 let mut results = vec![];
                 for field in fields.iter() {
-                    results.extend(self.rust_forward_decl(field)?);
+                    results.extend(self.rust_forward_decl(field, convention)?);
                 }
+                let mut repr = String::from("C");
+                if let ReprStruct(_, struct_repr, _) = val {
+                    match (struct_repr.packed, struct_repr.align) {
+                        (Some(_), Some(_)) => {
+                            return Err(GenerateError::RustUnsupported(format!(
+                                "{name} can't be both packed and over-aligned"
+                            )))
+                        }
+                        (Some(packed), None) => repr.push_str(&format!(", packed({packed})")),
+                        (None, Some(align)) => repr.push_str(&format!(", align({align})")),
+                        (None, None) => {}
+                    }
+                }
+                let layout = self.rust_struct_layout(val)?;
                 let mut output = String::new();
                 let ref_name = name.to_string();
-                output.push_str("\n#[repr(C)]\n");
+                output.push_str(&format!("\n#[repr({repr})]\n"));
+                output.push_str("#[derive(Copy, Clone)]\n");
                 output.push_str(&format!("pub struct {name} {{\n"));
-                for (idx, field) in fields.iter().enumerate() {
+                if let Some(align_ty) = layout.bitfield_align {
+                    output.push_str(&format!("    {BITFIELD_ALIGN_NAME}: [{align_ty}; 0],\n"));
+                }
+                for member in &layout.members {
+                    let line = match member {
+                        RustStructMember::Field(idx) => format!(
+                            "    {}: {},\n",
+                            FIELD_NAMES[*idx],
+                            self.rust_nested_type(&fields[*idx])?
+                        ),
+                        RustStructMember::Bitfields { run_idx, len, .. } => {
+                            format!("    {BITFIELD_NAME}{run_idx}: [u8; {len}],\n")
+                        }
+                    };
+                    output.push_str(&line);
+                }
+                output.push('}');
+                results.push((ref_name, output));
+                Ok(results)
+            }
+            Float(FloatVal::c__Complex_float(..) | FloatVal::c__Complex_double(..)) => {
+                // Rust doesn't have complex numbers, so use the usual emulation
+                let name = self.rust_arg_type(val)?;
+                let part = if let Float(FloatVal::c__Complex_float(..)) = val {
+                    "f32"
+                } else {
+                    "f64"
+                };
+                let mut output = String::new();
+                output.push_str("\n#[repr(C)]\n");
+                output.push_str("#[derive(Copy, Clone)]\n");
+                output.push_str(&format!(
+                    "pub struct {name} {{\n    re: {part},\n    im: {part},\n}}"
+                ));
+                Ok(vec![(name, output)])
+            }
+            Transparent(name, inner) => {
+                let mut results = self.rust_forward_decl(inner, convention)?;
+                let mut output = String::new();
+                output.push_str("\n#[repr(transparent)]\n");
+                output.push_str("#[derive(Copy, Clone)]\n");
+                output.push_str(&format!(
+                    "pub struct {name}(pub {});",
+                    self.rust_nested_type(inner)?
+                ));
+                results.push((name.clone(), output));
+                Ok(results)
+            }
+            Union(name, variants, _active) => {
+                let mut results = vec![];
+                for variant in variants.iter() {
+                    results.extend(self.rust_forward_decl(variant, convention)?);
+                }
+                let mut output = String::new();
+                let ref_name = name.to_string();
+                // Union fields must be Copy, which is why structs derive it too
+                output.push_str("\n#[repr(C)]\n");
+                output.push_str("#[derive(Copy, Clone)]\n");
+                output.push_str(&format!("pub union {name} {{\n"));
+                for (idx, variant) in variants.iter().enumerate() {
                     let line = format!(
                         "    {}: {},\n",
                         FIELD_NAMES[idx],
-                        self.rust_nested_type(field)?
+                        self.rust_nested_type(variant)?
                     );
                     output.push_str(&line);
                 }
@@ -352,18 +1079,121 @@ impl RustcAbiImpl {
                 results.push((ref_name, output));
                 Ok(results)
             }
-            Array(vals) => self.rust_forward_decl(&vals[0]),
-            Ref(pointee) => self.rust_forward_decl(pointee),
+            Enum(name, discriminants, _active) => {
+                let mut output = String::new();
+                let ref_name = name.to_string();
+                let repr = self.rust_arg_type(&Int(discriminants[0].clone()))?;
+                output.push_str(&format!("\n#[repr({repr})]\n"));
+                output.push_str("#[derive(Copy, Clone)]\n");
+                output.push_str(&format!("pub enum {name} {{\n"));
+                for (idx, discriminant) in discriminants.iter().enumerate() {
+                    let line = format!(
+                        "    {} = {},\n",
+                        VARIANT_NAMES[idx],
+                        self.rust_val(&Int(discriminant.clone()))?
+                    );
+                    output.push_str(&line);
+                }
+                output.push('}');
+                Ok(vec![(ref_name, output)])
+            }
+            TaggedUnion(name, variants, _active) => {
+                let mut results = vec![];
+                for (_discriminant, fields) in variants.iter() {
+                    for field in fields.iter() {
+                        results.extend(self.rust_forward_decl(field, convention)?);
+                    }
+                }
+                let mut output = String::new();
+                let ref_name = name.to_string();
+                let repr = self.rust_arg_type(&Int(variants[0].0.clone()))?;
+                output.push_str(&format!("\n#[repr(C, {repr})]\n"));
+                output.push_str("#[derive(Copy, Clone)]\n");
+                output.push_str(&format!("pub enum {name} {{\n"));
+                for (idx, (discriminant, fields)) in variants.iter().enumerate() {
+                    output.push_str(&format!("    {}", VARIANT_NAMES[idx]));
+                    if !fields.is_empty() {
+                        output.push_str(" {\n");
+                        for (field_idx, field) in fields.iter().enumerate() {
+                            let line = format!(
+                                "        {}: {},\n",
+                                FIELD_NAMES[field_idx],
+                                self.rust_nested_type(field)?
+                            );
+                            output.push_str(&line);
+                        }
+                        output.push_str("    }");
+                    }
+                    let line = format!(" = {},\n", self.rust_val(&Int(discriminant.clone()))?);
+                    output.push_str(&line);
+                }
+                output.push('}');
+                results.push((ref_name, output));
+                Ok(results)
+            }
+            FnPtr(name, inputs, output) => {
+                let mut results = vec![];
+                for val in inputs.iter().chain(output.as_deref()) {
+                    results.extend(self.rust_forward_decl(val, convention)?);
+                }
+                let signature = Func {
+                    name: name.clone(),
+                    conventions: vec![convention],
+                    inputs: inputs.clone(),
+                    variadic_inputs: vec![],
+                    output: output.as_deref().cloned().into_iter().collect(),
+                };
+                let convention_decl = self.rust_convention_decl(convention);
+                let mut output = Vec::new();
+                write!(
+                    output,
+                    "\npub type {name} = unsafe extern \"{convention_decl}\" fn"
+                )?;
+                self.write_rust_params(&mut output, &signature, "...")?;
+                write!(output, ";")?;
+                results.push((name.clone(), String::from_utf8(output).unwrap()));
+                Ok(results)
+            }
+            Array(vals) => self.rust_forward_decl(&vals[0], convention),
+            EmptyArray(elem) => self.rust_forward_decl(elem, convention),
+            Ref(pointee)
+            | Nullable(pointee, _)
+            | Slice(pointee)
+            | Boxed(pointee)
+            | RustOption(pointee, _) => self.rust_forward_decl(pointee, convention),
+            Tuple(fields) => {
+                let mut results = vec![];
+                for field in fields {
+                    results.extend(self.rust_forward_decl(field, convention)?);
+                }
+                Ok(results)
+            }
+            MutRef(before, after) => {
+                let mut results = self.rust_forward_decl(before, convention)?;
+                results.extend(self.rust_forward_decl(after, convention)?);
+                Ok(results)
+            }
             _ => Ok(vec![]),
         }
     }
 
     /// The decl to use for a local var (reference-ness stripped)
     fn rust_var_decl(&self, val: &Val, var_name: &str) -> Result<String, GenerateError> {
-        if let Val::Ref(pointee) = val {
-            Ok(self.rust_var_decl(pointee, var_name)?)
-        } else {
-            Ok(format!("let {var_name}: {}", self.rust_arg_type(val)?))
+        match val {
+            Val::Ref(pointee) => Ok(format!("let {var_name}: {}", self.rust_arg_type(pointee)?)),
+            Val::MutRef(pointee, _) => {
+                // Check that the after value is valid
+                self.rust_arg_type(val)?;
+                Ok(format!(
+                    "let mut {var_name}: {}",
+                    self.rust_arg_type(pointee)?
+                ))
+            }
+            // The var is the pointee, which may not be passed
+            Val::Nullable(inner, _) if matches!(**inner, Val::Ref(_)) => {
+                self.rust_var_decl(inner, var_name)
+            }
+            _ => Ok(format!("let {var_name}: {}", self.rust_arg_type(val)?)),
         }
     }
 
@@ -371,6 +1201,19 @@ impl RustcAbiImpl {
     fn rust_arg_decl(&self, val: &Val, arg_name: &str) -> Result<String, GenerateError> {
         if let Val::Ref(pointee) = val {
             Ok(format!("{arg_name}: &{}", self.rust_arg_type(pointee)?))
+        } else if let Val::MutRef(pointee, _) = val {
+            self.rust_arg_type(val)?;
+            Ok(format!("{arg_name}: &mut {}", self.rust_arg_type(pointee)?))
+        } else if let Val::Nullable(inner, _) = val {
+            if let Val::Ref(pointee) = &**inner {
+                // Check that the inner value is valid
+                self.rust_arg_type(val)?;
+                return Ok(format!(
+                    "{arg_name}: Option<&{}>",
+                    self.rust_arg_type(pointee)?
+                ));
+            }
+            Ok(format!("{arg_name}: {}", self.rust_arg_type(val)?))
         } else {
             Ok(format!("{arg_name}: {}", self.rust_arg_type(val)?))
         }
@@ -409,12 +1252,59 @@ impl RustcAbiImpl {
         }
     }
 
+    /// How to read this value out of a VaList in a variadic function.
+    ///
+    /// Only primitives that can't be promoted implement `VaArgSafe`, so anything that
+    /// gets promoted is read as its promoted type and cast back, and everything else
+    /// (structs, unions, enums, ...) just can't be read at all.
+    fn rust_va_arg(&self, val: &Val) -> Result<String, GenerateError> {
+        let val = match val {
+            Val::Bool(_) => format!("{VARARGS_NAME}.next_arg::<core::ffi::c_int>() != 0"),
+            Val::Ptr(_) => format!("{VARARGS_NAME}.next_arg::<*mut ()>()"),
+            Val::Ref(pointee) => {
+                let ty = self.rust_arg_type(pointee)?;
+                format!("&*{VARARGS_NAME}.next_arg::<*const {ty}>()")
+            }
+            Val::MutRef(pointee, _) => {
+                let ty = self.rust_arg_type(pointee)?;
+                format!("&mut *{VARARGS_NAME}.next_arg::<*mut {ty}>()")
+            }
+            Val::Int(IntVal::c__int128(_) | IntVal::c__uint128(_)) => {
+                return Err(GenerateError::RustUnsupported(
+                    "128-bit ints can't be read out of a VaList".to_string(),
+                ))
+            }
+            Val::Int(_) | Val::Float(FloatVal::c_float(_) | FloatVal::c_double(_)) => {
+                let ty = self.rust_arg_type(val)?;
+                if let Some(promoted) = val.va_promoted(self.target()) {
+                    let promoted_ty = self.rust_arg_type(&promoted)?;
+                    format!("{VARARGS_NAME}.next_arg::<{promoted_ty}>() as {ty}")
+                } else {
+                    format!("{VARARGS_NAME}.next_arg::<{ty}>()")
+                }
+            }
+            _ => {
+                return Err(GenerateError::RustUnsupported(
+                    "only primitives can be read out of a VaList".to_string(),
+                ))
+            }
+        };
+        Ok(val)
+    }
+
     /// How to pass an argument
     fn rust_arg_pass(&self, val: &Val, arg_name: &str) -> Result<String, GenerateError> {
-        if let Val::Ref(_) = val {
-            Ok(format!("&{arg_name}"))
-        } else {
-            Ok(arg_name.to_string())
+        match val {
+            Val::Ref(_) => Ok(format!("&{arg_name}")),
+            Val::MutRef(..) => Ok(format!("&mut {arg_name}")),
+            Val::Nullable(inner, is_some) if matches!(**inner, Val::Ref(_)) => {
+                if *is_some {
+                    Ok(format!("Some(&{arg_name})"))
+                } else {
+                    Ok("None".to_string())
+                }
+            }
+            _ => Ok(arg_name.to_string()),
         }
     }
 
@@ -437,13 +1327,108 @@ impl RustcAbiImpl {
         use IntVal::*;
         use Val::*;
         let out = match val {
+            Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
             Ref(pointee) => format!("*mut {}", self.rust_arg_type(pointee)?),
+            MutRef(before, after) => {
+                let ty = self.rust_arg_type(before)?;
+                if ty != self.rust_arg_type(after)? {
+                    return Err(GenerateError::RustUnsupported(
+                        "a mutable ref's value after the call must have the same type".to_string(),
+                    ));
+                }
+                format!("*mut {ty}")
+            }
             Ptr(_) => "*mut ()".to_string(),
             Bool(_) => "bool".to_string(),
             Array(vals) => format!("[{}; {}]", self.rust_arg_type(&vals[0])?, vals.len()),
-            Struct(name, _) => name.to_string(),
+            EmptyArray(elem) => format!("[{}; 0]", self.rust_arg_type(elem)?),
+            Transparent(name, inner) => {
+                if let Ref(_) = **inner {
+                    return Err(GenerateError::RustUnsupported(
+                        "transparent wrappers around refs aren't supported".to_string(),
+                    ));
+                }
+                name.to_string()
+            }
+            Struct(name, _) | ReprStruct(name, _, _) => name.to_string(),
+            Union(name, _, _) => name.to_string(),
+            Enum(name, _, _) => name.to_string(),
+            TaggedUnion(name, _, _) => name.to_string(),
+            FnPtr(name, _, _) => name.to_string(),
+            Nullable(inner, is_some) => {
+                inner
+                    .check_nullable(*is_some)
+                    .map_err(GenerateError::RustUnsupported)?;
+                match &**inner {
+                    Ref(pointee) => format!("Option<&'static {}>", self.rust_arg_type(pointee)?),
+                    Ptr(_) => "Option<core::ptr::NonNull<()>>".to_string(),
+                    Int(_) => format!("Option<core::num::NonZero<{}>>", self.rust_arg_type(inner)?),
+                    _ => format!("Option<{}>", self.rust_arg_type(inner)?),
+                }
+            }
+            Char(_) => "char".to_string(),
+            Unit => "()".to_string(),
+            Tuple(fields) => {
+                let mut output = String::from("(");
+                for field in fields {
+                    output.push_str(&format!("{}, ", self.rust_arg_type(field)?));
+                }
+                output.push(')');
+                output
+            }
+            Slice(elems) => {
+                let Some((_len, elem)) = elems.array_elem() else {
+                    return Err(GenerateError::RustUnsupported(
+                        "slices must be given an array of their elements".to_string(),
+                    ));
+                };
+                format!("&'static [{}]", self.rust_arg_type(elem)?)
+            }
+            Str(_) => "&'static str".to_string(),
+            Boxed(pointee) => format!("Box<{}>", self.rust_arg_type(pointee)?),
+            RustOption(inner, _) => {
+                if let Ref(_) | MutRef(..) | FnPtr(..) = **inner {
+                    return Err(GenerateError::RustUnsupported(
+                        "Options of refs and function pointers should be Nullable".to_string(),
+                    ));
+                }
+                format!("Option<{}>", self.rust_arg_type(inner)?)
+            }
+            CxxRef(_) | Class(..) | Base(_) | NoUniqueAddress(_) => {
+                return Err(GenerateError::RustUnsupported(
+                    "C++-only types don't exist in Rust".to_string(),
+                ));
+            }
+            Bitfield(..) => {
+                return Err(GenerateError::RustUnsupported(
+                    "bitfields can only be struct fields".to_string(),
+                ));
+            }
+            Vector(vector_val) => match vector_val {
+                VectorVal::c__m128(_) => "__m128".to_string(),
+                VectorVal::c__m128d(_) => "__m128d".to_string(),
+                VectorVal::c__m128i(_) => "__m128i".to_string(),
+                VectorVal::c__m256(_) => "__m256".to_string(),
+                VectorVal::c__m256d(_) => "__m256d".to_string(),
+                VectorVal::c__m256i(_) => "__m256i".to_string(),
+            },
             Float(FloatVal::c_double(_)) => "f64".to_string(),
             Float(FloatVal::c_float(_)) => "f32".to_string(),
+            Float(FloatVal::c__Complex_float(..)) => "ComplexF32".to_string(),
+            Float(FloatVal::c__Complex_double(..)) => "ComplexF64".to_string(),
+            Float(FloatVal::c__Float16(_)) => self.rust_nightly_float("f16")?,
+            Float(FloatVal::c__float128(_)) => self.rust_nightly_float("f128")?,
+            Float(FloatVal::c_long_double(_)) => {
+                match LongDoubleFormat::for_target(self.target()) {
+                    LongDoubleFormat::Double => "f64".to_string(),
+                    LongDoubleFormat::Quad => self.rust_nightly_float("f128")?,
+                    LongDoubleFormat::X87 => {
+                        return Err(GenerateError::RustUnsupported(
+                            "Rust has no type for x87's long double".to_string(),
+                        ))
+                    }
+                }
+            }
             Int(int_val) => match int_val {
                 c__int128(_) => {
                     if STRUCT_128 {
@@ -467,17 +1452,52 @@ impl RustcAbiImpl {
                 c_uint32_t(_) => "u32".to_string(),
                 c_uint16_t(_) => "u16".to_string(),
                 c_uint8_t(_) => "u8".to_string(),
+                c_char(_) => "core::ffi::c_char".to_string(),
+                c_schar(_) => "core::ffi::c_schar".to_string(),
+                c_uchar(_) => "core::ffi::c_uchar".to_string(),
+                c_short(_) => "core::ffi::c_short".to_string(),
+                c_ushort(_) => "core::ffi::c_ushort".to_string(),
+                c_int(_) => "core::ffi::c_int".to_string(),
+                c_uint(_) => "core::ffi::c_uint".to_string(),
+                c_long(_) => "core::ffi::c_long".to_string(),
+                c_ulong(_) => "core::ffi::c_ulong".to_string(),
+                c_longlong(_) => "core::ffi::c_longlong".to_string(),
+                c_ulonglong(_) => "core::ffi::c_ulonglong".to_string(),
+                c_size_t(_) => "usize".to_string(),
+                c_ptrdiff_t(_) => "isize".to_string(),
+                c_intptr_t(_) => "isize".to_string(),
+                c_uintptr_t(_) => "usize".to_string(),
+                c_wchar_t(_) => rust_wchar_t(self.target()).to_string(),
             },
         };
         Ok(out)
     }
 
+    /// The name of a float type that's still unstable.
+    fn rust_nightly_float(&self, name: &str) -> Result<String, GenerateError> {
+        if self.is_nightly {
+            Ok(name.to_string())
+        } else {
+            Err(GenerateError::RustUnsupported(format!(
+                "{name} is only available on nightly"
+            )))
+        }
+    }
+
     /// The type name to use for this value when it is stored in composite.
     ///
     /// This is separated out in case there's a type that needs different
     /// handling in this context to conform to a layout (i.e. how C arrays
     /// decay into pointers when used in function args).
     fn rust_nested_type(&self, val: &Val) -> Result<String, GenerateError> {
+        // Composites all derive Copy (unions need it)
+        let mut has_box = false;
+        val.visit(&mut |val| has_box |= matches!(val, Val::Boxed(_)));
+        if has_box {
+            return Err(GenerateError::RustUnsupported(
+                "Box isn't Copy, so it can't be in a struct, union, or enum".to_string(),
+            ));
+        }
         self.rust_arg_type(val)
     }
 
@@ -486,29 +1506,178 @@ impl RustcAbiImpl {
         use IntVal::*;
         use Val::*;
         let out = match val {
-            Ref(pointee) => self.rust_val(pointee)?,
+            Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
+            // The var is the pointee
+            Ref(pointee) => self.rust_nested_val(pointee)?,
             Ptr(addr) => format!("{addr:#X} as *mut ()"),
             Bool(val) => format!("{val}"),
             Array(vals) => {
                 let mut output = String::new();
                 output.push('[');
                 for elem in vals {
-                    let part = format!("{}, ", self.rust_val(elem)?);
+                    let part = format!("{}, ", self.rust_nested_val(elem)?);
                     output.push_str(&part);
                 }
                 output.push(']');
                 output
             }
-            Struct(name, fields) => {
+            EmptyArray(_) => "[]".to_string(),
+            Transparent(name, inner) => format!("{name}({})", self.rust_val(inner)?),
+            Nullable(inner, is_some) => match &**inner {
+                // Only the pointee is stored in a var
+                Ref(pointee) => self.rust_nested_val(pointee)?,
+                _ if !is_some => "None".to_string(),
+                Ptr(_) => format!("core::ptr::NonNull::new({})", self.rust_val(inner)?),
+                Int(_) => format!("core::num::NonZero::new({})", self.rust_val(inner)?),
+                _ => self.rust_val(inner)?,
+            },
+            Struct(name, fields) | ReprStruct(name, _, fields) => {
+                let layout = self.rust_struct_layout(val)?;
                 let mut output = String::new();
                 output.push_str(&format!("{name} {{ "));
-                for (idx, field) in fields.iter().enumerate() {
-                    let part = format!("{}: {},", FIELD_NAMES[idx], self.rust_val(field)?);
+                if layout.bitfield_align.is_some() {
+                    output.push_str(&format!("{BITFIELD_ALIGN_NAME}: [],"));
+                }
+                for member in &layout.members {
+                    let part = match member {
+                        RustStructMember::Field(idx) => {
+                            format!(
+                                "{}: {},",
+                                FIELD_NAMES[*idx],
+                                self.rust_nested_val(&fields[*idx])?
+                            )
+                        }
+                        RustStructMember::Bitfields {
+                            run_idx,
+                            len,
+                            bitfields,
+                        } => {
+                            // Pack the values into their bits ourselves
+                            let mut bits = 0u128;
+                            for &(idx, offset, width) in bitfields {
+                                let Bitfield(int_val, _) = &fields[idx] else {
+                                    unreachable!()
+                                };
+                                let mask = u128::MAX >> (128 - width);
+                                bits |= (int_val.bits() & mask) << offset;
+                            }
+                            let bytes = bits.to_le_bytes()[..*len as usize]
+                                .iter()
+                                .map(|byte| format!("{byte:#04x}"))
+                                .collect::<Vec<_>>();
+                            format!("{BITFIELD_NAME}{run_idx}: [{}],", bytes.join(", "))
+                        }
+                    };
                     output.push_str(&part);
                 }
                 output.push_str(" }");
                 output
             }
+            Union(name, variants, active) => {
+                format!(
+                    "{name} {{ {}: {} }}",
+                    FIELD_NAMES[*active],
+                    self.rust_nested_val(&variants[*active])?
+                )
+            }
+            Enum(name, _discriminants, active) => {
+                format!("{name}::{}", VARIANT_NAMES[*active])
+            }
+            TaggedUnion(name, variants, active) => {
+                let (_discriminant, fields) = &variants[*active];
+                let mut output = String::new();
+                output.push_str(&format!("{name}::{}", VARIANT_NAMES[*active]));
+                if !fields.is_empty() {
+                    output.push_str(" { ");
+                    for (idx, field) in fields.iter().enumerate() {
+                        let part =
+                            format!("{}: {},", FIELD_NAMES[idx], self.rust_nested_val(field)?);
+                        output.push_str(&part);
+                    }
+                    output.push_str(" }");
+                }
+                output
+            }
+            FnPtr(..) => {
+                // The caller passes its generated callback, see Func::callbacks
+                return Err(GenerateError::RustUnsupported(
+                    "function pointers can only be passed directly as arguments".to_string(),
+                ));
+            }
+            MutRef(..) => {
+                // The caller stores the value before the call, see write_rust_call
+                return Err(GenerateError::RustUnsupported(
+                    "mutable refs can only be passed directly as arguments".to_string(),
+                ));
+            }
+            Char(val) => format!("'\\u{{{:x}}}'", *val as u32),
+            Unit => "()".to_string(),
+            Tuple(fields) => {
+                let mut output = String::from("(");
+                for field in fields {
+                    output.push_str(&format!("{}, ", self.rust_val(field)?));
+                }
+                output.push(')');
+                output
+            }
+            Slice(elems) => {
+                // Leak the elements so the slice can be 'static
+                format!(
+                    "(Box::leak(Box::<{}>::new({})) as &[{}])",
+                    self.rust_arg_type(elems)?,
+                    self.rust_val(elems)?,
+                    self.rust_arg_type(elems.array_elem().unwrap().1)?
+                )
+            }
+            Str(val) => format!("{val:?}"),
+            Boxed(pointee) => format!("Box::new({})", self.rust_val(pointee)?),
+            RustOption(inner, true) => format!("Some({})", self.rust_val(inner)?),
+            RustOption(_, false) => "None".to_string(),
+            CxxRef(_) | Class(..) | Base(_) | NoUniqueAddress(_) => {
+                return Err(GenerateError::RustUnsupported(
+                    "C++-only types don't exist in Rust".to_string(),
+                ));
+            }
+            Bitfield(..) => {
+                return Err(GenerateError::RustUnsupported(
+                    "bitfields can only be struct fields".to_string(),
+                ));
+            }
+            Vector(vector_val) => {
+                // There's no nice way to construct these from lanes, so just transmute.
+                let (lane_ty, lanes) = match vector_val {
+                    VectorVal::c__m128(lanes) => (
+                        "f32",
+                        self.rust_lanes(lanes, |&lane| Float(FloatVal::c_float(lane)))?,
+                    ),
+                    VectorVal::c__m128d(lanes) => (
+                        "f64",
+                        self.rust_lanes(lanes, |&lane| Float(FloatVal::c_double(lane)))?,
+                    ),
+                    VectorVal::c__m128i(lanes) => (
+                        "u32",
+                        self.rust_lanes(lanes, |&lane| Int(c_uint32_t(lane)))?,
+                    ),
+                    VectorVal::c__m256(lanes) => (
+                        "f32",
+                        self.rust_lanes(lanes, |&lane| Float(FloatVal::c_float(lane)))?,
+                    ),
+                    VectorVal::c__m256d(lanes) => (
+                        "f64",
+                        self.rust_lanes(lanes, |&lane| Float(FloatVal::c_double(lane)))?,
+                    ),
+                    VectorVal::c__m256i(lanes) => (
+                        "u32",
+                        self.rust_lanes(lanes, |&lane| Int(c_uint32_t(lane)))?,
+                    ),
+                };
+                format!(
+                    "core::mem::transmute::<[{lane_ty}; {}], {}>([{}])",
+                    lanes.len(),
+                    self.rust_arg_type(val)?,
+                    lanes.join(", ")
+                )
+            }
             Float(FloatVal::c_double(val)) => {
                 if val.fract() == 0.0 {
                     format!("{val}.0")
@@ -523,6 +1692,25 @@ impl RustcAbiImpl {
                     format!("{val}")
                 }
             }
+            Float(FloatVal::c__Complex_float(re, im)) => format!(
+                "ComplexF32 {{ re: {}, im: {} }}",
+                self.rust_val(&Float(FloatVal::c_float(*re)))?,
+                self.rust_val(&Float(FloatVal::c_float(*im)))?
+            ),
+            Float(FloatVal::c__Complex_double(re, im)) => format!(
+                "ComplexF64 {{ re: {}, im: {} }}",
+                self.rust_val(&Float(FloatVal::c_double(*re)))?,
+                self.rust_val(&Float(FloatVal::c_double(*im)))?
+            ),
+            Float(FloatVal::c__Float16(bits)) => format!("f16::from_bits({bits:#X})"),
+            Float(FloatVal::c__float128(bits)) => format!("f128::from_bits({bits:#X})"),
+            Float(FloatVal::c_long_double(bits)) => {
+                // Only the low bytes are part of the value, see FloatVal::bytes
+                match LongDoubleFormat::for_target(self.target()) {
+                    LongDoubleFormat::Quad => format!("f128::from_bits({bits:#X})"),
+                    _ => format!("f64::from_bits({:#X})", *bits as u64),
+                }
+            }
             Int(int_val) => match int_val {
                 c__int128(val) => {
                     if STRUCT_128 {
@@ -546,16 +1734,62 @@ impl RustcAbiImpl {
                 c_uint32_t(val) => format!("{val:#X}"),
                 c_uint16_t(val) => format!("{val:#X}"),
                 c_uint8_t(val) => format!("{val:#X}"),
+                // Let `as` truncate the value to whatever width the platform uses
+                c_char(wide) | c_schar(wide) | c_short(wide) | c_int(wide) | c_long(wide)
+                | c_longlong(wide) | c_ptrdiff_t(wide) | c_intptr_t(wide) | c_wchar_t(wide) => {
+                    format!("{wide}i64 as {}", self.rust_arg_type(val)?)
+                }
+                c_uchar(wide) | c_ushort(wide) | c_uint(wide) | c_ulong(wide)
+                | c_ulonglong(wide) | c_size_t(wide) | c_uintptr_t(wide) => {
+                    format!("{wide:#X}u64 as {}", self.rust_arg_type(val)?)
+                }
             },
         };
         Ok(out)
     }
 
+    /// An expression that generates this value when it's nested inside another.
+    ///
+    /// Unlike a var, a nested ref has to point at something that outlives the
+    /// callee (it might be returning it), so it gets a leaked copy of its pointee.
+    fn rust_nested_val(&self, val: &Val) -> Result<String, GenerateError> {
+        match val {
+            Val::Ref(pointee) => {
+                let ty = self.rust_arg_type(pointee)?;
+                let init = self.rust_nested_val(pointee)?;
+                Ok(format!("Box::leak(Box::<{ty}>::new({init})) as *mut {ty}"))
+            }
+            Val::Nullable(inner, true) => match &**inner {
+                Val::Ref(pointee) => {
+                    let ty = self.rust_arg_type(pointee)?;
+                    let init = self.rust_nested_val(pointee)?;
+                    Ok(format!("Some(&*Box::leak(Box::<{ty}>::new({init})))"))
+                }
+                _ => self.rust_val(val),
+            },
+            Val::Nullable(inner, false) if matches!(**inner, Val::Ref(_)) => Ok("None".to_string()),
+            _ => self.rust_val(val),
+        }
+    }
+
+    fn rust_lanes<T>(
+        &self,
+        lanes: &[T],
+        to_val: impl Fn(&T) -> Val,
+    ) -> Result<Vec<String>, GenerateError> {
+        lanes
+            .iter()
+            .map(|lane| self.rust_val(&to_val(lane)))
+            .collect()
+    }
+
     /// A suitable default value for this type
     fn rust_default_val(&self, val: &Val) -> Result<String, GenerateError> {
         use Val::*;
         let out = match val {
-            Ref(pointee) => self.rust_default_val(pointee)?,
+            Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
+            // Only nested refs get here (a var for a ref is its pointee)
+            Ref(_) => "core::ptr::null_mut()".to_string(),
             Ptr(_) => "0 as *mut ()".to_string(),
             Bool(_) => "false".to_string(),
             Array(vals) => {
@@ -568,16 +1802,94 @@ impl RustcAbiImpl {
                 output.push(']');
                 output
             }
-            Struct(name, fields) => {
+            EmptyArray(_) => "[]".to_string(),
+            Transparent(name, inner) => format!("{name}({})", self.rust_default_val(inner)?),
+            Nullable(..) => "None".to_string(),
+            Struct(name, fields) | ReprStruct(name, _, fields) => {
+                let layout = self.rust_struct_layout(val)?;
                 let mut output = String::new();
                 output.push_str(&format!("{name} {{ "));
-                for (idx, field) in fields.iter().enumerate() {
-                    let part = format!("{}: {},", FIELD_NAMES[idx], self.rust_default_val(field)?);
+                if layout.bitfield_align.is_some() {
+                    output.push_str(&format!("{BITFIELD_ALIGN_NAME}: [],"));
+                }
+                for member in &layout.members {
+                    let part = match member {
+                        RustStructMember::Field(idx) => format!(
+                            "{}: {},",
+                            FIELD_NAMES[*idx],
+                            self.rust_default_val(&fields[*idx])?
+                        ),
+                        RustStructMember::Bitfields { run_idx, len, .. } => {
+                            format!("{BITFIELD_NAME}{run_idx}: [0; {len}],")
+                        }
+                    };
                     output.push_str(&part);
                 }
                 output.push_str(" }");
                 output
             }
+            Union(name, variants, _active) => {
+                format!(
+                    "{name} {{ {}: {} }}",
+                    FIELD_NAMES[0],
+                    self.rust_default_val(&variants[0])?
+                )
+            }
+            Enum(name, _discriminants, _active) => {
+                format!("{name}::{}", VARIANT_NAMES[0])
+            }
+            TaggedUnion(name, variants, _active) => {
+                let (_discriminant, fields) = &variants[0];
+                let mut output = String::new();
+                output.push_str(&format!("{name}::{}", VARIANT_NAMES[0]));
+                if !fields.is_empty() {
+                    output.push_str(" { ");
+                    for (idx, field) in fields.iter().enumerate() {
+                        let part =
+                            format!("{}: {},", FIELD_NAMES[idx], self.rust_default_val(field)?);
+                        output.push_str(&part);
+                    }
+                    output.push_str(" }");
+                }
+                output
+            }
+            Vector(_) => "core::mem::zeroed()".to_string(),
+            FnPtr(..) => {
+                return Err(GenerateError::RustUnsupported(
+                    "function pointers can only be passed directly as arguments".to_string(),
+                ));
+            }
+            MutRef(..) => {
+                return Err(GenerateError::RustUnsupported(
+                    "mutable refs can only be passed as inputs".to_string(),
+                ));
+            }
+            Char(_) => "'\\0'".to_string(),
+            Unit => "()".to_string(),
+            Tuple(fields) => {
+                let mut output = String::from("(");
+                for field in fields {
+                    output.push_str(&format!("{}, ", self.rust_default_val(field)?));
+                }
+                output.push(')');
+                output
+            }
+            Slice(_) => "&[]".to_string(),
+            Str(_) => "\"\"".to_string(),
+            Boxed(pointee) => format!("Box::new({})", self.rust_default_val(pointee)?),
+            RustOption(..) => "None".to_string(),
+            CxxRef(_) | Class(..) | Base(_) | NoUniqueAddress(_) => {
+                return Err(GenerateError::RustUnsupported(
+                    "C++-only types don't exist in Rust".to_string(),
+                ));
+            }
+            Bitfield(..) => {
+                return Err(GenerateError::RustUnsupported(
+                    "bitfields can only be struct fields".to_string(),
+                ));
+            }
+            Float(FloatVal::c__Complex_float(..)) => "ComplexF32 { re: 0.0, im: 0.0 }".to_string(),
+            Float(FloatVal::c__Complex_double(..)) => "ComplexF64 { re: 0.0, im: 0.0 }".to_string(),
             Float(..) => "0.0".to_string(),
             Int(IntVal::c__int128(..)) => {
                 if STRUCT_128 {
@@ -611,13 +1923,101 @@ impl RustcAbiImpl {
         use std::fmt::Write;
         let mut output = String::new();
         for path in self.rust_var_paths(val, from, is_var_root)? {
-            writeln!(output, "        WRITE_FIELD.unwrap()({to}, &{path} as *const _ as *const _, core::mem::size_of_val(&{path}) as u32);").unwrap();
+            writeln!(output, "{}", self.rust_write_field(to, &path)).unwrap();
         }
         write!(output, "        FINISHED_VAL.unwrap()({to});").unwrap();
 
         Ok(output)
     }
 
+    /// Write out the field at `path`.
+    fn rust_write_field(&self, to: &str, path: &str) -> String {
+        // Copy the field out first, because the fields of packed structs
+        // can't be borrowed, and bitfields are computed.
+        format!("        {{ let field = {path}; WRITE_FIELD.unwrap()({to}, &field as *const _ as *const _, core::mem::size_of_val(&field) as u32); }}")
+    }
+
+    /// Like rust_write_val, but for a fixed input: in `--poison` mode, anything
+    /// that got poisoned also has its raw bytes reported, after its fields.
+    ///
+    /// If `widened`, narrow inputs were actually taken as pointer-sized ints
+    /// (see [`Func::poison_widened`][]), and their raw bytes are the whole thing.
+    fn rust_write_input(
+        &self,
+        val: &Val,
+        to: &str,
+        from: &str,
+        is_var_root: bool,
+        widened: bool,
+    ) -> Result<String, GenerateError> {
+        use std::fmt::Write;
+        let mut output = String::new();
+        if let (Some(Poison::HighBits { .. }), true) = (val.poison(self.target()), widened) {
+            let narrowed = match val {
+                Val::Bool(_) => format!("({from} as u8 != 0)"),
+                _ => format!("({from} as {})", self.rust_arg_type(val)?),
+            };
+            writeln!(output, "{}", self.rust_write_field(to, &narrowed)).unwrap();
+            writeln!(output, "{}", self.rust_write_field(to, from)).unwrap();
+            write!(output, "        FINISHED_VAL.unwrap()({to});").unwrap();
+            return Ok(output);
+        }
+        for path in self.rust_var_paths(val, from, is_var_root)? {
+            writeln!(output, "{}", self.rust_write_field(to, &path)).unwrap();
+        }
+        match val.poison(self.target()) {
+            // A typed Rust value's padding is uninit, so reading it would be UB:
+            // report no raw bytes (the other side's are just informational anyway)
+            Some(Poison::Padding) if self.poison => writeln!(output, "        WRITE_FIELD.unwrap()({to}, core::ptr::NonNull::<u8>::dangling().as_ptr(), 0);").unwrap(),
+            Some(Poison::HighBits { signed, .. }) if self.poison || self.check_extension => {
+                let ty = if signed { "isize" } else { "usize" };
+                let path = format!("({from} as {ty})");
+                writeln!(output, "{}", self.rust_write_field(to, &path)).unwrap();
+            }
+            _ => {}
+        }
+        write!(output, "        FINISHED_VAL.unwrap()({to});").unwrap();
+        Ok(output)
+    }
+
+    /// The writes that copy every field of `from` into the place `to` one at a time,
+    /// which leaves `to`'s padding alone.
+    fn rust_poison_writes(&self, val: &Val, to: &str, from: &str) -> Vec<String> {
+        match val {
+            Val::Struct(_name, fields) | Val::ReprStruct(_name, _, fields) => fields
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, field)| {
+                    let field_name = FIELD_NAMES[idx];
+                    let to = format!("{to}.{field_name}");
+                    let from = format!("{from}.{field_name}");
+                    self.rust_poison_writes(field, &to, &from)
+                })
+                .collect(),
+            Val::Union(_name, variants, active) => {
+                let field_name = FIELD_NAMES[*active];
+                let to = format!("{to}.{field_name}");
+                let from = format!("{from}.{field_name}");
+                self.rust_poison_writes(&variants[*active], &to, &from)
+            }
+            Val::Array(vals) => vals
+                .iter()
+                .enumerate()
+                .flat_map(|(i, elem)| {
+                    self.rust_poison_writes(elem, &format!("{to}[{i}]"), &format!("{from}[{i}]"))
+                })
+                .collect(),
+            Val::EmptyArray(_) => vec![],
+            Val::Transparent(_name, inner) => {
+                self.rust_poison_writes(inner, &format!("{to}.0"), &format!("{from}.0"))
+            }
+            // Packed structs can't have their fields borrowed, so write through raw pointers
+            _ => vec![format!(
+                "core::ptr::addr_of_mut!({to}).write_unaligned({from});"
+            )],
+        }
+    }
+
     /// Compute the paths to every subfield of this value, with `from`
     /// as the base path to that value, for rust_write_val's use.
     fn rust_var_paths(
@@ -627,18 +2027,127 @@ impl RustcAbiImpl {
         is_var_root: bool,
     ) -> Result<Vec<String>, GenerateError> {
         let paths = match val {
-            Val::Int(_) | Val::Float(_) | Val::Bool(_) | Val::Ptr(_) => {
+            Val::Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
+            Val::Int(_)
+            | Val::Float(_)
+            | Val::Bool(_)
+            | Val::Ptr(_)
+            | Val::Enum(..)
+            | Val::Vector(_)
+            | Val::Char(_) => {
                 vec![format!("{from}")]
             }
-            Val::Struct(_name, fields) => {
+            // The callback's own inputs/outputs are reported when it's called
+            Val::FnPtr(..) => vec![],
+            Val::Unit => vec![],
+            Val::CxxRef(_) | Val::Class(..) | Val::Base(_) | Val::NoUniqueAddress(_) => {
+                return Err(GenerateError::RustUnsupported(
+                    "C++-only types don't exist in Rust".to_string(),
+                ));
+            }
+            Val::Tuple(fields) => {
                 let mut paths = vec![];
                 for (idx, field) in fields.iter().enumerate() {
-                    let base = format!("{from}.{}", FIELD_NAMES[idx]);
+                    let base = format!("{from}.{idx}");
                     paths.extend(self.rust_var_paths(field, &base, false)?);
                 }
                 paths
             }
-            Val::Ref(pointee) => {
+            Val::Slice(elems) => {
+                let mut paths = vec![format!("{from}.len()")];
+                if let Val::Array(vals) = &**elems {
+                    for (i, elem) in vals.iter().enumerate() {
+                        let base = format!("{from}[{i}]");
+                        paths.extend(self.rust_var_paths(elem, &base, false)?);
+                    }
+                }
+                paths
+            }
+            Val::Str(val) => {
+                let mut paths = vec![format!("{from}.len()")];
+                for i in 0..val.len() {
+                    paths.push(format!("{from}.as_bytes()[{i}]"));
+                }
+                paths
+            }
+            Val::Boxed(pointee) => {
+                let base = format!("(*{from})");
+                self.rust_var_paths(pointee, &base, false)?
+            }
+            Val::RustOption(inner, is_some) => {
+                let mut paths = vec![format!("{from}.is_some()")];
+                if *is_some {
+                    let base = format!("(*{from}.as_ref().unwrap())");
+                    paths.extend(self.rust_var_paths(inner, &base, false)?);
+                }
+                paths
+            }
+            Val::Struct(_name, fields) | Val::ReprStruct(_name, _, fields) => {
+                let mut paths = vec![];
+                for member in self.rust_struct_layout(val)?.members {
+                    match member {
+                        RustStructMember::Field(idx) => {
+                            let base = format!("{from}.{}", FIELD_NAMES[idx]);
+                            paths.extend(self.rust_var_paths(&fields[idx], &base, false)?);
+                        }
+                        RustStructMember::Bitfields {
+                            run_idx, bitfields, ..
+                        } => {
+                            // Extract the bits ourselves, sign-extending if needed
+                            for (idx, offset, width) in bitfields {
+                                let Val::Bitfield(int_val, _) = &fields[idx] else {
+                                    unreachable!()
+                                };
+                                let ty = self.rust_arg_type(&Val::Int(int_val.clone()))?;
+                                let shifted = format!("(bits << {})", 128 - offset - width);
+                                let shifted = if int_val.is_signed(self.target()) {
+                                    format!("{shifted} as i128")
+                                } else {
+                                    shifted
+                                };
+                                paths.push(format!(
+                                    "{{ let mut bits = 0u128; for (i, byte) in {from}.{BITFIELD_NAME}{run_idx}.iter().enumerate() {{ bits |= (*byte as u128) << (i * 8); }} ({shifted} >> {}) as {ty} }}",
+                                    128 - width
+                                ));
+                            }
+                        }
+                    }
+                }
+                paths
+            }
+            Val::Bitfield(..) => {
+                return Err(GenerateError::RustUnsupported(
+                    "bitfields can only be struct fields".to_string(),
+                ));
+            }
+            Val::Union(_name, variants, active) => {
+                // Only the active variant has meaningful bytes
+                let base = format!("{from}.{}", FIELD_NAMES[*active]);
+                self.rust_var_paths(&variants[*active], &base, false)?
+            }
+            Val::TaggedUnion(name, variants, active) => {
+                // Rust doesn't let us name the tag or the fields of an enum directly,
+                // so read the tag out of the raw memory (repr(C, int) guarantees it's
+                // at the start) and get at the fields by matching on the variant.
+                let (discriminant, fields) = &variants[*active];
+                let tag_ty = self.rust_arg_type(&Val::Int(discriminant.clone()))?;
+                let mut paths = vec![format!("(*(&{from} as *const {name} as *const {tag_ty}))")];
+                let fallback = if variants.len() > 1 {
+                    ", _ => unreachable!()"
+                } else {
+                    ""
+                };
+                for (idx, field) in fields.iter().enumerate() {
+                    let field_name = FIELD_NAMES[idx];
+                    let base = format!(
+                        "(*match &{from} {{ {name}::{} {{ {field_name}, .. }} => {field_name}{fallback} }})",
+                        VARIANT_NAMES[*active],
+                    );
+                    paths.extend(self.rust_var_paths(field, &base, false)?);
+                }
+                paths
+            }
+            Val::Ref(pointee) | Val::MutRef(pointee, _) => {
                 if is_var_root {
                     self.rust_var_paths(pointee, from, false)?
                 } else {
@@ -654,6 +2163,28 @@ impl RustcAbiImpl {
                 }
                 paths
             }
+            // There's nothing in here to check
+            Val::EmptyArray(_) => vec![],
+            Val::Transparent(_name, inner) => {
+                let base = format!("{from}.0");
+                self.rust_var_paths(inner, &base, false)?
+            }
+            Val::Nullable(inner, is_some) => match (&**inner, is_some) {
+                (Val::Ref(pointee), true) => {
+                    if is_var_root {
+                        self.rust_var_paths(pointee, from, false)?
+                    } else {
+                        let base = format!("(*{from}.unwrap())");
+                        self.rust_var_paths(pointee, &base, false)?
+                    }
+                }
+                (Val::FnPtr(..), true) => vec![],
+                // The caller's var is the unused pointee, so report a null
+                (Val::Ref(_), false) if is_var_root => {
+                    vec!["core::ptr::null::<()>()".to_string()]
+                }
+                _ => vec![from.to_string()],
+            },
         };
 
         Ok(paths)
@@ -0,0 +1,1410 @@
+use crate::Config;
+
+use super::super::*;
+use super::*;
+
+pub static ZIG_TEST_PREFIX: &str = include_str!("../../harness/zig_test_prefix.zig");
+
+/// There's no `c_wchar_t` in Zig, so pick the type the target uses (same as libc).
+fn zig_wchar_t(target: &str) -> &'static str {
+    let triple = TargetTriple::new(target);
+    if triple.is_windows() {
+        "u16"
+    } else if triple.is_linux_like() && matches!(triple.arch, "aarch64" | "arm") {
+        "u32"
+    } else {
+        "i32"
+    }
+}
+
+/// Whether this value takes up no space at all.
+///
+/// Zig makes pointers to these zero-sized too, so they can't stand in for a C pointer.
+fn zig_is_zero_sized(val: &Val) -> bool {
+    match val {
+        Val::Struct(_name, fields) => fields.iter().all(zig_is_zero_sized),
+        Val::ReprStruct(_name, repr, fields) => {
+            repr.align.is_none() && fields.iter().all(zig_is_zero_sized)
+        }
+        Val::Array(vals) => zig_is_zero_sized(&vals[0]),
+        Val::EmptyArray(_) => true,
+        Val::Transparent(_name, inner) => zig_is_zero_sized(inner),
+        _ => false,
+    }
+}
+
+/// An anonymous initializer with these parts (Zig figures out the type from where it goes).
+fn zig_init(parts: &[String]) -> String {
+    if parts.is_empty() {
+        ".{}".to_string()
+    } else {
+        format!(".{{ {} }}", parts.join(", "))
+    }
+}
+
+/// The bytes of the value at the place `place`.
+fn zig_bytes(place: &str) -> String {
+    format!("std.mem.asBytes(&{place})")
+}
+
+/// Native Zig, with `extern struct`s and `export fn`s, built with `zig build-lib`.
+///
+/// Zig lowers the C ABI itself (in its self-hosted backends too), so this checks
+/// that against everyone else. This uses the spellings of Zig 0.12/0.13.
+pub struct ZigAbiImpl {
+    poison: bool,
+    check_extension: bool,
+    profile: FlagProfile,
+}
+
+impl AbiImpl for ZigAbiImpl {
+    fn name(&self) -> &'static str {
+        ABI_IMPL_ZIG
+    }
+    fn lang(&self) -> &'static str {
+        "zig"
+    }
+    fn src_ext(&self) -> &'static str {
+        "zig"
+    }
+
+    fn supports_convention(&self, convention: CallingConvention) -> bool {
+        self.zig_convention_decl(convention).is_ok()
+    }
+
+    fn supports_val(&self, val: &Val) -> bool {
+        // Arrays are only "unsupported" by zig_arg_type to catch them being passed by-value
+        self.zig_type(val).is_ok()
+    }
+
+    fn supports_callee(&self, func: &Func) -> bool {
+        // Defining variadic functions needs @cVaStart, which isn't implemented everywhere
+        !func.is_variadic()
+    }
+
+    fn generate_callee(
+        &self,
+        f: &mut dyn Write,
+        test: &Test,
+        convention: CallingConvention,
+    ) -> Result<(), GenerateError> {
+        self.write_zig_prefix(f, test, convention)?;
+
+        // Generate the impls
+        for function in &test.funcs {
+            if !function.has_convention(convention) {
+                continue;
+            }
+            // If the caller is meant to extend the narrow args, take them as
+            // whole registers to see what it actually left in there
+            let widened = function
+                .poison_widened(self.target())
+                .filter(|_| self.widens_callee());
+            write!(f, "export ")?;
+            self.write_zig_signature(f, widened.as_ref().unwrap_or(function), convention)?;
+            writeln!(f, " {{")?;
+            self.write_zig_body(
+                f,
+                function,
+                convention,
+                "CALLEE_INPUTS",
+                "CALLEE_OUTPUTS",
+                "CALLEE_POSTCALL",
+                true,
+                widened.is_some(),
+            )?;
+            writeln!(f, "}}")?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+
+    fn generate_caller(
+        &self,
+        f: &mut dyn Write,
+        test: &Test,
+        convention: CallingConvention,
+    ) -> Result<(), GenerateError> {
+        self.write_zig_prefix(f, test, convention)?;
+
+        // Generate the extern decls
+        for function in &test.funcs {
+            write!(f, "extern ")?;
+            self.write_zig_signature(f, function, convention)?;
+            writeln!(f, ";")?;
+        }
+        writeln!(f)?;
+
+        // Generate the callbacks the callee will call
+        for function in &test.funcs {
+            if !function.has_convention(convention) {
+                continue;
+            }
+            for (_arg_idx, callback) in function.callbacks() {
+                self.write_zig_signature(f, &callback, convention)?;
+                writeln!(f, " {{")?;
+                self.write_zig_body(
+                    f,
+                    &callback,
+                    convention,
+                    "CALLER_INPUTS",
+                    "CALLER_OUTPUTS",
+                    "CALLER_POSTCALL",
+                    false,
+                    false,
+                )?;
+                writeln!(f, "}}")?;
+                writeln!(f)?;
+            }
+        }
+
+        writeln!(f, "export fn do_test() void {{")?;
+
+        // Generate the impls
+        for function in &test.funcs {
+            if !function.has_convention(convention) {
+                continue;
+            }
+            // Add an extra scope to avoid clashes between subtests
+            writeln!(f, "{{")?;
+            self.write_zig_call(
+                f,
+                function,
+                convention,
+                &function.name,
+                "",
+                "CALLER_INPUTS",
+                "CALLER_OUTPUTS",
+                "CALLER_POSTCALL",
+            )?;
+            writeln!(f, "    finishedFunc(CALLER_INPUTS, CALLER_OUTPUTS);")?;
+            writeln!(f, "    finishedFunc(CALLER_POSTCALL, CALLEE_POSTCALL);")?;
+            writeln!(f, "}}")?;
+            writeln!(f)?;
+        }
+        writeln!(f, "}}")?;
+        Ok(())
+    }
+
+    fn compile_callee(&self, src_path: &Path, lib_name: &str) -> Result<String, BuildError> {
+        let target = TargetTriple::new(self.target());
+        let lib_path = if target.is_msvc() {
+            format!("target/temp/{lib_name}.lib")
+        } else {
+            format!("target/temp/lib{lib_name}.a")
+        };
+        // Zig only has build modes, so pick the closest one
+        let build_mode = match &*self.profile.opt_level {
+            "0" => "Debug",
+            "s" | "z" => "ReleaseSmall",
+            _ => "ReleaseFast",
+        };
+        let mut cmd = Command::new("zig");
+        cmd.arg("build-lib")
+            .arg("-static")
+            .arg("-O")
+            .arg(build_mode)
+            // Bring along whatever runtime support the code needs (its symbols are weak,
+            // so they don't clash with the ones rustc links into the harness)
+            .arg("-fcompiler-rt")
+            .arg("--cache-dir")
+            .arg("target/temp/zig-cache")
+            .arg(format!("-femit-bin={lib_path}"));
+        if let Some(target_cpu) = &self.profile.target_cpu {
+            cmd.arg(format!("-mcpu={target_cpu}"));
+        }
+        if !target.is_windows() {
+            cmd.arg(if self.profile.pic {
+                "-fPIC"
+            } else {
+                "-fno-PIC"
+            });
+        }
+        cmd.arg(src_path);
+        eprintln!("running: {:?}", cmd);
+        let out = cmd.output()?;
+
+        if !out.status.success() {
+            Err(BuildError::ZigCompile(out))
+        } else {
+            Ok(String::from(lib_name))
+        }
+    }
+
+    fn compile_caller(&self, src_path: &Path, lib_name: &str) -> Result<String, BuildError> {
+        // Currently no need to be different
+        self.compile_callee(src_path, lib_name)
+    }
+}
+
+impl ZigAbiImpl {
+    pub fn new(system_info: &Config, profile: &FlagProfile) -> Self {
+        Self {
+            poison: system_info.poison,
+            check_extension: system_info.check_extension,
+            profile: profile.clone(),
+        }
+    }
+
+    fn zig_convention_decl(
+        &self,
+        convention: CallingConvention,
+    ) -> Result<&'static str, GenerateError> {
+        use CallingConvention::*;
+        let target = TargetTriple::new(self.target());
+        let windows_x86 = target.is_windows() && target.arch == "x86";
+        let val = match convention {
+            Handwritten => "handwritten",
+            All => {
+                // All is sugar, we shouldn't get here!
+                return Err(GenerateError::UnsupportedConvention);
+            }
+            System | Win64 | Sysv64 | Aapcs => {
+                // Same as C, these should be covered by the other conventions
+                return Err(GenerateError::UnsupportedConvention);
+            }
+            Rust => {
+                return Err(GenerateError::UnsupportedConvention);
+            }
+            C => ".C",
+            // Zig only accepts these on the targets where they actually mean something
+            Cdecl if target.is_windows() => ".C",
+            Stdcall if windows_x86 => ".Stdcall",
+            Fastcall if windows_x86 => ".Fastcall",
+            Vectorcall if target.is_windows() && matches!(target.arch, "x86" | "x86_64") => {
+                ".Vectorcall"
+            }
+            Cdecl | Stdcall | Fastcall | Vectorcall => {
+                return Err(GenerateError::UnsupportedConvention);
+            }
+        };
+        Ok(val)
+    }
+
+    /// Whether narrow fixed inputs get passed with garbage in their high bits.
+    ///
+    /// That means calling through a widened signature, which is only reliable
+    /// unoptimized (see [`Func::poison_widened`][]), so otherwise they're passed normally.
+    fn poisons_high_bits(&self) -> bool {
+        let poisons = if self.check_extension {
+            Extender::for_target(self.target()) == Extender::Callee
+        } else {
+            self.poison
+        };
+        poisons && self.profile.opt_level == "0"
+    }
+
+    /// Whether callees take narrow fixed inputs as pointer-sized ints,
+    /// to check that the caller extended them (again, only unoptimized).
+    fn widens_callee(&self) -> bool {
+        self.check_extension
+            && Extender::for_target(self.target()) != Extender::Callee
+            && self.profile.opt_level == "0"
+    }
+
+    /// Every test should start by loading in the harness' "header"
+    /// and declaring any types that will be used.
+    fn write_zig_prefix(
+        &self,
+        f: &mut dyn Write,
+        test: &Test,
+        convention: CallingConvention,
+    ) -> Result<(), GenerateError> {
+        // Load test harness "headers"
+        write!(f, "{}", ZIG_TEST_PREFIX)?;
+
+        // Declare the types (Zig doesn't care what order they're in)
+        let mut type_decls = std::collections::HashMap::<String, String>::new();
+        for function in &test.funcs {
+            for val in function.vals() {
+                for (name, decl) in self.zig_type_decls(val, convention)? {
+                    match type_decls.entry(name) {
+                        std::collections::hash_map::Entry::Occupied(entry) => {
+                            if entry.get() != &decl {
+                                return Err(GenerateError::InconsistentStructDefinition {
+                                    name: entry.key().clone(),
+                                    old_decl: entry.remove(),
+                                    new_decl: decl,
+                                });
+                            }
+                        }
+                        std::collections::hash_map::Entry::Vacant(entry) => {
+                            writeln!(f, "{decl}")?;
+                            entry.insert(decl);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Emit a function signature
+    fn write_zig_signature(
+        &self,
+        f: &mut dyn Write,
+        function: &Func,
+        convention: CallingConvention,
+    ) -> Result<(), GenerateError> {
+        write!(f, "fn {}", function.name)?;
+        self.write_zig_params(f, function, convention, true)
+    }
+
+    /// Emit a function's args, convention, and return type (also used for function
+    /// pointer types, which don't get `named` args).
+    fn write_zig_params(
+        &self,
+        f: &mut dyn Write,
+        function: &Func,
+        convention: CallingConvention,
+        named: bool,
+    ) -> Result<(), GenerateError> {
+        let convention_decl = self.zig_convention_decl(convention)?;
+        for output in &function.output {
+            if let Val::MutRef(..) = output {
+                return Err(GenerateError::ZigUnsupported(
+                    "mutable refs can only be passed as inputs".to_string(),
+                ));
+            }
+            if let Val::Nullable(inner, _) = output {
+                if let Val::Ref(_) = **inner {
+                    return Err(GenerateError::ZigUnsupported(
+                        "nullable refs can only be passed as inputs".to_string(),
+                    ));
+                }
+            }
+        }
+        let mut params = vec![];
+        for (idx, input) in function.inputs.iter().enumerate() {
+            params.push((ARG_NAMES[idx].to_string(), self.zig_arg_type(input)?));
+        }
+        // By-ref and extra outputs require out-params
+        let mut return_ty = "void".to_string();
+        for (idx, output) in function.lowered_outputs().iter().enumerate() {
+            if let Val::Ref(_) = output {
+                params.push((out_param_name(idx), self.zig_arg_type(output)?));
+            } else {
+                return_ty = self.zig_arg_type(output)?;
+            }
+        }
+        let mut params = params
+            .into_iter()
+            .map(|(name, ty)| if named { format!("{name}: {ty}") } else { ty })
+            .collect::<Vec<_>>();
+        if function.is_variadic() {
+            if function.inputs.is_empty() {
+                return Err(GenerateError::ZigUnsupported(
+                    "variadic functions need at least one fixed arg".to_string(),
+                ));
+            }
+            params.push("...".to_string());
+        }
+        write!(
+            f,
+            "({}) callconv({convention_decl}) {return_ty}",
+            params.join(", ")
+        )?;
+        Ok(())
+    }
+
+    /// Emit the body of a function that reports its inputs, calls any callbacks
+    /// it was passed, writes through its mutable refs, and then reports and
+    /// returns its output.
+    #[allow(clippy::too_many_arguments)]
+    fn write_zig_body(
+        &self,
+        f: &mut dyn Write,
+        function: &Func,
+        convention: CallingConvention,
+        inputs: &str,
+        outputs: &str,
+        postcall: &str,
+        finish_func: bool,
+        widened_inputs: bool,
+    ) -> Result<(), GenerateError> {
+        writeln!(f)?;
+        for (idx, input) in function.inputs.iter().enumerate() {
+            let write =
+                self.zig_write_input(input, inputs, ARG_NAMES[idx], false, widened_inputs)?;
+            writeln!(f, "{write}")?;
+        }
+        writeln!(f)?;
+        for (arg_idx, callback) in function.callbacks() {
+            let arg_name = ARG_NAMES[arg_idx];
+            let var_prefix = format!("{arg_name}_");
+            let callee = if let Val::Nullable(..) = function.inputs[arg_idx] {
+                format!("{arg_name}.?")
+            } else {
+                arg_name.to_string()
+            };
+            self.write_zig_call(
+                f,
+                &callback,
+                convention,
+                &callee,
+                &var_prefix,
+                inputs,
+                outputs,
+                postcall,
+            )?;
+            writeln!(f)?;
+        }
+        for (arg_idx, after) in function.mut_refs() {
+            // Report it by reading it back through the ref, so a callee that
+            // got a copy will still think it wrote the value
+            let arg_name = ARG_NAMES[arg_idx];
+            let after_name = format!("{arg_name}_after");
+            writeln!(
+                f,
+                "    const {after_name}: {} = {};",
+                self.zig_type(after)?,
+                self.zig_val(after)?
+            )?;
+            writeln!(f, "    {} = {after_name};", self.zig_deref(after, arg_name))?;
+            let after_ref = Val::Ref(Box::new(after.clone()));
+            writeln!(
+                f,
+                "{}",
+                self.zig_write_val(&after_ref, postcall, arg_name, false)?
+            )?;
+            writeln!(f)?;
+        }
+        let lowered_outputs = function.lowered_outputs();
+        for (idx, output) in lowered_outputs.iter().enumerate() {
+            let var_name = output_name(idx);
+            writeln!(
+                f,
+                "    const {var_name}: {} = {};",
+                self.zig_var_type(output)?,
+                self.zig_val(output)?
+            )?;
+            writeln!(
+                f,
+                "{}",
+                self.zig_write_val(output, outputs, &var_name, true)?
+            )?;
+        }
+        if finish_func {
+            writeln!(f, "    finishedFunc({inputs}, {outputs});")?;
+        }
+        // The first output may be a real return, so it has to go last
+        for (idx, output) in lowered_outputs.iter().enumerate().rev() {
+            writeln!(
+                f,
+                "    {}",
+                self.zig_var_return(output, &output_name(idx), &out_param_name(idx))
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Emit a call to `function` (through `callee`, which may be a function pointer),
+    /// reporting the inputs it's passed, the output it returns, and what its
+    /// mutable refs point to afterwards.
+    ///
+    /// All the local vars will be prefixed with `var_prefix`.
+    #[allow(clippy::too_many_arguments)]
+    fn write_zig_call(
+        &self,
+        f: &mut dyn Write,
+        function: &Func,
+        convention: CallingConvention,
+        callee: &str,
+        var_prefix: &str,
+        inputs: &str,
+        outputs: &str,
+        postcall: &str,
+    ) -> Result<(), GenerateError> {
+        // Inputs
+        for (idx, input) in function.all_inputs().enumerate() {
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[idx]);
+            let is_callback = match input {
+                Val::FnPtr(..) => true,
+                Val::Nullable(inner, true) => matches!(**inner, Val::FnPtr(..)),
+                _ => false,
+            };
+            let val = if is_callback {
+                format!("&{}", function.callback_name(idx))
+            } else if let Val::MutRef(before, _) = input {
+                self.zig_val(before)?
+            } else {
+                self.zig_val(input)?
+            };
+            let ty = self.zig_var_type(input)?;
+            let is_fixed = idx < function.inputs.len();
+            if self.poison && is_fixed && input.poison(self.target()) == Some(Poison::Padding) {
+                // Poison the whole thing and then write the fields in, so the padding
+                // is garbage (an initializer would let the compiler zero it)
+                let init_name = format!("{var_name}_init");
+                writeln!(f, "    const {init_name}: {ty} = {val};")?;
+                writeln!(f, "    var {var_name}: {ty} = undefined;")?;
+                writeln!(
+                    f,
+                    "    @memset(std.mem.asBytes(&{var_name}), {POISON_BYTE:#x});"
+                )?;
+                let writes = self.zig_poison_writes(input, &var_name, &init_name);
+                if writes.is_empty() {
+                    writeln!(f, "    _ = &{init_name};")?;
+                }
+                for write in writes {
+                    writeln!(f, "    {write}")?;
+                }
+            } else {
+                // Zig insists that anything declared `var` gets mutated
+                let mutability = if self.zig_passes_by_ref(input) {
+                    "var"
+                } else {
+                    "const"
+                };
+                writeln!(f, "    {mutability} {var_name}: {ty} = {val};")?;
+            }
+            let write = if is_fixed {
+                self.zig_write_input(input, inputs, &var_name, true, false)?
+            } else {
+                self.zig_write_val(input, inputs, &var_name, true)?
+            };
+            writeln!(f, "{write}")?;
+        }
+        writeln!(f)?;
+
+        // Outputs (the out-params have to be declared before the call)
+        let lowered_outputs = function.lowered_outputs();
+        let mut out_params = vec![];
+        for (idx, output) in lowered_outputs.iter().enumerate() {
+            if let Val::Ref(_) = output {
+                let output_name = format!("{var_prefix}{}", output_name(idx));
+                writeln!(
+                    f,
+                    "    var {output_name}: {} = undefined;",
+                    self.zig_var_type(output)?
+                )?;
+                out_params.push(format!("&{output_name}"));
+            }
+        }
+        match lowered_outputs.first() {
+            Some(output) if !matches!(output, Val::Ref(_)) => {
+                let output_name = format!("{var_prefix}{OUTPUT_NAME}");
+                write!(
+                    f,
+                    "    const {output_name}: {} = ",
+                    self.zig_var_type(output)?
+                )?;
+            }
+            _ => write!(f, "    ")?,
+        }
+
+        // Do the actual call (through a signature with wider args, to poison their high bits)
+        match function
+            .poison_widened(self.target())
+            .filter(|_| self.poisons_high_bits())
+        {
+            Some(widened) => {
+                let mut signature = Vec::new();
+                write!(signature, "*const fn ")?;
+                self.write_zig_params(&mut signature, &widened, convention, false)?;
+                let signature = String::from_utf8(signature).unwrap();
+                // Top-level functions are called by name, and callbacks through their pointers
+                let callee_ptr = if var_prefix.is_empty() {
+                    format!("&{callee}")
+                } else {
+                    callee.to_string()
+                };
+                write!(f, "@as({signature}, @ptrCast({callee_ptr}))(")?;
+            }
+            None => write!(f, "{callee}(")?,
+        }
+        let mut args = vec![];
+        for (idx, input) in function.inputs.iter().enumerate() {
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[idx]);
+            match input.poison(self.target()) {
+                Some(Poison::HighBits { bits, .. }) if self.poisons_high_bits() => {
+                    let narrow = match input {
+                        Val::Bool(_) => format!("@intFromBool({var_name})"),
+                        _ => format!("@as(u{bits}, @bitCast({var_name}))"),
+                    };
+                    args.push(format!(
+                        "(@as(usize, @truncate(@as(u64, {:#x}))) << {bits}) | @as(usize, {narrow})",
+                        poison_word()
+                    ));
+                }
+                _ => args.push(self.zig_arg_pass(input, &var_name)),
+            }
+        }
+        args.extend(out_params);
+        // Unlike C, Zig makes us apply the default argument promotions ourselves
+        for (idx, input) in function.variadic_inputs.iter().enumerate() {
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[function.inputs.len() + idx]);
+            let pass = self.zig_arg_pass(input, &var_name);
+            match input.va_promoted(self.target()) {
+                Some(promoted) => {
+                    let pass = match input {
+                        Val::Bool(_) => format!("@intFromBool({pass})"),
+                        Val::Enum(..) => format!("@intFromEnum({pass})"),
+                        _ => pass,
+                    };
+                    args.push(format!("@as({}, {pass})", self.zig_arg_type(&promoted)?));
+                }
+                None => args.push(pass),
+            }
+        }
+        writeln!(f, "{});", args.join(", "))?;
+
+        for (idx, output) in lowered_outputs.iter().enumerate() {
+            let output_name = format!("{var_prefix}{}", output_name(idx));
+            writeln!(
+                f,
+                "{}",
+                self.zig_write_val(output, outputs, &output_name, true)?
+            )?;
+        }
+        for (arg_idx, after) in function.mut_refs() {
+            let var_name = format!("{var_prefix}{}", ARG_NAMES[arg_idx]);
+            let after_ref = Val::Ref(Box::new(after.clone()));
+            writeln!(
+                f,
+                "{}",
+                self.zig_write_val(&after_ref, postcall, &var_name, true)?
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The declarations of every named type this value uses.
+    fn zig_type_decls(
+        &self,
+        val: &Val,
+        convention: CallingConvention,
+    ) -> Result<Vec<(String, String)>, GenerateError> {
+        use Val::*;
+        match val {
+            Struct(name, fields) | ReprStruct(name, _, fields) => {
+                // Bail out on anything Zig can't lay out
+                self.zig_type(val)?;
+                let mut results = vec![];
+                for field in fields.iter() {
+                    results.extend(self.zig_type_decls(field, convention)?);
+                }
+                let align = match val {
+                    ReprStruct(_, repr, _) => repr.align,
+                    _ => None,
+                };
+                if align.is_some() && fields.is_empty() {
+                    return Err(GenerateError::ZigUnsupported(format!(
+                        "{name} can't be over-aligned without any fields"
+                    )));
+                }
+                let mut output = String::new();
+                output.push_str(&format!("const {name} = extern struct {{\n"));
+                for (idx, field) in fields.iter().enumerate() {
+                    // Over-aligning the first field over-aligns the whole struct
+                    let align_decl = match align {
+                        Some(align) if idx == 0 => format!(" align({align})"),
+                        _ => String::new(),
+                    };
+                    let line = format!(
+                        "    {}: {}{align_decl},\n",
+                        FIELD_NAMES[idx],
+                        self.zig_type(field)?
+                    );
+                    output.push_str(&line);
+                }
+                output.push_str("};\n");
+                results.push((name.clone(), output));
+                Ok(results)
+            }
+            Union(name, variants, _active) => {
+                let mut results = vec![];
+                for variant in variants.iter() {
+                    results.extend(self.zig_type_decls(variant, convention)?);
+                }
+                let mut output = String::new();
+                output.push_str(&format!("const {name} = extern union {{\n"));
+                for (idx, variant) in variants.iter().enumerate() {
+                    let line = format!("    {}: {},\n", FIELD_NAMES[idx], self.zig_type(variant)?);
+                    output.push_str(&line);
+                }
+                output.push_str("};\n");
+                results.push((name.clone(), output));
+                Ok(results)
+            }
+            Transparent(name, inner) => {
+                // repr(transparent) means it's passed exactly like the inner type
+                let mut results = self.zig_type_decls(inner, convention)?;
+                self.zig_type(val)?;
+                let inner_ty = self.zig_type(inner)?;
+                results.push((name.clone(), format!("const {name} = {inner_ty};\n")));
+                Ok(results)
+            }
+            Enum(name, discriminants, _active) => {
+                let decl = self.zig_enum_decl(name, discriminants)?;
+                Ok(vec![(name.clone(), decl)])
+            }
+            TaggedUnion(name, variants, _active) => {
+                // Lower this to the layout specified by RFC 2195, just like C:
+                // a struct containing the tag and a union of the variants' fields.
+                let mut results = vec![];
+                for (_discriminant, fields) in variants.iter() {
+                    for field in fields.iter() {
+                        results.extend(self.zig_type_decls(field, convention)?);
+                    }
+                }
+
+                let tag_name = format!("{name}_Tag");
+                let discriminants = variants
+                    .iter()
+                    .map(|(discriminant, _fields)| discriminant.clone())
+                    .collect::<Vec<_>>();
+                let tag_decl = self.zig_enum_decl(&tag_name, &discriminants)?;
+                results.push((tag_name.clone(), tag_decl));
+
+                let mut payload = String::new();
+                for (idx, (_discriminant, fields)) in variants.iter().enumerate() {
+                    // Fieldless variants are omitted from the union
+                    if fields.is_empty() {
+                        continue;
+                    }
+                    let variant_name = format!("{name}_{}", VARIANT_NAMES[idx]);
+                    let mut output = String::new();
+                    output.push_str(&format!("const {variant_name} = extern struct {{\n"));
+                    for (field_idx, field) in fields.iter().enumerate() {
+                        let line = format!(
+                            "    {}: {},\n",
+                            FIELD_NAMES[field_idx],
+                            self.zig_type(field)?
+                        );
+                        output.push_str(&line);
+                    }
+                    output.push_str("};\n");
+                    results.push((variant_name.clone(), output));
+                    payload.push_str(&format!("    {}: {variant_name},\n", VARIANT_NAMES[idx]));
+                }
+
+                let mut output = String::new();
+                output.push_str(&format!("const {name} = extern struct {{\n"));
+                output.push_str(&format!("    {TAG_NAME}: {tag_name},\n"));
+                if !payload.is_empty() {
+                    let payload_name = format!("{name}_Payload");
+                    let payload_decl =
+                        format!("const {payload_name} = extern union {{\n{payload}}};\n");
+                    results.push((payload_name.clone(), payload_decl));
+                    output.push_str(&format!("    {PAYLOAD_NAME}: {payload_name},\n"));
+                }
+                output.push_str("};\n");
+                results.push((name.clone(), output));
+                Ok(results)
+            }
+            FnPtr(name, inputs, output) => {
+                let mut results = vec![];
+                for val in inputs.iter().chain(output.as_deref()) {
+                    results.extend(self.zig_type_decls(val, convention)?);
+                }
+                let signature = Func {
+                    name: name.clone(),
+                    conventions: vec![convention],
+                    inputs: inputs.clone(),
+                    variadic_inputs: vec![],
+                    output: output.as_deref().cloned().into_iter().collect(),
+                };
+                let mut output = Vec::new();
+                write!(output, "const {name} = *const fn ")?;
+                self.write_zig_params(&mut output, &signature, convention, false)?;
+                writeln!(output, ";")?;
+                results.push((name.clone(), String::from_utf8(output).unwrap()));
+                Ok(results)
+            }
+            Array(vals) => self.zig_type_decls(&vals[0], convention),
+            EmptyArray(elem) => self.zig_type_decls(elem, convention),
+            Ref(pointee) | Nullable(pointee, _) => self.zig_type_decls(pointee, convention),
+            MutRef(before, after) => {
+                let mut results = self.zig_type_decls(before, convention)?;
+                results.extend(self.zig_type_decls(after, convention)?);
+                Ok(results)
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// The declaration of an enum with an explicit tag type and these discriminants.
+    fn zig_enum_decl(&self, name: &str, discriminants: &[IntVal]) -> Result<String, GenerateError> {
+        let repr = self.zig_type(&Val::Int(discriminants[0].clone()))?;
+        let mut output = String::new();
+        output.push_str(&format!("const {name} = enum({repr}) {{\n"));
+        for (idx, discriminant) in discriminants.iter().enumerate() {
+            let line = format!(
+                "    {} = {},\n",
+                VARIANT_NAMES[idx],
+                self.zig_int(discriminant)
+            );
+            output.push_str(&line);
+        }
+        output.push_str("};\n");
+        Ok(output)
+    }
+
+    /// The type to use for a local var (reference-ness stripped)
+    fn zig_var_type(&self, val: &Val) -> Result<String, GenerateError> {
+        match val {
+            Val::Ref(pointee) | Val::MutRef(pointee, _) => self.zig_type(pointee),
+            // The var is the pointee (a None is just a null)
+            Val::Nullable(inner, true) => match &**inner {
+                Val::Ref(pointee) => self.zig_type(pointee),
+                _ => self.zig_arg_type(val),
+            },
+            _ => self.zig_arg_type(val),
+        }
+    }
+
+    /// Whether the caller passes a pointer to its var for this value.
+    fn zig_passes_by_ref(&self, val: &Val) -> bool {
+        match val {
+            Val::Ref(_) | Val::MutRef(..) => true,
+            Val::Nullable(inner, true) => matches!(**inner, Val::Ref(_)),
+            _ => false,
+        }
+    }
+
+    /// How to pass an argument
+    fn zig_arg_pass(&self, val: &Val, arg_name: &str) -> String {
+        if self.zig_passes_by_ref(val) {
+            format!("&{arg_name}")
+        } else {
+            arg_name.to_string()
+        }
+    }
+
+    /// The place that `ptr` (a ref to `pointee`) points at.
+    ///
+    /// Refs to arrays are pointers to their first element, so this reslices them.
+    fn zig_deref(&self, pointee: &Val, ptr: &str) -> String {
+        match pointee.array_elem() {
+            Some((len, _elem)) => format!("{ptr}[0..{len}].*"),
+            None => format!("{ptr}.*"),
+        }
+    }
+
+    /// How to return a value
+    fn zig_var_return(&self, val: &Val, var_name: &str, out_param_name: &str) -> String {
+        if let Val::Ref(pointee) = val {
+            format!("{} = {var_name};", self.zig_deref(pointee, out_param_name))
+        } else {
+            format!("return {var_name};")
+        }
+    }
+
+    /// The type to use for this value when it's passed to or returned from a function.
+    fn zig_arg_type(&self, val: &Val) -> Result<String, GenerateError> {
+        if let Val::Array(_) | Val::EmptyArray(_) = val {
+            // Zig would happily pass these by-value, but C can't (they decay to a
+            // pointer), so they need to be wrapped in Ref for other ABIs to understand.
+            return Err(GenerateError::ZigUnsupported(
+                "Arrays can't be passed directly, wrap this in Ref".to_string(),
+            ));
+        }
+        self.zig_type(val)
+    }
+
+    /// The type to use for this value when it's stored in vars and fields.
+    fn zig_type(&self, val: &Val) -> Result<String, GenerateError> {
+        use IntVal::*;
+        use Val::*;
+        let val = match val {
+            Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
+            Ref(pointee) => {
+                if zig_is_zero_sized(pointee) {
+                    return Err(GenerateError::ZigUnsupported(
+                        "Zig's pointers to zero-sized types are zero-sized too".to_string(),
+                    ));
+                }
+                // Refs to arrays are pointers to their first element, like in C
+                match pointee.array_elem() {
+                    Some((_len, elem)) => format!("[*]{}", self.zig_type(elem)?),
+                    None => format!("*{}", self.zig_type(pointee)?),
+                }
+            }
+            MutRef(before, after) => {
+                if self.zig_type(before)? != self.zig_type(after)? {
+                    return Err(GenerateError::ZigUnsupported(
+                        "a mutable ref's value after the call must have the same type".to_string(),
+                    ));
+                }
+                self.zig_type(&Ref(before.clone()))?
+            }
+            Ptr(_) => "?*anyopaque".to_string(),
+            Bool(_) => "bool".to_string(),
+            Array(vals) => format!("[{}]{}", vals.len(), self.zig_type(&vals[0])?),
+            EmptyArray(elem) => format!("[0]{}", self.zig_type(elem)?),
+            ReprStruct(_, repr, _) if repr.packed.is_some() => {
+                return Err(GenerateError::ZigUnsupported(
+                    "Zig's packed structs are bit-packed, it has nothing like #pragma pack"
+                        .to_string(),
+                ));
+            }
+            Struct(name, _) | ReprStruct(name, _, _) => name.to_string(),
+            Transparent(name, inner) => {
+                if let Ref(_) = **inner {
+                    return Err(GenerateError::ZigUnsupported(
+                        "transparent wrappers around refs aren't supported".to_string(),
+                    ));
+                }
+                // Arrays will bail out here
+                self.zig_arg_type(inner)?;
+                name.to_string()
+            }
+            Union(name, _, _) | Enum(name, _, _) | TaggedUnion(name, _, _) | FnPtr(name, _, _) => {
+                name.to_string()
+            }
+            Nullable(inner, is_some) => {
+                inner
+                    .check_nullable(*is_some)
+                    .map_err(GenerateError::ZigUnsupported)?;
+                match &**inner {
+                    Ref(pointee) if pointee.array_elem().is_some() => {
+                        return Err(GenerateError::ZigUnsupported(
+                            "nullable refs to arrays aren't supported".to_string(),
+                        ));
+                    }
+                    // Already nullable (or just 0)
+                    Ptr(_) | Int(_) => self.zig_type(inner)?,
+                    _ => format!("?{}", self.zig_type(inner)?),
+                }
+            }
+            CxxRef(_) | Class(..) | Base(_) | NoUniqueAddress(_) => {
+                return Err(GenerateError::ZigUnsupported(
+                    "C++-only types don't exist in Zig".to_string(),
+                ));
+            }
+            Char(_) | Unit | Tuple(_) | Slice(_) | Str(_) | Boxed(_) | RustOption(..) => {
+                return Err(GenerateError::ZigUnsupported(
+                    "Rust-only types don't exist in Zig".to_string(),
+                ));
+            }
+            Bitfield(..) => {
+                return Err(GenerateError::ZigUnsupported(
+                    "Zig doesn't have C bitfields (its packed structs are laid out differently)"
+                        .to_string(),
+                ));
+            }
+            Vector(_) => {
+                return Err(GenerateError::ZigUnsupported(
+                    "Zig can't enable target features per function, so x86 vectors aren't supported"
+                        .to_string(),
+                ));
+            }
+            Float(FloatVal::c_double(_)) => "f64".to_string(),
+            Float(FloatVal::c_float(_)) => "f32".to_string(),
+            Float(FloatVal::c__Float16(_)) => "f16".to_string(),
+            Float(FloatVal::c__float128(_)) => "f128".to_string(),
+            Float(FloatVal::c_long_double(_)) => "c_longdouble".to_string(),
+            Float(FloatVal::c__Complex_float(..) | FloatVal::c__Complex_double(..)) => {
+                return Err(GenerateError::ZigUnsupported(
+                    "Zig doesn't have _Complex".to_string(),
+                ));
+            }
+            Int(int_val) => match int_val {
+                c__int128(_) => "i128".to_string(),
+                c_int64_t(_) => "i64".to_string(),
+                c_int32_t(_) => "i32".to_string(),
+                c_int16_t(_) => "i16".to_string(),
+                c_int8_t(_) => "i8".to_string(),
+                c__uint128(_) => "u128".to_string(),
+                c_uint64_t(_) => "u64".to_string(),
+                c_uint32_t(_) => "u32".to_string(),
+                c_uint16_t(_) => "u16".to_string(),
+                c_uint8_t(_) => "u8".to_string(),
+                c_char(_) => "c_char".to_string(),
+                c_schar(_) => "i8".to_string(),
+                c_uchar(_) => "u8".to_string(),
+                c_short(_) => "c_short".to_string(),
+                c_ushort(_) => "c_ushort".to_string(),
+                c_int(_) => "c_int".to_string(),
+                c_uint(_) => "c_uint".to_string(),
+                c_long(_) => "c_long".to_string(),
+                c_ulong(_) => "c_ulong".to_string(),
+                c_longlong(_) => "c_longlong".to_string(),
+                c_ulonglong(_) => "c_ulonglong".to_string(),
+                c_size_t(_) => "usize".to_string(),
+                c_ptrdiff_t(_) => "isize".to_string(),
+                c_intptr_t(_) => "isize".to_string(),
+                c_uintptr_t(_) => "usize".to_string(),
+                c_wchar_t(_) => zig_wchar_t(self.target()).to_string(),
+            },
+        };
+        Ok(val)
+    }
+
+    /// An expression that generates this value.
+    ///
+    /// Aggregates are anonymous initializers, so this must only be used
+    /// where the type is already known (which is everywhere we put values).
+    fn zig_val(&self, val: &Val) -> Result<String, GenerateError> {
+        use Val::*;
+        let val = match val {
+            Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
+            // The var is the pointee
+            Ref(pointee) => self.zig_nested_val(pointee)?,
+            Ptr(0) => "null".to_string(),
+            Ptr(addr) => format!("@ptrFromInt({:#X})", *addr as usize),
+            Bool(val) => format!("{val}"),
+            Array(vals) => {
+                let elems = vals
+                    .iter()
+                    .map(|elem| self.zig_nested_val(elem))
+                    .collect::<Result<Vec<_>, _>>()?;
+                zig_init(&elems)
+            }
+            EmptyArray(_) => zig_init(&[]),
+            Transparent(_name, inner) => self.zig_val(inner)?,
+            Struct(_name, fields) | ReprStruct(_name, _, fields) => {
+                let fields = fields
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, field)| {
+                        Ok(format!(
+                            ".{} = {}",
+                            FIELD_NAMES[idx],
+                            self.zig_nested_val(field)?
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, GenerateError>>()?;
+                zig_init(&fields)
+            }
+            Union(_name, variants, active) => {
+                let variant = format!(
+                    ".{} = {}",
+                    FIELD_NAMES[*active],
+                    self.zig_nested_val(&variants[*active])?
+                );
+                zig_init(&[variant])
+            }
+            Enum(_name, _discriminants, active) => format!(".{}", VARIANT_NAMES[*active]),
+            TaggedUnion(_name, variants, active) => {
+                let (_discriminant, fields) = &variants[*active];
+                let mut parts = vec![format!(".{TAG_NAME} = .{}", VARIANT_NAMES[*active])];
+                if !fields.is_empty() {
+                    let fields = fields
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, field)| {
+                            Ok(format!(
+                                ".{} = {}",
+                                FIELD_NAMES[idx],
+                                self.zig_nested_val(field)?
+                            ))
+                        })
+                        .collect::<Result<Vec<_>, GenerateError>>()?;
+                    let payload = zig_init(&[format!(
+                        ".{} = {}",
+                        VARIANT_NAMES[*active],
+                        zig_init(&fields)
+                    )]);
+                    parts.push(format!(".{PAYLOAD_NAME} = {payload}"));
+                } else if variants.iter().any(|(_, fields)| !fields.is_empty()) {
+                    // Every field has to be initialized, even the payload nobody reads
+                    parts.push(format!(".{PAYLOAD_NAME} = undefined"));
+                }
+                zig_init(&parts)
+            }
+            Nullable(inner, true) => self.zig_val(inner)?,
+            Nullable(inner, false) => match **inner {
+                Int(_) => "0".to_string(),
+                _ => "null".to_string(),
+            },
+            FnPtr(..) => {
+                // The caller passes its generated callback, see Func::callbacks
+                return Err(GenerateError::ZigUnsupported(
+                    "function pointers can only be passed directly as arguments".to_string(),
+                ));
+            }
+            MutRef(..) => {
+                // The caller stores the value before the call, see write_zig_call
+                return Err(GenerateError::ZigUnsupported(
+                    "mutable refs can only be passed directly as arguments".to_string(),
+                ));
+            }
+            Float(float_val) => match *float_val {
+                // Only fall back to the bits if there's no literal for it
+                // (Debug keeps the sign of -0.0, which Zig would read as the int 0)
+                FloatVal::c_double(val) if val.is_finite() => format!("{val:?}"),
+                FloatVal::c_float(val) if val.is_finite() => format!("{val:?}"),
+                FloatVal::c_double(val) => format!("@bitCast(@as(u64, {:#X}))", val.to_bits()),
+                FloatVal::c_float(val) => format!("@bitCast(@as(u32, {:#X}))", val.to_bits()),
+                FloatVal::c__Float16(bits) => format!("@bitCast(@as(u16, {bits:#X}))"),
+                FloatVal::c__float128(bits) => format!("@bitCast(@as(u128, {bits:#X}))"),
+                FloatVal::c_long_double(bits) => {
+                    match LongDoubleFormat::for_target(self.target()) {
+                        LongDoubleFormat::Double => {
+                            format!("@bitCast(@as(u64, {:#X}))", bits as u64)
+                        }
+                        LongDoubleFormat::X87 => {
+                            format!("@bitCast(@as(u80, {:#X}))", bits & ((1 << 80) - 1))
+                        }
+                        LongDoubleFormat::Quad => format!("@bitCast(@as(u128, {bits:#X}))"),
+                    }
+                }
+                FloatVal::c__Complex_float(..) | FloatVal::c__Complex_double(..) => {
+                    return Err(GenerateError::ZigUnsupported(
+                        "Zig doesn't have _Complex".to_string(),
+                    ));
+                }
+            },
+            Int(int_val) => self.zig_int(int_val),
+            CxxRef(_) | Class(..) | Base(_) | NoUniqueAddress(_) => {
+                return Err(GenerateError::ZigUnsupported(
+                    "C++-only types don't exist in Zig".to_string(),
+                ));
+            }
+            Char(_) | Unit | Tuple(_) | Slice(_) | Str(_) | Boxed(_) | RustOption(..) => {
+                return Err(GenerateError::ZigUnsupported(
+                    "Rust-only types don't exist in Zig".to_string(),
+                ));
+            }
+            Bitfield(..) | Vector(_) => {
+                // zig_type already refused these
+                return Err(GenerateError::ZigUnsupported(format!(
+                    "{} values aren't supported",
+                    procgen::arg_ty(val)
+                )));
+            }
+        };
+        Ok(val)
+    }
+
+    /// An expression that generates this value when it's nested inside another.
+    ///
+    /// Unlike a var, a nested ref has to point at something that outlives the
+    /// callee (it might be returning it), so it gets a static of its own.
+    fn zig_nested_val(&self, val: &Val) -> Result<String, GenerateError> {
+        match val {
+            Val::Ref(pointee) => Ok(format!(
+                "&struct {{ var val: {} = {}; }}.val",
+                self.zig_type(pointee)?,
+                self.zig_nested_val(pointee)?
+            )),
+            Val::Nullable(inner, true) if matches!(**inner, Val::Ref(_)) => {
+                self.zig_nested_val(inner)
+            }
+            _ => self.zig_val(val),
+        }
+    }
+
+    /// A literal for this int.
+    fn zig_int(&self, int_val: &IntVal) -> String {
+        use IntVal::*;
+        match *int_val {
+            c__int128(val) => format!("{val}"),
+            c_int64_t(val) => format!("{val}"),
+            c_int32_t(val) => format!("{val}"),
+            c_int16_t(val) => format!("{val}"),
+            c_int8_t(val) => format!("{val}"),
+            c__uint128(val) => format!("{val:#X}"),
+            c_uint64_t(val) => format!("{val:#X}"),
+            c_uint32_t(val) => format!("{val:#X}"),
+            c_uint16_t(val) => format!("{val:#X}"),
+            c_uint8_t(val) => format!("{val:#X}"),
+            // Truncate the value to whatever width the platform uses
+            _ => {
+                let ty = self.zig_type(&Val::Int(int_val.clone())).unwrap();
+                format!("truncInt({ty}, {:#X})", int_val.bits() as u64)
+            }
+        }
+    }
+
+    /// The bytes of a temporary `ty` initialized with `init`.
+    fn zig_temp(&self, ty: &str, init: &str) -> String {
+        zig_bytes(&format!("@as({ty}, {init})"))
+    }
+
+    /// Emit the writeField calls and finishedVal for this value.
+    /// This will write every leaf subfield of the type.
+    /// `to` is the BUFFER to use, `from` is the variable name of the value.
+    fn zig_write_val(
+        &self,
+        val: &Val,
+        to: &str,
+        from: &str,
+        is_var_root: bool,
+    ) -> Result<String, GenerateError> {
+        let paths = self.zig_var_paths(val, from, is_var_root)?;
+        Ok(self.zig_write_paths(&paths, to, from))
+    }
+
+    /// Like zig_write_val, but for a fixed input: in `--poison` mode, anything
+    /// that got poisoned also has its raw bytes reported, after its fields.
+    ///
+    /// If `widened`, narrow inputs were actually taken as pointer-sized ints
+    /// (see [`Func::poison_widened`][]), and their raw bytes are the whole thing.
+    fn zig_write_input(
+        &self,
+        val: &Val,
+        to: &str,
+        from: &str,
+        is_var_root: bool,
+        widened: bool,
+    ) -> Result<String, GenerateError> {
+        let mut paths = self.zig_var_paths(val, from, is_var_root)?;
+        match val.poison(self.target()) {
+            Some(Poison::Padding) if self.poison => paths.push(zig_bytes(from)),
+            Some(Poison::HighBits { bits, .. }) if widened => {
+                let narrowed = match val {
+                    Val::Bool(_) => format!("@as(u8, @truncate({from})) != 0"),
+                    _ => format!("@bitCast(@as(u{bits}, @truncate({from})))"),
+                };
+                paths = vec![
+                    self.zig_temp(&self.zig_arg_type(val)?, &narrowed),
+                    zig_bytes(from),
+                ];
+            }
+            Some(Poison::HighBits { signed, .. }) if self.poison || self.check_extension => {
+                let ty = if signed { "isize" } else { "usize" };
+                let extended = match val {
+                    Val::Bool(_) => format!("@intFromBool({from})"),
+                    _ => from.to_string(),
+                };
+                paths.push(self.zig_temp(ty, &extended));
+            }
+            _ => {}
+        }
+        Ok(self.zig_write_paths(&paths, to, from))
+    }
+
+    /// Write out every path in `paths` as one value (of the var `from`).
+    fn zig_write_paths(&self, paths: &[String], to: &str, from: &str) -> String {
+        use std::fmt::Write;
+        let mut output = String::new();
+        if paths.is_empty() {
+            // Zig won't let a var go unused, even if there's nothing in it to check
+            writeln!(output, "    _ = &{from};").unwrap();
+        }
+        for path in paths {
+            writeln!(output, "    writeField({to}, {path});").unwrap();
+        }
+        write!(output, "    finishedVal({to});").unwrap();
+        output
+    }
+
+    /// The assignments that copy every field of `from` into `to` one at a time,
+    /// which leaves `to`'s padding alone.
+    fn zig_poison_writes(&self, val: &Val, to: &str, from: &str) -> Vec<String> {
+        match val {
+            Val::Struct(_name, fields) | Val::ReprStruct(_name, _, fields) => fields
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, field)| {
+                    let field_name = FIELD_NAMES[idx];
+                    let to = format!("{to}.{field_name}");
+                    let from = format!("{from}.{field_name}");
+                    self.zig_poison_writes(field, &to, &from)
+                })
+                .collect(),
+            Val::Union(_name, variants, active) => {
+                let field_name = FIELD_NAMES[*active];
+                let to = format!("{to}.{field_name}");
+                let from = format!("{from}.{field_name}");
+                self.zig_poison_writes(&variants[*active], &to, &from)
+            }
+            Val::Array(vals) => vals
+                .iter()
+                .enumerate()
+                .flat_map(|(i, elem)| {
+                    self.zig_poison_writes(elem, &format!("{to}[{i}]"), &format!("{from}[{i}]"))
+                })
+                .collect(),
+            Val::EmptyArray(_) => vec![],
+            // It's just another name for the inner type
+            Val::Transparent(_name, inner) => self.zig_poison_writes(inner, to, from),
+            _ => vec![format!("{to} = {from};")],
+        }
+    }
+
+    /// Compute the bytes of every subfield of this value, with `from`
+    /// as the base path to that value, for zig_write_val's use.
+    fn zig_var_paths(
+        &self,
+        val: &Val,
+        from: &str,
+        is_var_root: bool,
+    ) -> Result<Vec<String>, GenerateError> {
+        let paths = match val {
+            Val::Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
+            Val::Float(FloatVal::c_long_double(_)) => {
+                // x87's long double has padding that's just whatever garbage, so only
+                // report the bytes that actually hold the value.
+                let size = LongDoubleFormat::for_target(self.target()).value_size();
+                vec![format!("{}[0..{size}]", zig_bytes(from))]
+            }
+            Val::Int(_) | Val::Float(_) | Val::Bool(_) | Val::Ptr(_) | Val::Enum(..) => {
+                vec![zig_bytes(from)]
+            }
+            // The callback's own inputs/outputs are reported when it's called
+            Val::FnPtr(..) => vec![],
+            Val::Struct(_name, fields) | Val::ReprStruct(_name, _, fields) => {
+                let mut paths = vec![];
+                for (idx, field) in fields.iter().enumerate() {
+                    let base = format!("{from}.{}", FIELD_NAMES[idx]);
+                    paths.extend(self.zig_var_paths(field, &base, false)?);
+                }
+                paths
+            }
+            Val::Union(_name, variants, active) => {
+                // Only the active variant has meaningful bytes
+                let base = format!("{from}.{}", FIELD_NAMES[*active]);
+                self.zig_var_paths(&variants[*active], &base, false)?
+            }
+            Val::TaggedUnion(_name, variants, active) => {
+                // Only the tag and the active variant have meaningful bytes
+                let mut paths = vec![zig_bytes(&format!("{from}.{TAG_NAME}"))];
+                let (_discriminant, fields) = &variants[*active];
+                for (idx, field) in fields.iter().enumerate() {
+                    let base = format!(
+                        "{from}.{PAYLOAD_NAME}.{}.{}",
+                        VARIANT_NAMES[*active], FIELD_NAMES[idx]
+                    );
+                    paths.extend(self.zig_var_paths(field, &base, false)?);
+                }
+                paths
+            }
+            Val::Ref(pointee) | Val::MutRef(pointee, _) => {
+                // Refs to arrays are already pointers to their elements
+                if is_var_root || pointee.array_elem().is_some() {
+                    self.zig_var_paths(pointee, from, false)?
+                } else {
+                    let base = format!("{from}.*");
+                    self.zig_var_paths(pointee, &base, false)?
+                }
+            }
+            Val::Array(vals) => {
+                let mut paths = vec![];
+                for (i, elem) in vals.iter().enumerate() {
+                    let base = format!("{from}[{i}]");
+                    paths.extend(self.zig_var_paths(elem, &base, false)?);
+                }
+                paths
+            }
+            // There's nothing in here to check
+            Val::EmptyArray(_) => vec![],
+            // It's just another name for the inner type
+            Val::Transparent(_name, inner) => self.zig_var_paths(inner, from, false)?,
+            Val::Nullable(inner, is_some) => match (&**inner, is_some) {
+                // The caller's var is the pointee
+                (Val::Ref(_) | Val::FnPtr(..), true) if is_var_root => {
+                    self.zig_var_paths(inner, from, true)?
+                }
+                (Val::Ref(_) | Val::FnPtr(..), true) => {
+                    self.zig_var_paths(inner, &format!("{from}.?"), false)?
+                }
+                _ => vec![zig_bytes(from)],
+            },
+            _ => {
+                // zig_type already refused these
+                return Err(GenerateError::ZigUnsupported(format!(
+                    "{} values aren't supported",
+                    procgen::arg_ty(val)
+                )));
+            }
+        };
+
+        Ok(paths)
+    }
+}
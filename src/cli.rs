@@ -1,5 +1,6 @@
 use crate::{abis::*, Config, OutputFormat};
-use clap::{AppSettings, Arg};
+use clap::{AppSettings, Arg, ErrorKind};
+use std::path::PathBuf;
 
 pub fn make_app() -> Config {
     static ABI_IMPLS: &[&str] = &[
@@ -8,15 +9,28 @@ pub fn make_app() -> Config {
         ABI_IMPL_GCC,
         ABI_IMPL_CLANG,
         ABI_IMPL_MSVC,
+        ABI_IMPL_CXX,
+        ABI_IMPL_GXX,
+        ABI_IMPL_CLANGXX,
+        ABI_IMPL_ZIG,
+        ABI_IMPL_ZIGCC,
     ];
     /// The pairings of impls to run. LHS calls RHS.
     static DEFAULT_TEST_PAIRS: &[(&str, &str)] = &[
-        (ABI_IMPL_RUSTC, ABI_IMPL_CC), // Rust calls C
-        (ABI_IMPL_CC, ABI_IMPL_RUSTC), // C calls Rust
-        (ABI_IMPL_CC, ABI_IMPL_CC),    // C calls C
+        (ABI_IMPL_RUSTC, ABI_IMPL_CC),  // Rust calls C
+        (ABI_IMPL_CC, ABI_IMPL_RUSTC),  // C calls Rust
+        (ABI_IMPL_CC, ABI_IMPL_CC),     // C calls C
+        (ABI_IMPL_RUSTC, ABI_IMPL_CXX), // Rust calls C++
+        (ABI_IMPL_CXX, ABI_IMPL_RUSTC), // C++ calls Rust
+        (ABI_IMPL_CXX, ABI_IMPL_CXX),   // C++ calls C++
     ];
 
-    let app = clap::Command::new("abi-cafe")
+    let impls_help = format!(
+        "Only run the given impls (compilers/languages): any of {}, or one added with --add-c-compiler or --add-rustc-codegen-backend",
+        ABI_IMPLS.join(", ")
+    );
+
+    let mut app = clap::Command::new("abi-cafe")
         .version(clap::crate_version!())
         .about("Compares the FFI ABIs of different langs/compilers by generating and running them.")
         .next_line_help(true)
@@ -36,6 +50,7 @@ pub fn make_app() -> Config {
                     "fastcall",
                     "stdcall",
                     "vectorcall",
+                    "rust",
                     "handwritten",
                 ])
                 .multiple_values(true)
@@ -44,8 +59,7 @@ pub fn make_app() -> Config {
         .arg(
             Arg::new("impls")
                 .long("impls")
-                .long_help("Only run the given impls (compilers/languages)")
-                .possible_values(ABI_IMPLS)
+                .long_help(&*impls_help)
                 .multiple_values(true)
                 .takes_value(true),
         )
@@ -70,6 +84,41 @@ pub fn make_app() -> Config {
                 .multiple_values(true)
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("add-c-compiler")
+                .long("add-c-compiler")
+                .long_help("Add a C compiler, in the form of impl_name:path/to/compiler[:extra flags] (the flags are split on whitespace, and the path can start with a drive letter like C:\\)")
+                .multiple_values(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("flag-matrix")
+                .long("flag-matrix")
+                .long_help("Build every impl with each of the given flag profiles, and test each pair with both sides built with the same one. A profile is a comma-separated list of settings: O0/O1/O2/O3/Os/Oz, cpu=<target cpu>, pic/no-pic, panic=unwind/abort (Rust only). Defaults to just O0")
+                .multiple_values(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("cross-profiles")
+                .long("cross-profiles")
+                .long_help("Test each pair with every combination of the --flag-matrix profiles, so the caller and callee can disagree on their settings"),
+        )
+        .arg(
+            Arg::new("poison")
+                .long("poison")
+                .long_help("Fill struct padding and the high bits of narrow int/bool args with garbage before passing them, and check their raw bytes"),
+        )
+        .arg(
+            Arg::new("check-extension")
+                .long("check-extension")
+                .long_help("Check that narrow int/bool args get sign/zero-extended by whichever side this platform's ABI says is responsible for it"),
+        )
+        .arg(
+            Arg::new("xwin-dir")
+                .long("xwin-dir")
+                .long_help("Where xwin put the Windows SDK and CRT, for cross-compiling the msvc impl with clang-cl and lld-link (otherwise they look in INCLUDE and LIB)")
+                .takes_value(true),
+        )
         .arg(
             Arg::new("output-format")
                 .long("output-format")
@@ -80,8 +129,12 @@ pub fn make_app() -> Config {
         )
         .after_help("");
 
-    let matches = app.get_matches();
+    let matches = app.get_matches_mut();
     let procgen_tests = matches.is_present("procgen-tests");
+    let poison = matches.is_present("poison");
+    let check_extension = matches.is_present("check-extension");
+    let cross_profiles = matches.is_present("cross-profiles");
+    let xwin_dir = matches.value_of("xwin-dir").map(PathBuf::from);
 
     let mut run_conventions: Vec<_> = matches
         .values_of("conventions")
@@ -94,7 +147,7 @@ pub fn make_app() -> Config {
         run_conventions = ALL_CONVENTIONS.to_vec();
     }
 
-    let run_impls = matches
+    let run_impls: Vec<String> = matches
         .values_of("impls")
         .into_iter()
         .flatten()
@@ -126,7 +179,7 @@ pub fn make_app() -> Config {
         .map(String::from)
         .collect();
 
-    let rustc_codegen_backends = matches
+    let rustc_codegen_backends: Vec<(String, String)> = matches
         .values_of("add-rustc-codegen-backend")
         .into_iter()
         .flatten()
@@ -147,6 +200,95 @@ pub fn make_app() -> Config {
         }
     }
 
+    let mut c_compilers = vec![];
+    for spec in matches.values_of("add-c-compiler").into_iter().flatten() {
+        let (name, path, flags) = match spec.split_once(':') {
+            Some((name, rest)) => {
+                let (path, flags) = split_compiler_path(rest);
+                (name, path, flags)
+            }
+            None => ("", "", None),
+        };
+        if name.is_empty() || path.is_empty() {
+            app.error(
+                ErrorKind::InvalidValue,
+                format!(
+                    "invalid --add-c-compiler `{spec}`, must be 'impl_name:path/to/compiler[:flags]'"
+                ),
+            )
+            .exit();
+        }
+        let flags = flags
+            .into_iter()
+            .flat_map(str::split_whitespace)
+            .map(String::from)
+            .collect();
+        let compiler = CustomCCompiler {
+            path: PathBuf::from(path),
+            flags,
+        };
+        c_compilers.push((String::from(name), compiler));
+    }
+
+    for (name, _compiler) in &c_compilers {
+        if ABI_IMPLS.contains(&&**name) {
+            app.error(
+                ErrorKind::InvalidValue,
+                format!("can't add a C compiler named `{name}`, that's already a built-in impl"),
+            )
+            .exit();
+        }
+        if !run_pairs.iter().any(|(a, b)| a == name || b == name) {
+            eprintln!("Warning: C compiler `{name}` is not tested.");
+            eprintln!("Hint: Try using `--pairs {name}_calls_rustc` or `--pairs cc_calls_{name}`.");
+            eprintln!();
+        }
+    }
+
+    // Added impls can't be possible_values, so check everything here instead
+    let known_impls: Vec<&str> = ABI_IMPLS
+        .iter()
+        .copied()
+        .chain(rustc_codegen_backends.iter().map(|(name, _)| &**name))
+        .chain(c_compilers.iter().map(|(name, _)| &**name))
+        .collect();
+    let unknown_impl = run_impls
+        .iter()
+        .map(|name| (name, "--impls"))
+        .chain(
+            run_pairs
+                .iter()
+                .flat_map(|(a, b)| [(a, "--pairs"), (b, "--pairs")]),
+        )
+        .find(|(name, _)| !known_impls.contains(&&***name));
+    if let Some((name, arg)) = unknown_impl {
+        app.error(
+            ErrorKind::InvalidValue,
+            format!(
+                "unknown impl `{name}` in {arg} (expected one of {})",
+                known_impls.join(", ")
+            ),
+        )
+        .exit();
+    }
+
+    let mut flag_profiles = vec![];
+    for profile in matches.values_of("flag-matrix").into_iter().flatten() {
+        match FlagProfile::parse(profile) {
+            Ok(profile) => flag_profiles.push(profile),
+            Err(e) => app
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!("invalid flag profile `{profile}` in --flag-matrix: {e}"),
+                )
+                .exit(),
+        }
+    }
+
+    if flag_profiles.is_empty() {
+        flag_profiles = vec![FlagProfile::default()];
+    }
+
     let output_format = match matches.value_of("output-format").unwrap() {
         "human" => OutputFormat::Human,
         "json" => OutputFormat::Json,
@@ -162,5 +304,25 @@ pub fn make_app() -> Config {
         run_tests,
         run_pairs,
         rustc_codegen_backends,
+        c_compilers,
+        flag_profiles,
+        cross_profiles,
+        poison,
+        check_extension,
+        xwin_dir,
+    }
+}
+
+/// Split the `path/to/compiler[:flags]` part of --add-c-compiler.
+///
+/// The path can start with a Windows drive letter (`C:\...`), whose colon isn't the separator.
+fn split_compiler_path(spec: &str) -> (&str, Option<&str>) {
+    let drive_len = match spec.as_bytes() {
+        [drive, b':', b'\\' | b'/', ..] if drive.is_ascii_alphabetic() => 2,
+        _ => 0,
+    };
+    match spec[drive_len..].split_once(':') {
+        Some((path, flags)) => (&spec[..drive_len + path.len()], Some(flags)),
+        None => (spec, None),
     }
 }
@@ -39,6 +39,12 @@ pub struct Config {
     pub run_pairs: Vec<(String, String)>,
     pub run_tests: Vec<String>,
     pub rustc_codegen_backends: Vec<(String, String)>,
+    pub c_compilers: Vec<(String, CustomCCompiler)>,
+    pub flag_profiles: Vec<FlagProfile>,
+    pub cross_profiles: bool,
+    pub poison: bool,
+    pub check_extension: bool,
+    pub xwin_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -62,35 +68,43 @@ fn main() -> Result<(), Box<dyn Error>> {
     env::set_var("OUT_DIR", &out_dir);
     env::set_var("HOST", built_info::HOST);
     env::set_var("TARGET", built_info::TARGET);
-    env::set_var("OPT_LEVEL", "0");
 
-    let mut abi_impls: HashMap<&str, Box<dyn AbiImpl + Send + Sync>> = HashMap::new();
-    abi_impls.insert(
-        ABI_IMPL_RUSTC,
-        Box::new(abis::RustcAbiImpl::new(&cfg, None)),
-    );
-    abi_impls.insert(
-        ABI_IMPL_CC,
-        Box::new(abis::CcAbiImpl::new(&cfg, ABI_IMPL_CC)),
-    );
-    abi_impls.insert(
-        ABI_IMPL_GCC,
-        Box::new(abis::CcAbiImpl::new(&cfg, ABI_IMPL_GCC)),
-    );
-    abi_impls.insert(
-        ABI_IMPL_CLANG,
-        Box::new(abis::CcAbiImpl::new(&cfg, ABI_IMPL_CLANG)),
-    );
-    abi_impls.insert(
-        ABI_IMPL_MSVC,
-        Box::new(abis::CcAbiImpl::new(&cfg, ABI_IMPL_MSVC)),
-    );
-
-    for &(ref name, ref path) in &cfg.rustc_codegen_backends {
-        abi_impls.insert(
-            name,
-            Box::new(abis::RustcAbiImpl::new(&cfg, Some(path.to_owned()))),
-        );
+    // Work out which flag profiles the two sides of each pair get built with:
+    // each profile is only paired with itself, unless --cross-profiles is passed.
+    let flag_profiles = &cfg.flag_profiles;
+    let profile_pairs: Vec<(&FlagProfile, &FlagProfile)> = if cfg.cross_profiles {
+        flag_profiles
+            .iter()
+            .flat_map(|caller| flag_profiles.iter().map(move |callee| (caller, callee)))
+            .collect()
+    } else {
+        flag_profiles
+            .iter()
+            .map(|profile| (profile, profile))
+            .collect()
+    };
+    let run_pairs: Vec<(&str, &str)> = cfg
+        .run_pairs
+        .iter()
+        .filter(|(caller_id, callee_id)| {
+            cfg.run_impls.is_empty()
+                || cfg.run_impls.iter().any(|x| x == caller_id)
+                || cfg.run_impls.iter().any(|x| x == callee_id)
+        })
+        .map(|(caller_id, callee_id)| (caller_id.as_str(), callee_id.as_str()))
+        .collect();
+
+    // Only instantiate the impls (with each profile) that those will actually use
+    let mut abi_impls: HashMap<(&str, &FlagProfile), Box<dyn AbiImpl + Send + Sync>> =
+        HashMap::new();
+    for &(caller_id, callee_id) in &run_pairs {
+        for &(caller_profile, callee_profile) in &profile_pairs {
+            for (id, profile) in [(caller_id, caller_profile), (callee_id, callee_profile)] {
+                abi_impls
+                    .entry((id, profile))
+                    .or_insert_with(|| new_abi_impl(&cfg, id, profile));
+            }
+        }
     }
     eprintln!("configured ABIs!");
 
@@ -142,20 +156,24 @@ fn main() -> Result<(), Box<dyn Error>> {
                         // Don't bother with a convention if the test doesn't use it.
                         return Vec::new();
                     }
-                    // Create versions of the test for each "X calls Y" pair we care about.
-                    cfg.run_pairs
+                    // Create versions of the test for each "X calls Y" pair we care about,
+                    // and for each pair of flag profiles the two sides get built with.
+                    run_pairs
                         .iter()
-                        .filter_map(|(caller_id, callee_id)| {
-                            if !cfg.run_impls.is_empty()
-                                && !cfg.run_impls.iter().any(|x| x == caller_id)
-                                && !cfg.run_impls.iter().any(|x| &**x == callee_id)
-                            {
-                                return None;
-                            }
-                            let caller =
-                                &**abi_impls.get(&**caller_id).expect("invalid id for caller!");
-                            let callee =
-                                &**abi_impls.get(&**callee_id).expect("invalid id for callee!");
+                        .flat_map(|&(caller_id, callee_id)| {
+                            profile_pairs
+                                .iter()
+                                .map(move |&(caller_profile, callee_profile)| {
+                                    (caller_id, caller_profile, callee_id, callee_profile)
+                                })
+                        })
+                        .map(|(caller_id, caller_profile, callee_id, callee_profile)| {
+                            let caller = &**abi_impls
+                                .get(&(caller_id, caller_profile))
+                                .expect("invalid id for caller!");
+                            let callee = &**abi_impls
+                                .get(&(callee_id, callee_profile))
+                                .expect("invalid id for callee!");
 
                             let convention_name = convention.name();
 
@@ -165,6 +183,8 @@ fn main() -> Result<(), Box<dyn Error>> {
                                 convention: convention_name.to_owned(),
                                 caller_id: caller_id.to_owned(),
                                 callee_id: callee_id.to_owned(),
+                                caller_profile: caller_profile.clone(),
+                                callee_profile: callee_profile.clone(),
                             };
                             let rules = get_test_rules(&test_key, caller, callee);
                             let results = do_test(
@@ -175,9 +195,11 @@ fn main() -> Result<(), Box<dyn Error>> {
                                 caller,
                                 callee,
                                 &out_dir,
+                                cfg.poison,
+                                cfg.check_extension,
+                                cfg.xwin_dir.as_deref(),
                             );
-                            let report = report_test(test_key, rules, results);
-                            Some(report)
+                            report_test(test_key, rules, results)
                         })
                         .collect()
                 })
@@ -227,7 +249,37 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Instantiate the impl with this name, built with the given flag profile.
+fn new_abi_impl(cfg: &Config, name: &str, profile: &FlagProfile) -> Box<dyn AbiImpl + Send + Sync> {
+    if let Some((_, path)) = cfg.rustc_codegen_backends.iter().find(|(x, _)| x == name) {
+        return Box::new(abis::RustcAbiImpl::new(cfg, profile, Some(path.to_owned())));
+    }
+    if let Some((name, compiler)) = cfg.c_compilers.iter().find(|(x, _)| x == name) {
+        return Box::new(abis::CcAbiImpl::new_custom(cfg, profile, name, compiler));
+    }
+    let c_modes = [
+        ABI_IMPL_CC,
+        ABI_IMPL_GCC,
+        ABI_IMPL_CLANG,
+        ABI_IMPL_MSVC,
+        ABI_IMPL_ZIGCC,
+    ];
+    let cxx_modes = [ABI_IMPL_CXX, ABI_IMPL_GXX, ABI_IMPL_CLANGXX];
+    if let Some(mode) = c_modes.into_iter().find(|&mode| mode == name) {
+        Box::new(abis::CcAbiImpl::new(cfg, profile, mode))
+    } else if let Some(mode) = cxx_modes.into_iter().find(|&mode| mode == name) {
+        Box::new(abis::CxxAbiImpl::new(cfg, profile, mode))
+    } else if name == ABI_IMPL_RUSTC {
+        Box::new(abis::RustcAbiImpl::new(cfg, profile, None))
+    } else if name == ABI_IMPL_ZIG {
+        Box::new(abis::ZigAbiImpl::new(cfg, profile))
+    } else {
+        unreachable!("unknown impl {name}, the cli should have rejected it")
+    }
+}
+
 /// Generate, Compile, Link, Load, and Run this test.
+#[allow(clippy::too_many_arguments)]
 fn do_test(
     test: &Test,
     test_key: &TestKey,
@@ -236,11 +288,18 @@ fn do_test(
     caller: &dyn AbiImpl,
     callee: &dyn AbiImpl,
     _out_dir: &Path,
+    poison: bool,
+    check_extension: bool,
+    xwin_dir: Option<&Path>,
 ) -> TestRunResults {
     use TestRunMode::*;
 
     let mut run_results = TestRunResults::default();
     if test_rules.run <= Skip {
+        if let Some(reason) = &test_rules.skip_reason {
+            eprintln!("skipping {}: {reason}", full_test_name(test_key));
+            run_results.source = Some(Err(GenerateError::Skipped(reason.clone())));
+        }
         return run_results;
     }
 
@@ -250,6 +309,7 @@ fn do_test(
     ));
     let source = match run_results.source.as_ref().unwrap() {
         Ok(v) => v,
+        Err(GenerateError::Skipped(_)) => return run_results,
         Err(e) => {
             eprintln!("Failed to generate source: {}", e);
             return run_results;
@@ -273,7 +333,7 @@ fn do_test(
     }
 
     run_results.ran_to = Link;
-    run_results.link = Some(link_test(test, test_key, build));
+    run_results.link = Some(link_test(test, test_key, caller.target(), build, xwin_dir));
     let link = match run_results.link.as_ref().unwrap() {
         Ok(v) => v,
         Err(e) => {
@@ -299,20 +359,120 @@ fn do_test(
     }
 
     run_results.ran_to = Check;
-    run_results.check = Some(check_test(test, test_key, run));
+    run_results.check = Some(check_test(
+        test,
+        test_key,
+        caller.target(),
+        run,
+        poison,
+        check_extension,
+    ));
 
     run_results
 }
 
+/// Check a narrow input's raw bytes (as reported in `--check-extension` mode) agree
+/// up to the width that whichever side is responsible should have extended it to
+/// (on the target the test was built for).
+fn check_narrow_extension(
+    target: &str,
+    func_idx: usize,
+    input_idx: usize,
+    bits: u32,
+    signed: bool,
+    caller_field: &[u8],
+    callee_field: &[u8],
+) -> Result<(), CheckFailure> {
+    let to_word = |bytes: &[u8]| match bytes.len() {
+        4 => Some(u32::from_ne_bytes(bytes.try_into().unwrap()) as u64),
+        8 => Some(u64::from_ne_bytes(bytes.try_into().unwrap())),
+        _ => None,
+    };
+    let (Some(caller_word), Some(callee_word)) = (to_word(caller_field), to_word(callee_field))
+    else {
+        // Not a pointer-sized int, so the generator messed up somehow
+        return Err(CheckFailure::InputFieldMismatch(
+            func_idx,
+            input_idx,
+            1,
+            caller_field.to_vec(),
+            callee_field.to_vec(),
+        ));
+    };
+    let extender = Extender::for_target(target);
+    let word_bits = caller_field.len() as u32 * 8;
+    let width = match extender {
+        Extender::Caller { bits } => bits.min(word_bits),
+        Extender::Callee => word_bits,
+    };
+    let truncate = |word: u64, bits: u32| {
+        if bits >= 64 {
+            word
+        } else {
+            word & ((1 << bits) - 1)
+        }
+    };
+    if truncate(caller_word, width) == truncate(callee_word, width) {
+        return Ok(());
+    }
+
+    // Work out how far the callee's view of it was actually extended
+    let extended = |width: u32, signed: bool| {
+        let zero_extended = truncate(callee_word, bits);
+        if signed && (zero_extended >> (bits - 1)) & 1 == 1 {
+            truncate(zero_extended | !truncate(u64::MAX, bits), width)
+        } else {
+            zero_extended
+        }
+    };
+    let got = [width, 32, 16]
+        .into_iter()
+        .filter(|&w| w > bits && w <= width)
+        .find_map(|w| {
+            let word = truncate(callee_word, w);
+            if word == extended(w, signed) {
+                Some(if signed {
+                    Extension::Sign(w)
+                } else {
+                    Extension::Zero(w)
+                })
+            } else if word == extended(w, !signed) {
+                Some(if signed {
+                    Extension::Zero(w)
+                } else {
+                    Extension::Sign(w)
+                })
+            } else {
+                None
+            }
+        })
+        .unwrap_or(Extension::Garbage);
+    let expected = if signed {
+        Extension::Sign(width)
+    } else {
+        Extension::Zero(width)
+    };
+    Err(CheckFailure::ExtensionMismatch(
+        func_idx,
+        input_idx,
+        expected,
+        extender.side(),
+        got,
+        caller_field.to_vec(),
+        callee_field.to_vec(),
+    ))
+}
+
 /// Read a test .ron file
 fn read_test_manifest(test_file: &Path) -> Result<Test, GenerateError> {
     let file = File::open(&test_file)?;
     let mut reader = BufReader::new(file);
     let mut input = String::new();
     reader.read_to_string(&mut input)?;
-    let test: Test = ron::from_str(&input).map_err(|e| {
+    let mut test: Test = ron::from_str(&input).map_err(|e| {
         GenerateError::ParseError(test_file.to_string_lossy().into_owned(), input, e)
     })?;
+    test.resolve_types()?;
     Ok(test)
 }
 
@@ -331,17 +491,52 @@ fn generate_test_src(
     let caller_id = &test_key.caller_id;
     let callee_id = &test_key.callee_id;
 
+    let skip = |reason: String| {
+        eprintln!("skipping {full_test_name}: {reason}");
+        Err(GenerateError::Skipped(reason))
+    };
+
+    // Only C++ has references, everyone else just sees the pointers they're passed as
+    let lowered_test = test.without_cxx_refs();
+    let caller_test = if caller.lang() == "c++" {
+        test
+    } else {
+        &lowered_test
+    };
+    let callee_test = if callee.lang() == "c++" {
+        test
+    } else {
+        &lowered_test
+    };
+    if caller.target() != callee.target() {
+        return skip(format!(
+            "{caller_id} builds for {} but {callee_id} builds for {}",
+            caller.target(),
+            callee.target()
+        ));
+    }
     if !caller.supports_convention(convention) {
-        eprintln!(
-            "skipping {full_test_name}: {caller_id} doesn't support convention {convention_name}"
-        );
-        return Err(GenerateError::Skipped);
+        return skip(format!(
+            "{caller_id} doesn't support convention {convention_name}"
+        ));
     }
     if !callee.supports_convention(convention) {
-        eprintln!(
-            "skipping {full_test_name}: {callee_id} doesn't support convention {convention_name}"
-        );
-        return Err(GenerateError::Skipped);
+        return skip(format!(
+            "{callee_id} doesn't support convention {convention_name}"
+        ));
+    }
+    if let Some(ty) = unsupported_val_ty(caller_test, caller) {
+        return skip(format!("{caller_id} doesn't support {ty} values"));
+    }
+    if let Some(ty) = unsupported_val_ty(callee_test, callee) {
+        return skip(format!("{callee_id} doesn't support {ty} values"));
+    }
+    if let Some(func) = callee_test
+        .funcs
+        .iter()
+        .find(|func| func.has_convention(convention) && !callee.supports_callee(func))
+    {
+        return skip(format!("{callee_id} can't define {}", func.name));
     }
 
     let src_dir = if convention == CallingConvention::Handwritten {
@@ -359,8 +554,7 @@ fn generate_test_src(
 
     if convention == CallingConvention::Handwritten {
         if !caller_src.exists() || !callee_src.exists() {
-            eprintln!("skipping {full_test_name}: source for callee and caller doesn't exist");
-            return Err(GenerateError::Skipped);
+            return skip("source for callee and caller doesn't exist".to_owned());
         }
     } else {
         eprintln!("generating {full_test_name}");
@@ -370,10 +564,10 @@ fn generate_test_src(
         std::fs::create_dir_all(caller_src.parent().unwrap())?;
         std::fs::create_dir_all(callee_src.parent().unwrap())?;
         let mut caller_output = File::create(&caller_src)?;
-        caller.generate_caller(&mut caller_output, test, convention)?;
+        caller.generate_caller(&mut caller_output, caller_test, convention)?;
 
         let mut callee_output = File::create(&callee_src)?;
-        callee.generate_callee(&mut callee_output, test, convention)?;
+        callee.generate_callee(&mut callee_output, callee_test, convention)?;
     }
 
     Ok(GenerateOutput {
@@ -382,6 +576,18 @@ fn generate_test_src(
     })
 }
 
+/// The type of the first value (or subvalue) in the test that `abi_impl` can't handle.
+fn unsupported_val_ty(test: &Test, abi_impl: &dyn AbiImpl) -> Option<String> {
+    let mut found = None;
+    test.any_val(|val| {
+        if found.is_none() && !abi_impl.supports_val(val) {
+            found = Some(procgen::arg_ty(val));
+        }
+        false
+    });
+    found
+}
+
 fn build_test(
     _test: &Test,
     test_key: &TestKey,
@@ -396,8 +602,10 @@ fn build_test(
     let callee_id = &test_key.callee_id;
     eprintln!("compiling  {full_test_name}");
 
-    let caller_lib = format!("{test_name}_{convention_name}_{caller_id}_caller");
-    let callee_lib = format!("{test_name}_{convention_name}_{callee_id}_callee");
+    let caller_profile = test_key.caller_profile.id();
+    let callee_profile = test_key.callee_profile.id();
+    let caller_lib = format!("{test_name}_{convention_name}_{caller_id}_{caller_profile}_caller");
+    let callee_lib = format!("{test_name}_{convention_name}_{callee_id}_{callee_profile}_callee");
 
     // Compile the tests (and let them change the lib name).
     let caller_lib = caller.compile_caller(&src.caller_src, &caller_lib)?;
@@ -413,14 +621,27 @@ fn build_test(
 fn link_test(
     _test: &Test,
     test_key: &TestKey,
+    target: &str,
     build: &BuildOutput,
+    xwin_dir: Option<&Path>,
 ) -> Result<LinkOutput, LinkError> {
     let test_name = &test_key.test_name;
     let caller_id = &test_key.caller_id;
     let callee_id = &test_key.callee_id;
     let full_test_name = full_test_name(test_key);
-    let src = PathBuf::from("harness/harness.rs");
-    let output = format!("target/temp/{test_name}_{caller_id}_calls_{callee_id}_harness.dll");
+    // If we can't load it ourselves, it has to be an executable to run under the runner
+    let runner = test_runner(target);
+    let (src, crate_type, ext) = if runner.is_some() {
+        ("harness/runner.rs", "bin", "exe")
+    } else {
+        ("harness/harness.rs", "cdylib", "dll")
+    };
+    let src = PathBuf::from(src);
+    let caller_profile = test_key.caller_profile.id();
+    let callee_profile = test_key.callee_profile.id();
+    let output = format!(
+        "target/temp/{test_name}_{caller_id}_{caller_profile}_calls_{callee_id}_{callee_profile}_harness.{ext}"
+    );
     eprintln!("linking  {full_test_name}");
 
     let mut cmd = Command::new("rustc");
@@ -432,15 +653,28 @@ fn link_test(
         .arg("-l")
         .arg(&build.callee_lib)
         .arg("--crate-type")
-        .arg("cdylib")
+        .arg(crate_type)
         .arg("--target")
-        .arg(built_info::TARGET)
+        .arg(target)
         // .arg("-Csave-temps=y")
         // .arg("--out-dir")
         // .arg("target/temp/")
         .arg("-o")
         .arg(&output)
         .arg(&src);
+    if target != built_info::TARGET {
+        // Only the msvc impl cross-compiles, so this is linking a windows-msvc dll,
+        // which lld can do with the same SDK the objects were built against.
+        // (This needs rustc to have the std for that target installed.)
+        cmd.arg("-C").arg("linker=lld-link");
+        for lib_dir in xwin_dir
+            .map(|dir| abis::c::xwin_lib_dirs(dir, target))
+            .unwrap_or_default()
+        {
+            cmd.arg("-C")
+                .arg(format!("link-arg=/libpath:{}", lib_dir.display()));
+        }
+    }
 
     eprintln!("running: {:?}", cmd);
     let out = cmd.output()?;
@@ -450,10 +684,74 @@ fn link_test(
     } else {
         Ok(LinkOutput {
             test_bin: PathBuf::from(output),
+            runner,
         })
     }
 }
 
+/// The program to run a test built for `target` under, if it's not the host
+/// (so we can't load it ourselves) but there's still a way to run it here.
+///
+/// For now that's just Wine, for the windows-msvc tests the msvc impl cross-compiles.
+pub fn test_runner(target: &str) -> Option<PathBuf> {
+    if target == built_info::TARGET || !target.contains("-windows") {
+        return None;
+    }
+    let path = env::var_os("PATH")?;
+    ["wine64", "wine"].iter().find_map(|runner| {
+        env::split_paths(&path)
+            .map(|dir| dir.join(runner))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// Run a test executable under `runner`, and read back the WriteBuffers it printed
+/// (see harness/runner.rs for the format), in the order they're passed to `test_start`.
+fn run_test_under(runner: &Path, test_bin: &Path) -> Result<Vec<WriteBuffer>, RunError> {
+    let out = Command::new(runner)
+        .arg(test_bin)
+        // Wine is very chatty about everything it's doing
+        .env("WINEDEBUG", "-all")
+        .output()?;
+    if !out.status.success() {
+        return Err(RunError::Runner(out));
+    }
+
+    let stdout = std::str::from_utf8(&out.stdout).map_err(|_| RunError::Runner(out.clone()))?;
+    let mut buffers: Vec<WriteBuffer> = Vec::new();
+    for line in stdout.lines() {
+        let parsed = match line {
+            "buffer" => {
+                buffers.push(WriteBuffer { funcs: vec![] });
+                Some(())
+            }
+            "func" => buffers.last_mut().map(|buf| buf.funcs.push(vec![])),
+            "val" => buffers
+                .last_mut()
+                .and_then(|buf| buf.funcs.last_mut())
+                .map(|func| func.push(vec![])),
+            _ => line.strip_prefix("field ").and_then(|hex| {
+                let field = (0..hex.len())
+                    .step_by(2)
+                    .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+                    .collect::<Option<Vec<u8>>>()?;
+                buffers
+                    .last_mut()
+                    .and_then(|buf| buf.funcs.last_mut())
+                    .and_then(|func| func.last_mut())
+                    .map(|val| val.push(field))
+            }),
+        };
+        if parsed.is_none() || buffers.len() > 6 {
+            return Err(RunError::Runner(out.clone()));
+        }
+    }
+    if buffers.len() != 6 {
+        return Err(RunError::Runner(out.clone()));
+    }
+    Ok(buffers)
+}
+
 /// Tests write back the raw bytes of their values to a WriteBuffer.
 ///
 /// This hierarchical design is confusing as hell, but represents the
@@ -508,6 +806,8 @@ fn run_dynamic_test(
         &mut WriteBuffer,
         &mut WriteBuffer,
         &mut WriteBuffer,
+        &mut WriteBuffer,
+        &mut WriteBuffer,
     ) -> ();
 
     unsafe extern "C" fn write_field(output: &mut WriteBuffer, input: *const u8, size: u32) {
@@ -560,29 +860,51 @@ fn run_dynamic_test(
         let mut caller_outputs = WriteBuffer::new();
         let mut callee_inputs = WriteBuffer::new();
         let mut callee_outputs = WriteBuffer::new();
-
-        // Load the dylib of the test, and get its test_start symbol
-        eprintln!("loading: {}", &test_dylib.test_bin.display());
-        let lib = libloading::Library::new(&test_dylib.test_bin)?;
-        let do_test: libloading::Symbol<TestInit> = lib.get(b"test_start")?;
-        eprintln!("running    {full_test_name}");
-
-        // Actually run the test!
-        do_test(
-            write_field,
-            finished_val,
-            finished_func,
-            &mut caller_inputs,
-            &mut caller_outputs,
-            &mut callee_inputs,
-            &mut callee_outputs,
-        );
+        let mut caller_postcall = WriteBuffer::new();
+        let mut callee_postcall = WriteBuffer::new();
+
+        if let Some(runner) = &test_dylib.runner {
+            // It's built for somewhere else, so it has to run in its own process
+            eprintln!("running    {full_test_name} (under {})", runner.display());
+            let mut buffers = run_test_under(runner, &test_dylib.test_bin)?.into_iter();
+            for buffer in [
+                &mut caller_inputs,
+                &mut caller_outputs,
+                &mut callee_inputs,
+                &mut callee_outputs,
+                &mut caller_postcall,
+                &mut callee_postcall,
+            ] {
+                *buffer = buffers.next().unwrap();
+            }
+        } else {
+            // Load the dylib of the test, and get its test_start symbol
+            eprintln!("loading: {}", &test_dylib.test_bin.display());
+            let lib = libloading::Library::new(&test_dylib.test_bin)?;
+            let do_test: libloading::Symbol<TestInit> = lib.get(b"test_start")?;
+            eprintln!("running    {full_test_name}");
+
+            // Actually run the test!
+            do_test(
+                write_field,
+                finished_val,
+                finished_func,
+                &mut caller_inputs,
+                &mut caller_outputs,
+                &mut callee_inputs,
+                &mut callee_outputs,
+                &mut caller_postcall,
+                &mut callee_postcall,
+            );
+        }
 
         // Finalize the buffers (clear all the pending values).
         caller_inputs.finish_tests();
         caller_outputs.finish_tests();
         callee_inputs.finish_tests();
         callee_outputs.finish_tests();
+        caller_postcall.finish_tests();
+        callee_postcall.finish_tests();
 
         // As a basic sanity-check, make sure everything agrees on how
         // many tests actually executed. If this fails, then something
@@ -592,6 +914,8 @@ fn run_dynamic_test(
             || caller_outputs.funcs.len() != expected_test_count
             || callee_inputs.funcs.len() != expected_test_count
             || callee_outputs.funcs.len() != expected_test_count
+            || caller_postcall.funcs.len() != expected_test_count
+            || callee_postcall.funcs.len() != expected_test_count
         {
             return Err(RunError::TestCountMismatch(
                 expected_test_count,
@@ -599,6 +923,8 @@ fn run_dynamic_test(
                 caller_outputs.funcs.len(),
                 callee_inputs.funcs.len(),
                 callee_outputs.funcs.len(),
+                caller_postcall.funcs.len(),
+                callee_postcall.funcs.len(),
             ));
         }
 
@@ -627,22 +953,113 @@ fn run_dynamic_test(
             val: &Val,
         ) {
             match val {
-                Val::Int(_) | Val::Float(_) | Val::Bool(_) | Val::Ptr(_) => {
+                Val::Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
+                Val::Int(_)
+                | Val::Float(_)
+                | Val::Bool(_)
+                | Val::Ptr(_)
+                | Val::Enum(..)
+                | Val::Bitfield(..)
+                | Val::Vector(_)
+                | Val::Char(_) => {
+                    output.insert(cur_path, format_bytes(input, cur_idx));
+                }
+                // The callback's values are reported separately
+                Val::FnPtr(..) => {}
+                Val::Unit => {}
+                Val::Ref(sub_val)
+                | Val::Transparent(_, sub_val)
+                | Val::Boxed(sub_val)
+                | Val::CxxRef(sub_val)
+                | Val::Base(sub_val)
+                | Val::NoUniqueAddress(sub_val) => {
+                    add_field(input, output, cur_idx, cur_path, sub_val)
+                }
+                Val::Tuple(fields) => {
+                    for (field_idx, field) in fields.iter().enumerate() {
+                        let sub_path = format!("{}.{}", cur_path, field_idx);
+                        add_field(input, output, cur_idx, sub_path, field);
+                    }
+                }
+                Val::Slice(elems) => {
+                    let len_path = format!("{}.len", cur_path);
+                    output.insert(len_path, format_bytes(input, cur_idx));
+                    if let Val::Array(arr) = &**elems {
+                        for (arr_idx, sub_val) in arr.iter().enumerate() {
+                            let sub_path = format!("{}[{}]", cur_path, arr_idx);
+                            add_field(input, output, cur_idx, sub_path, sub_val);
+                        }
+                    }
+                }
+                Val::Str(val) => {
+                    let len_path = format!("{}.len", cur_path);
+                    output.insert(len_path, format_bytes(input, cur_idx));
+                    for byte_idx in 0..val.len() {
+                        let sub_path = format!("{}[{}]", cur_path, byte_idx);
+                        output.insert(sub_path, format_bytes(input, cur_idx));
+                    }
+                }
+                Val::RustOption(sub_val, is_some) => {
+                    let tag_path = format!("{}.is_some", cur_path);
+                    output.insert(tag_path, format_bytes(input, cur_idx));
+                    if *is_some {
+                        add_field(input, output, cur_idx, cur_path, sub_val);
+                    }
+                }
+                // The value after the call is reported separately
+                Val::MutRef(before, _after) => add_field(input, output, cur_idx, cur_path, before),
+                // A non-null ref/callback is reported just like the non-nullable version
+                Val::Nullable(sub_val, true)
+                    if matches!(**sub_val, Val::Ref(_) | Val::FnPtr(..)) =>
+                {
+                    add_field(input, output, cur_idx, cur_path, sub_val)
+                }
+                Val::Nullable(..) => {
                     output.insert(cur_path, format_bytes(input, cur_idx));
                 }
-                Val::Ref(sub_val) => add_field(input, output, cur_idx, cur_path, sub_val),
                 Val::Array(arr) => {
                     for (arr_idx, sub_val) in arr.iter().enumerate() {
                         let sub_path = format!("{}[{}]", cur_path, arr_idx);
                         add_field(input, output, cur_idx, sub_path, sub_val);
                     }
                 }
-                Val::Struct(_struct_name, fields) => {
+                // Zero-length arrays don't have any fields to report
+                Val::EmptyArray(_) => {}
+                Val::Struct(_struct_name, fields)
+                | Val::ReprStruct(_struct_name, _, fields)
+                | Val::Class(_struct_name, _, fields) => {
                     for (field_idx, field) in fields.iter().enumerate() {
                         let sub_path = format!("{}.{}", cur_path, abis::FIELD_NAMES[field_idx]);
                         add_field(input, output, cur_idx, sub_path, field);
                     }
                 }
+                Val::Union(_union_name, variants, active) => {
+                    let sub_path = format!("{}.{}", cur_path, abis::FIELD_NAMES[*active]);
+                    add_field(input, output, cur_idx, sub_path, &variants[*active]);
+                }
+                Val::TaggedUnion(_enum_name, variants, active) => {
+                    let tag_path = format!("{}.{}", cur_path, abis::TAG_NAME);
+                    output.insert(tag_path, format_bytes(input, cur_idx));
+                    let (_discriminant, fields) = &variants[*active];
+                    for (field_idx, field) in fields.iter().enumerate() {
+                        let sub_path = format!(
+                            "{}.{}.{}",
+                            cur_path,
+                            abis::VARIANT_NAMES[*active],
+                            abis::FIELD_NAMES[field_idx]
+                        );
+                        add_field(input, output, cur_idx, sub_path, field);
+                    }
+                }
+            }
+        }
+
+        fn add_input(input: &[Vec<u8>], output: &mut LinkedHashMap<String, String>, val: &Val) {
+            let mut cur_idx = 0;
+            add_field(input, output, &mut cur_idx, String::new(), val);
+            // In --poison/--check-extension mode, the input's raw bytes get reported after its fields
+            if cur_idx < input.len() {
+                output.insert("raw".to_owned(), format_bytes(input, &mut cur_idx));
             }
         }
 
@@ -653,13 +1070,42 @@ fn run_dynamic_test(
         for (func_idx, func) in test.funcs.iter().enumerate() {
             let caller_func = caller.entry(func.name.clone()).or_default();
             let callee_func = callee.entry(func.name.clone()).or_default();
-            for (arg_idx, arg) in func.inputs.iter().enumerate() {
-                let caller_arg = caller_func
-                    .entry(ARG_NAMES[arg_idx].to_owned())
-                    .or_default();
-                let callee_arg = callee_func
-                    .entry(ARG_NAMES[arg_idx].to_owned())
-                    .or_default();
+
+            // The callbacks' inputs come after the function's own (fixed and variadic)
+            // inputs, and their outputs (and mutable refs) come before the function's own.
+            let callbacks = func.callbacks();
+            let mut inputs = func
+                .all_inputs()
+                .enumerate()
+                .map(|(arg_idx, arg)| (ARG_NAMES[arg_idx].to_owned(), arg))
+                .collect::<Vec<_>>();
+            let mut outputs = Vec::new();
+            let mut postcalls = Vec::new();
+            for (callback_idx, callback) in &callbacks {
+                let callback_name = ARG_NAMES[*callback_idx];
+                for (arg_idx, arg) in callback.inputs.iter().enumerate() {
+                    inputs.push((format!("{}.{}", callback_name, ARG_NAMES[arg_idx]), arg));
+                }
+                for (arg_idx, arg) in callback.output.iter().enumerate() {
+                    outputs.push((format!("{}.return{}", callback_name, arg_idx), arg));
+                }
+                for (arg_idx, after) in callback.mut_refs() {
+                    postcalls.push((
+                        format!("{}.{}.after", callback_name, ARG_NAMES[arg_idx]),
+                        after,
+                    ));
+                }
+            }
+            for (arg_idx, arg) in func.output.iter().enumerate() {
+                outputs.push((format!("return{}", arg_idx), arg));
+            }
+            for (arg_idx, after) in func.mut_refs() {
+                postcalls.push((format!("{}.after", ARG_NAMES[arg_idx]), after));
+            }
+
+            for (arg_idx, (arg_name, arg)) in inputs.into_iter().enumerate() {
+                let caller_arg = caller_func.entry(arg_name.clone()).or_default();
+                let callee_arg = callee_func.entry(arg_name).or_default();
 
                 let caller_arg_bytes = caller_inputs
                     .funcs
@@ -674,13 +1120,13 @@ fn run_dynamic_test(
                     .get(arg_idx)
                     .unwrap_or(&empty_arg);
 
-                add_field(caller_arg_bytes, caller_arg, &mut 0, String::new(), arg);
-                add_field(callee_arg_bytes, callee_arg, &mut 0, String::new(), arg);
+                add_input(caller_arg_bytes, caller_arg, arg);
+                add_input(callee_arg_bytes, callee_arg, arg);
             }
 
-            for (arg_idx, arg) in func.output.iter().enumerate() {
-                let caller_arg = caller_func.entry(format!("return{}", arg_idx)).or_default();
-                let callee_arg = callee_func.entry(format!("return{}", arg_idx)).or_default();
+            for (arg_idx, (arg_name, arg)) in outputs.into_iter().enumerate() {
+                let caller_arg = caller_func.entry(arg_name.clone()).or_default();
+                let callee_arg = callee_func.entry(arg_name).or_default();
 
                 let caller_output_bytes = caller_outputs
                     .funcs
@@ -698,6 +1144,27 @@ fn run_dynamic_test(
                 add_field(caller_output_bytes, caller_arg, &mut 0, String::new(), arg);
                 add_field(callee_output_bytes, callee_arg, &mut 0, String::new(), arg);
             }
+
+            for (arg_idx, (arg_name, arg)) in postcalls.into_iter().enumerate() {
+                let caller_arg = caller_func.entry(arg_name.clone()).or_default();
+                let callee_arg = callee_func.entry(arg_name).or_default();
+
+                let caller_after_bytes = caller_postcall
+                    .funcs
+                    .get(func_idx)
+                    .unwrap_or(&empty_func)
+                    .get(arg_idx)
+                    .unwrap_or(&empty_arg);
+                let callee_after_bytes = callee_postcall
+                    .funcs
+                    .get(func_idx)
+                    .unwrap_or(&empty_func)
+                    .get(arg_idx)
+                    .unwrap_or(&empty_arg);
+
+                add_field(caller_after_bytes, caller_arg, &mut 0, String::new(), arg);
+                add_field(callee_after_bytes, callee_arg, &mut 0, String::new(), arg);
+            }
         }
 
         Ok(RunOutput {
@@ -707,6 +1174,8 @@ fn run_dynamic_test(
             caller_outputs,
             callee_inputs,
             callee_outputs,
+            caller_postcall,
+            callee_postcall,
         })
     }
 }
@@ -714,13 +1183,18 @@ fn run_dynamic_test(
 fn check_test(
     test: &Test,
     test_key: &TestKey,
+    target: &str,
     RunOutput {
         caller_inputs,
         caller_outputs,
         callee_inputs,
         callee_outputs,
+        caller_postcall,
+        callee_postcall,
         ..
     }: &RunOutput,
+    poison: bool,
+    check_extension: bool,
 ) -> CheckOutput {
     // Now check the results
 
@@ -732,14 +1206,21 @@ fn check_test(
     // Layer 1 is the funcs/subtests. Because we have already checked
     // that they agree on their lengths, we can zip them together
     // to walk through their views of each subtest's execution.
-    'funcs: for (func_idx, (((caller_inputs, caller_outputs), callee_inputs), callee_outputs)) in
-        caller_inputs
-            .funcs
-            .iter()
-            .zip(&caller_outputs.funcs)
-            .zip(&callee_inputs.funcs)
-            .zip(&callee_outputs.funcs)
-            .enumerate()
+    'funcs: for (
+        func_idx,
+        (
+            ((((caller_inputs, caller_outputs), callee_inputs), callee_outputs), caller_postcall),
+            callee_postcall,
+        ),
+    ) in caller_inputs
+        .funcs
+        .iter()
+        .zip(&caller_outputs.funcs)
+        .zip(&callee_inputs.funcs)
+        .zip(&callee_outputs.funcs)
+        .zip(&caller_postcall.funcs)
+        .zip(&callee_postcall.funcs)
+        .enumerate()
     {
         // Now we must enforce that the caller and callee agree on how
         // many inputs and outputs there were. If this fails that's a
@@ -760,6 +1241,14 @@ fn check_test(
             )));
             continue 'funcs;
         }
+        if caller_postcall.len() != callee_postcall.len() {
+            results.push(Err(CheckFailure::PostcallCountMismatch(
+                func_idx,
+                caller_postcall.clone(),
+                callee_postcall.clone(),
+            )));
+            continue 'funcs;
+        }
 
         // Layer 2 is the values (arguments/returns).
         // The inputs and outputs loop do basically the same work,
@@ -781,11 +1270,48 @@ fn check_test(
                 continue 'funcs;
             }
 
+            // In --check-extension mode, a narrow input's raw bytes (its last field)
+            // only have to agree as far as the platform says it gets extended.
+            let narrow = match test.funcs[func_idx].inputs.get(input_idx) {
+                Some(input) if check_extension => match input.poison(target) {
+                    Some(Poison::HighBits { bits, signed }) => Some((bits, signed)),
+                    _ => None,
+                },
+                _ => None,
+            };
+
+            // In --poison mode, a poisoned aggregate's raw bytes (its last field) are
+            // just there to look at: nothing has to preserve what's in its padding.
+            let padding_is_info = poison
+                && test.funcs[func_idx]
+                    .inputs
+                    .get(input_idx)
+                    .and_then(|input| input.poison(target))
+                    == Some(Poison::Padding);
+
             // Layer 3 is the leaf subfields of the values.
             // At this point we just need to assert that they agree on the bytes.
             for (field_idx, (caller_field, callee_field)) in
                 caller_val.iter().zip(callee_val).enumerate()
             {
+                if padding_is_info && field_idx == caller_val.len() - 1 {
+                    continue;
+                }
+                if let Some((bits, signed)) = narrow.filter(|_| field_idx == caller_val.len() - 1) {
+                    if let Err(e) = check_narrow_extension(
+                        target,
+                        func_idx,
+                        input_idx,
+                        bits,
+                        signed,
+                        caller_field,
+                        callee_field,
+                    ) {
+                        results.push(Err(e));
+                        continue 'funcs;
+                    }
+                    continue;
+                }
                 if caller_field != callee_field {
                     results.push(Err(CheckFailure::InputFieldMismatch(
                         func_idx,
@@ -833,6 +1359,36 @@ fn check_test(
             }
         }
 
+        // Process what the mutable refs point to after the call
+        for (postcall_idx, (caller_val, callee_val)) in
+            caller_postcall.iter().zip(callee_postcall).enumerate()
+        {
+            if caller_val.len() != callee_val.len() {
+                results.push(Err(CheckFailure::PostcallFieldCountMismatch(
+                    func_idx,
+                    postcall_idx,
+                    caller_val.clone(),
+                    callee_val.clone(),
+                )));
+                continue 'funcs;
+            }
+
+            for (field_idx, (caller_field, callee_field)) in
+                caller_val.iter().zip(callee_val).enumerate()
+            {
+                if caller_field != callee_field {
+                    results.push(Err(CheckFailure::PostcallFieldMismatch(
+                        func_idx,
+                        postcall_idx,
+                        field_idx,
+                        caller_field.clone(),
+                        callee_field.clone(),
+                    )));
+                    continue 'funcs;
+                }
+            }
+        }
+
         // If we got this far then the test passes
         results.push(Ok(()));
     }
@@ -884,7 +1440,7 @@ fn report_test(key: TestKey, rules: TestRules, results: TestRunResults) -> TestR
     let conclusion = if rules.run == Skip {
         // If we were told to skip, we skipped
         Skipped
-    } else if let Some(Err(GenerateError::Skipped)) = results.source {
+    } else if let Some(Err(GenerateError::Skipped(_))) = results.source {
         // The generate step is allowed to unilaterally skip things
         // to avoid different configs having to explicitly disable
         // a million unsupported combinations
@@ -942,20 +1498,14 @@ fn full_test_name(
         convention,
         caller_id,
         callee_id,
+        caller_profile,
+        callee_profile,
     }: &TestKey,
 ) -> String {
-    format!("{test_name}::{convention}::{caller_id}_calls_{callee_id}")
+    format!("{test_name}::{convention}::{caller_id}[{caller_profile}]_calls_{callee_id}[{callee_profile}]")
 }
 
 /// The name of a subtest for pretty-printing.
-fn full_subtest_name(
-    TestKey {
-        test_name,
-        convention,
-        caller_id,
-        callee_id,
-    }: &TestKey,
-    func_name: &str,
-) -> String {
-    format!("{test_name}::{convention}::{caller_id}_calls_{callee_id}::{func_name}")
+fn full_subtest_name(test_key: &TestKey, func_name: &str) -> String {
+    format!("{}::{func_name}", full_test_name(test_key))
 }
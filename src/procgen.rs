@@ -40,6 +40,42 @@ pub fn procgen_tests(regenerate: bool) {
         ("bool", &[Val::Bool(true)]),
         ("f64", &[Val::Float(FloatVal::c_double(809239021.392))]),
         ("f32", &[Val::Float(FloatVal::c_float(-4921.3527))]),
+        // Vectors have their own special passing rules (and target feature requirements!)
+        // in every convention, so check them all out too.
+        (
+            "simd",
+            &[
+                Val::Vector(VectorVal::c__m128([809239021.392, -4921.3527, 0.5, 12.01])),
+                Val::Vector(VectorVal::c__m128d([809239021.392, -4921.3527])),
+                Val::Vector(VectorVal::c__m128i([
+                    0x1a2b_3c4d,
+                    0x23ea_f142,
+                    0x7a32_0c01,
+                    0xe012_0a82,
+                ])),
+                Val::Vector(VectorVal::c__m256([
+                    809239021.392,
+                    -4921.3527,
+                    0.5,
+                    12.01,
+                    1234.23,
+                    -0.23,
+                    81344789.12,
+                    3.245,
+                ])),
+                Val::Vector(VectorVal::c__m256d([809239021.392, -4921.3527, 0.5, 12.01])),
+                Val::Vector(VectorVal::c__m256i([
+                    0x1a2b_3c4d,
+                    0x23ea_f142,
+                    0x7a32_0c01,
+                    0xe012_0a82,
+                    0xe13e_362f,
+                    0xaf34_5e1a,
+                    0xb230_e178,
+                    0xa2e4_16ef,
+                ])),
+            ],
+        ),
         // These are split out because they are the buggy mess that inspired this whole enterprise!
         // These types are a GCC exenstion. Windows is a huge dumpster fire where no one agrees on
         // it (MSVC doesn't even define __(u)int128_t afaict, but has some equivalent extension).
@@ -64,6 +100,7 @@ pub fn procgen_tests(regenerate: bool) {
     for (test_name, vals) in tests {
         let mut test = Test {
             name: test_name.to_string(),
+            types: Vec::new(),
             funcs: Vec::new(),
         };
 
@@ -82,7 +119,22 @@ pub fn procgen_tests(regenerate: bool) {
                             continue;
                         }
                         Val::Struct(_, _) => unimplemented!(),
-                        Val::Array(_) => unimplemented!(),
+                        Val::ReprStruct(_, _, _) => unimplemented!(),
+                        Val::Bitfield(_, _) => unimplemented!(),
+                        Val::Union(_, _, _) => unimplemented!(),
+                        Val::Enum(_, _, _) => unimplemented!(),
+                        Val::TaggedUnion(_, _, _) => unimplemented!(),
+                        Val::FnPtr(_, _, _) => unimplemented!(),
+                        Val::Array(_) | Val::EmptyArray(_) => unimplemented!(),
+                        Val::Named(_, _) => unimplemented!(),
+                        Val::Transparent(_, _) => unimplemented!(),
+                        Val::Nullable(_, _) => unimplemented!(),
+                        Val::MutRef(_, _) => unimplemented!(),
+                        Val::Char(_) | Val::Unit | Val::Str(_) => unimplemented!(),
+                        Val::Tuple(_) | Val::Slice(_) | Val::Boxed(_) => unimplemented!(),
+                        Val::RustOption(_, _) => unimplemented!(),
+                        Val::CxxRef(_) | Val::Class(..) => unimplemented!(),
+                        Val::Base(_) | Val::NoUniqueAddress(_) => unimplemented!(),
                         Val::Ptr(out) => graffiti_primitive(out, i),
                         Val::Int(int_val) => match int_val {
                             IntVal::c__int128(out) => graffiti_primitive(out, i),
@@ -95,12 +147,47 @@ pub fn procgen_tests(regenerate: bool) {
                             IntVal::c_uint32_t(out) => graffiti_primitive(out, i),
                             IntVal::c_uint16_t(out) => graffiti_primitive(out, i),
                             IntVal::c_uint8_t(out) => graffiti_primitive(out, i),
+                            IntVal::c_char(out) => graffiti_primitive(out, i),
+                            IntVal::c_schar(out) => graffiti_primitive(out, i),
+                            IntVal::c_uchar(out) => graffiti_primitive(out, i),
+                            IntVal::c_short(out) => graffiti_primitive(out, i),
+                            IntVal::c_ushort(out) => graffiti_primitive(out, i),
+                            IntVal::c_int(out) => graffiti_primitive(out, i),
+                            IntVal::c_uint(out) => graffiti_primitive(out, i),
+                            IntVal::c_long(out) => graffiti_primitive(out, i),
+                            IntVal::c_ulong(out) => graffiti_primitive(out, i),
+                            IntVal::c_longlong(out) => graffiti_primitive(out, i),
+                            IntVal::c_ulonglong(out) => graffiti_primitive(out, i),
+                            IntVal::c_size_t(out) => graffiti_primitive(out, i),
+                            IntVal::c_ptrdiff_t(out) => graffiti_primitive(out, i),
+                            IntVal::c_intptr_t(out) => graffiti_primitive(out, i),
+                            IntVal::c_uintptr_t(out) => graffiti_primitive(out, i),
+                            IntVal::c_wchar_t(out) => graffiti_primitive(out, i),
                         },
                         Val::Float(float_val) => match float_val {
                             FloatVal::c_double(out) => graffiti_primitive(out, i),
                             FloatVal::c_float(out) => graffiti_primitive(out, i),
+                            FloatVal::c__Float16(out) => graffiti_primitive(out, i),
+                            FloatVal::c__float128(out) => graffiti_primitive(out, i),
+                            FloatVal::c_long_double(out) => graffiti_primitive(out, i),
+                            FloatVal::c__Complex_float(re, im) => {
+                                graffiti_primitive(re, i);
+                                graffiti_primitive(im, i);
+                            }
+                            FloatVal::c__Complex_double(re, im) => {
+                                graffiti_primitive(re, i);
+                                graffiti_primitive(im, i);
+                            }
                         },
                         Val::Bool(out) => *out = true,
+                        Val::Vector(vector_val) => match vector_val {
+                            VectorVal::c__m128(lanes) => graffiti_lanes(lanes, i),
+                            VectorVal::c__m128d(lanes) => graffiti_lanes(lanes, i),
+                            VectorVal::c__m128i(lanes) => graffiti_lanes(lanes, i),
+                            VectorVal::c__m256(lanes) => graffiti_lanes(lanes, i),
+                            VectorVal::c__m256d(lanes) => graffiti_lanes(lanes, i),
+                            VectorVal::c__m256i(lanes) => graffiti_lanes(lanes, i),
+                        },
                     }
                 }
 
@@ -114,21 +201,24 @@ pub fn procgen_tests(regenerate: bool) {
                 name: format!("{val_name}_val_in"),
                 conventions: vec![CallingConvention::All],
                 inputs: vec![new_val(0)],
-                output: None,
+                variadic_inputs: vec![],
+                output: vec![],
             });
 
             test.funcs.push(Func {
                 name: format!("{val_name}_val_out"),
                 conventions: vec![CallingConvention::All],
                 inputs: vec![],
-                output: Some(new_val(0)),
+                variadic_inputs: vec![],
+                output: vec![new_val(0)],
             });
 
             test.funcs.push(Func {
                 name: format!("{val_name}_val_in_out"),
                 conventions: vec![CallingConvention::All],
                 inputs: vec![new_val(0)],
-                output: Some(new_val(1)),
+                variadic_inputs: vec![],
+                output: vec![new_val(1)],
             });
 
             // Start gentle with basic one value in/out tests
@@ -136,21 +226,24 @@ pub fn procgen_tests(regenerate: bool) {
                 name: format!("{val_name}_ref_in"),
                 conventions: vec![CallingConvention::All],
                 inputs: vec![Val::Ref(Box::new(new_val(0)))],
-                output: None,
+                variadic_inputs: vec![],
+                output: vec![],
             });
 
             test.funcs.push(Func {
                 name: format!("{val_name}_ref_out"),
                 conventions: vec![CallingConvention::All],
                 inputs: vec![],
-                output: Some(Val::Ref(Box::new(new_val(0)))),
+                variadic_inputs: vec![],
+                output: vec![Val::Ref(Box::new(new_val(0)))],
             });
 
             test.funcs.push(Func {
                 name: format!("{val_name}_ref_in_out"),
                 conventions: vec![CallingConvention::All],
                 inputs: vec![Val::Ref(Box::new(new_val(0)))],
-                output: Some(Val::Ref(Box::new(new_val(1)))),
+                variadic_inputs: vec![],
+                output: vec![Val::Ref(Box::new(new_val(1)))],
             });
 
             // Stress out the calling convention and try lots of different
@@ -161,7 +254,8 @@ pub fn procgen_tests(regenerate: bool) {
                     name: format!("{val_name}_val_in_{len}"),
                     conventions: vec![CallingConvention::All],
                     inputs: (0..len).map(new_val).collect(),
-                    output: None,
+                    variadic_inputs: vec![],
+                    output: vec![],
                 });
             }
 
@@ -176,7 +270,8 @@ pub fn procgen_tests(regenerate: bool) {
                         format!("{val_name}_{len}"),
                         (0..len).map(new_val).collect(),
                     )],
-                    output: None,
+                    variadic_inputs: vec![],
+                    output: vec![],
                 });
             }
             // Check that by-ref works, for good measure
@@ -188,7 +283,8 @@ pub fn procgen_tests(regenerate: bool) {
                         format!("{val_name}_{len}"),
                         (0..len).map(new_val).collect(),
                     )))],
-                    output: None,
+                    variadic_inputs: vec![],
+                    output: vec![],
                 });
             }
 
@@ -217,7 +313,8 @@ pub fn procgen_tests(regenerate: bool) {
                     name: format!("{val_name}_val_in_{idx}_perturbed_small"),
                     conventions: vec![CallingConvention::All],
                     inputs,
-                    output: None,
+                    variadic_inputs: vec![],
+                    output: vec![],
                 });
             }
             for idx in 0..big_count {
@@ -234,7 +331,8 @@ pub fn procgen_tests(regenerate: bool) {
                     name: format!("{val_name}_val_in_{idx}_perturbed_big"),
                     conventions: vec![CallingConvention::All],
                     inputs,
-                    output: None,
+                    variadic_inputs: vec![],
+                    output: vec![],
                 });
             }
 
@@ -255,7 +353,8 @@ pub fn procgen_tests(regenerate: bool) {
                         format!("{val_name}_{idx}_perturbed_small"),
                         inputs,
                     )],
-                    output: None,
+                    variadic_inputs: vec![],
+                    output: vec![],
                 });
             }
             for idx in 0..big_count {
@@ -275,7 +374,8 @@ pub fn procgen_tests(regenerate: bool) {
                         format!("{val_name}_{idx}_perturbed_big"),
                         inputs,
                     )],
-                    output: None,
+                    variadic_inputs: vec![],
+                    output: vec![],
                 });
             }
 
@@ -297,7 +397,8 @@ pub fn procgen_tests(regenerate: bool) {
                         format!("{val_name}_{idx}_perturbed_small"),
                         inputs,
                     )))],
-                    output: None,
+                    variadic_inputs: vec![],
+                    output: vec![],
                 });
             }
             for idx in 0..big_count {
@@ -317,7 +418,8 @@ pub fn procgen_tests(regenerate: bool) {
                         format!("{val_name}_{idx}_perturbed_big"),
                         inputs,
                     )))],
-                    output: None,
+                    variadic_inputs: vec![],
+                    output: vec![],
                 });
             }
         }
@@ -326,6 +428,103 @@ pub fn procgen_tests(regenerate: bool) {
         let output = ron::to_string(&test).unwrap();
         file.write_all(output.as_bytes()).unwrap();
     }
+
+    // Returning aggregates is where conventions disagree the most (registers vs
+    // register pairs vs sret), so return a struct of every size up to 64 bytes.
+    let mut test = Test {
+        name: "struct_returns".to_string(),
+        types: Vec::new(),
+        funcs: Vec::new(),
+    };
+    for size in 1..=64 {
+        test.funcs.push(Func {
+            name: format!("struct_{size}_ret"),
+            conventions: vec![CallingConvention::All],
+            inputs: vec![],
+            variadic_inputs: vec![],
+            output: vec![returned_struct(size, 0)],
+        });
+        // And once more with an extra output, so the return has to share
+        // registers (or the sret pointer's spot) with an out-param
+        test.funcs.push(Func {
+            name: format!("struct_{size}_ret_and_out"),
+            conventions: vec![CallingConvention::All],
+            inputs: vec![Val::Int(IntVal::c_uint32_t(0x1a2b3c4d))],
+            variadic_inputs: vec![],
+            output: vec![returned_struct(size, 0), returned_struct(size, 7)],
+        });
+    }
+    let mut file = std::fs::File::create(proc_gen_root.join("struct_returns.ron")).unwrap();
+    let output = ron::to_string(&test).unwrap();
+    file.write_all(output.as_bytes()).unwrap();
+}
+
+/// A struct that's exactly `size` bytes, with a mix of int and float fields
+/// (`seed` shifts around the values they're filled with).
+///
+/// The struct is as aligned as it can be without needing tail padding,
+/// and its fields cycle through the ways that alignment's worth of bytes
+/// can be split up, so every size gets a different int/float classification.
+/// Bytes are grouped into arrays when the alignment is small, to keep the
+/// field count manageable.
+fn returned_struct(size: usize, seed: usize) -> Val {
+    let u8_arr = |len| Val::Array(vec![Val::Int(IntVal::c_uint8_t(0)); len]);
+    let align = 1 << size.trailing_zeros().min(3);
+    let mut fields = Vec::new();
+    let mut offset = 0;
+    while offset < size {
+        let slot: Vec<Val> = match (align, fields.len() % 4) {
+            (8, 0) => vec![Val::Float(FloatVal::c_double(0.0))],
+            (8, 1) => vec![
+                Val::Int(IntVal::c_uint32_t(0)),
+                Val::Float(FloatVal::c_float(0.0)),
+            ],
+            (8, 2) => vec![Val::Int(IntVal::c_uint64_t(0))],
+            (8, _) => vec![
+                Val::Float(FloatVal::c_float(0.0)),
+                Val::Float(FloatVal::c_float(0.0)),
+            ],
+            (4, 0 | 2) => vec![Val::Float(FloatVal::c_float(0.0))],
+            (4, _) => vec![Val::Int(IntVal::c_uint32_t(0))],
+            (2, 0 | 2) => vec![Val::Int(IntVal::c_uint16_t(0))],
+            (2, _) => vec![u8_arr(2)],
+            (_, 1 | 3) if size - offset >= 4 => vec![u8_arr(4)],
+            _ => vec![Val::Int(IntVal::c_uint8_t(0))],
+        };
+        for mut field in slot {
+            offset += graffiti_field(&mut field, fields.len() + seed);
+            fields.push(field);
+        }
+    }
+    Val::Struct(format!("ret_{size}"), fields)
+}
+
+/// Fill in a field of [`returned_struct`][], returning its size.
+fn graffiti_field(field: &mut Val, idx: usize) -> usize {
+    // Skip 0, all zeroes is too easy to get right by accident
+    let idx = idx % 15 + 1;
+    match field {
+        Val::Int(IntVal::c_uint64_t(out)) => graffiti_primitive(out, idx),
+        Val::Int(IntVal::c_uint32_t(out)) => graffiti_primitive(out, idx),
+        Val::Int(IntVal::c_uint16_t(out)) => graffiti_primitive(out, idx),
+        Val::Int(IntVal::c_uint8_t(out)) => graffiti_primitive(out, idx),
+        Val::Float(FloatVal::c_double(out)) => graffiti_primitive(out, idx),
+        Val::Float(FloatVal::c_float(out)) => graffiti_primitive(out, idx),
+        Val::Array(elems) => {
+            return elems
+                .iter_mut()
+                .enumerate()
+                .map(|(elem_idx, elem)| graffiti_field(elem, idx + elem_idx))
+                .sum();
+        }
+        _ => unreachable!(),
+    }
+    match field {
+        Val::Int(IntVal::c_uint64_t(_)) | Val::Float(FloatVal::c_double(_)) => 8,
+        Val::Int(IntVal::c_uint32_t(_)) | Val::Float(FloatVal::c_float(_)) => 4,
+        Val::Int(IntVal::c_uint16_t(_)) => 2,
+        _ => 1,
+    }
 }
 
 /// The type name to use for this value when it is stored in args/vars.
@@ -333,7 +532,9 @@ pub fn arg_ty(val: &Val) -> String {
     use IntVal::*;
     use Val::*;
     match val {
+        Named(..) => unreachable!("Val::Named is sugar and shouldn't reach here!"),
         Ref(x) => format!("ref_{}", arg_ty(x)),
+        MutRef(x, _) => format!("mut_ref_{}", arg_ty(x)),
         Ptr(_) => "ptr".to_string(),
         Bool(_) => "bool".to_string(),
         Array(vals) => format!(
@@ -341,9 +542,44 @@ pub fn arg_ty(val: &Val) -> String {
             vals.len(),
             arg_ty(vals.get(0).expect("arrays must have length > 0")),
         ),
-        Struct(name, _) => format!("struct_{name}"),
+        EmptyArray(elem) => format!("arr_0_{}", arg_ty(elem)),
+        Struct(name, _) | ReprStruct(name, _, _) => format!("struct_{name}"),
+        Transparent(name, _) => format!("transparent_{name}"),
+        Nullable(inner, _) => format!("option_{}", arg_ty(inner)),
+        Union(name, _, _) => format!("union_{name}"),
+        Enum(name, _, _) => format!("enum_{name}"),
+        TaggedUnion(name, _, _) => format!("tagged_{name}"),
+        FnPtr(name, _, _) => format!("fn_{name}"),
+        Char(_) => "char".to_string(),
+        Unit => "unit".to_string(),
+        Tuple(fields) => format!("tuple_{}", fields.len()),
+        Slice(elems) => match elems.array_elem() {
+            Some((_len, elem)) => format!("slice_{}", arg_ty(elem)),
+            None => "slice".to_string(),
+        },
+        Str(_) => "str".to_string(),
+        Boxed(x) => format!("box_{}", arg_ty(x)),
+        RustOption(inner, _) => format!("rust_option_{}", arg_ty(inner)),
+        CxxRef(x) => format!("cxx_ref_{}", arg_ty(x)),
+        Class(name, _, _) => format!("class_{name}"),
+        Base(x) => format!("base_{}", arg_ty(x)),
+        NoUniqueAddress(x) => format!("no_unique_address_{}", arg_ty(x)),
+        Bitfield(int_val, width) => format!("{}_bitfield_{width}", arg_ty(&Int(int_val.clone()))),
+        Vector(vector_val) => match vector_val {
+            VectorVal::c__m128(_) => "m128".to_string(),
+            VectorVal::c__m128d(_) => "m128d".to_string(),
+            VectorVal::c__m128i(_) => "m128i".to_string(),
+            VectorVal::c__m256(_) => "m256".to_string(),
+            VectorVal::c__m256d(_) => "m256d".to_string(),
+            VectorVal::c__m256i(_) => "m256i".to_string(),
+        },
         Float(FloatVal::c_double(_)) => "f64".to_string(),
         Float(FloatVal::c_float(_)) => "f32".to_string(),
+        Float(FloatVal::c__Float16(_)) => "f16".to_string(),
+        Float(FloatVal::c__float128(_)) => "f128".to_string(),
+        Float(FloatVal::c_long_double(_)) => "long_double".to_string(),
+        Float(FloatVal::c__Complex_float(..)) => "complex_f32".to_string(),
+        Float(FloatVal::c__Complex_double(..)) => "complex_f64".to_string(),
         Int(int_val) => match int_val {
             c__int128(_) => "i128".to_string(),
             c_int64_t(_) => "i64".to_string(),
@@ -355,10 +591,32 @@ pub fn arg_ty(val: &Val) -> String {
             c_uint32_t(_) => "u32".to_string(),
             c_uint16_t(_) => "u16".to_string(),
             c_uint8_t(_) => "u8".to_string(),
+            c_char(_) => "char".to_string(),
+            c_schar(_) => "schar".to_string(),
+            c_uchar(_) => "uchar".to_string(),
+            c_short(_) => "short".to_string(),
+            c_ushort(_) => "ushort".to_string(),
+            c_int(_) => "int".to_string(),
+            c_uint(_) => "uint".to_string(),
+            c_long(_) => "long".to_string(),
+            c_ulong(_) => "ulong".to_string(),
+            c_longlong(_) => "longlong".to_string(),
+            c_ulonglong(_) => "ulonglong".to_string(),
+            c_size_t(_) => "size_t".to_string(),
+            c_ptrdiff_t(_) => "ptrdiff_t".to_string(),
+            c_intptr_t(_) => "intptr_t".to_string(),
+            c_uintptr_t(_) => "uintptr_t".to_string(),
+            c_wchar_t(_) => "wchar_t".to_string(),
         },
     }
 }
 
+fn graffiti_lanes<T>(output: &mut [T], idx: usize) {
+    for lane in output {
+        graffiti_primitive(lane, idx);
+    }
+}
+
 fn graffiti_primitive<T>(output: &mut T, idx: usize) {
     let mut input = [
         0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E,
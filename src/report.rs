@@ -15,18 +15,22 @@ pub fn get_test_rules(test: &TestKey, caller: &dyn AbiImpl, callee: &dyn AbiImpl
     let mut result = TestRules {
         run: Check,
         check: Pass(Check),
+        skip_reason: None,
     };
 
     // Now apply specific custom expectations for platforms/suites
-    let is_c = caller.lang() == "c" || callee.lang() == "c";
+    // (the C++ impls are C compilers too)
+    let is_c = matches!(caller.lang(), "c" | "c++") || matches!(callee.lang(), "c" | "c++");
     let is_rust = caller.lang() == "rust" || callee.lang() == "rust";
     let is_rust_and_c = is_c && is_rust;
+    // Native Zig is built on llvm just like rustc, so it gets the same u128 treatment
+    let is_zig = caller.lang() == "zig" || callee.lang() == "zig";
 
     // llvm and gcc disagree on the u128 ABI everywhere but aarch64 (arm64) and s390x.
     // This is Bad! Ideally we should check for all clang<->gcc pairs but to start
     // let's mark rust <-> C as disagreeing (because rust also disagrees with clang).
     if !cfg!(any(target_arch = "aarch64", target_arch = "s390x"))
-        && test.test_name == "ui128" && is_rust_and_c {
+        && test.test_name == "ui128" && is_c && (is_rust || is_zig) {
         result.check = Busted(Check);
     }
 
@@ -44,6 +48,41 @@ pub fn get_test_rules(test: &TestKey, caller: &dyn AbiImpl, callee: &dyn AbiImpl
         result.check = Random;
     }
 
+    // Rust emulates _Complex with a two-field struct, but 32-bit x86 SysV returns
+    // `_Complex float` in eax:edx, while structs always go through a hidden out-pointer.
+    if cfg!(all(target_arch = "x86", not(windows))) && test.test_name == "complex" && is_rust_and_c
+    {
+        result.check = Busted(Check);
+    }
+
+    if test.test_name == "simd" {
+        // We only know how to spell the x86 vector types right now.
+        if !cfg!(any(target_arch = "x86", target_arch = "x86_64")) {
+            result.run = Skip;
+            result.skip_reason = Some("no x86 vector types on this arch".to_owned());
+        }
+        // rustc refuses to put vectors in an extern block without #![feature(simd_ffi)]
+        if caller.lang() == "rust" && !crate::built_info::RUSTC_VERSION.contains("nightly") {
+            result.run = Skip;
+            result.skip_reason = Some("needs nightly rustc for simd_ffi".to_owned());
+        }
+        // We can build the AVX tests anywhere, but running them would just crash.
+        if !host_has_avx() && result.run > Link {
+            result.run = Link;
+            result.check = Pass(Link);
+        }
+    }
+
+    // Cross-compiled tests can be built and linked, but they can only run if there's
+    // something here to run them under (like Wine for windows-msvc).
+    if caller.target() != crate::built_info::TARGET
+        && crate::test_runner(caller.target()).is_none()
+        && result.run > Link
+    {
+        result.run = Link;
+        result.check = Pass(Link);
+    }
+
     //
     //
     // THIS AREA RESERVED FOR VENDORS TO APPLY PATCHES
@@ -55,6 +94,17 @@ pub fn get_test_rules(test: &TestKey, caller: &dyn AbiImpl, callee: &dyn AbiImpl
     result
 }
 
+fn host_has_avx() -> bool {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        std::is_x86_feature_detected!("avx")
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    {
+        false
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum BuildError {
     #[error("io error\n{0}")]
@@ -65,6 +115,22 @@ pub enum BuildError {
     RustCompile(std::process::Output),
     #[error("c compile errror\n{0}")]
     CCompile(#[from] cc::Error),
+    #[error("gcc/clang compile error \n{} \n{}",
+        std::str::from_utf8(&.0.stdout).unwrap(),
+        std::str::from_utf8(&.0.stderr).unwrap())]
+    GnuCompile(std::process::Output),
+    #[error("c++ compile error \n{} \n{}",
+        std::str::from_utf8(&.0.stdout).unwrap(),
+        std::str::from_utf8(&.0.stderr).unwrap())]
+    CxxCompile(std::process::Output),
+    #[error("msvc compile error \n{} \n{}",
+        std::str::from_utf8(&.0.stdout).unwrap(),
+        std::str::from_utf8(&.0.stderr).unwrap())]
+    MsvcCompile(std::process::Output),
+    #[error("zig compile error \n{} \n{}",
+        std::str::from_utf8(&.0.stdout).unwrap(),
+        std::str::from_utf8(&.0.stderr).unwrap())]
+    ZigCompile(std::process::Output),
 }
 
 #[allow(clippy::enum_variant_names)]
@@ -73,14 +139,14 @@ pub enum CheckFailure {
     #[error("test {0} {} field {2} mismatch \ncaller: {3:02X?} \ncallee: {4:02X?}", ARG_NAMES[*.1])]
     InputFieldMismatch(usize, usize, usize, Vec<u8>, Vec<u8>),
     #[error(
-        "test {0} {} field {2} mismatch \ncaller: {3:02X?} \ncallee: {4:02X?}",
+        "test {0} {} {1} field {2} mismatch \ncaller: {3:02X?} \ncallee: {4:02X?}",
         OUTPUT_NAME
     )]
     OutputFieldMismatch(usize, usize, usize, Vec<u8>, Vec<u8>),
     #[error("test {0} {} field count mismatch \ncaller: {2:#02X?} \ncallee: {3:#02X?}", ARG_NAMES[*.1])]
     InputFieldCountMismatch(usize, usize, Vec<Vec<u8>>, Vec<Vec<u8>>),
     #[error(
-        "test {0} {} field count mismatch \ncaller: {2:#02X?} \ncallee: {3:#02X?}",
+        "test {0} {} {1} field count mismatch \ncaller: {2:#02X?} \ncallee: {3:#02X?}",
         OUTPUT_NAME
     )]
     OutputFieldCountMismatch(usize, usize, Vec<Vec<u8>>, Vec<Vec<u8>>),
@@ -88,6 +154,40 @@ pub enum CheckFailure {
     InputCountMismatch(usize, Vec<Vec<Vec<u8>>>, Vec<Vec<Vec<u8>>>),
     #[error("test {0} output count mismatch \ncaller: {1:#02X?} \ncallee: {2:#02X?}")]
     OutputCountMismatch(usize, Vec<Vec<Vec<u8>>>, Vec<Vec<Vec<u8>>>),
+    #[error("test {0} mutable ref {1} field {2} mismatch after the call \ncaller: {3:02X?} \ncallee: {4:02X?}")]
+    PostcallFieldMismatch(usize, usize, usize, Vec<u8>, Vec<u8>),
+    #[error("test {0} mutable ref {1} field count mismatch after the call \ncaller: {2:#02X?} \ncallee: {3:#02X?}")]
+    PostcallFieldCountMismatch(usize, usize, Vec<Vec<u8>>, Vec<Vec<u8>>),
+    #[error("test {0} mutable ref count mismatch after the call \ncaller: {1:#02X?} \ncallee: {2:#02X?}")]
+    PostcallCountMismatch(usize, Vec<Vec<Vec<u8>>>, Vec<Vec<Vec<u8>>>),
+    #[error("test {0} {} should have been {2} by the {3}, but was {4} \ncaller: {5:02X?} \ncallee: {6:02X?}", ARG_NAMES[*.1])]
+    ExtensionMismatch(
+        usize,
+        usize,
+        Extension,
+        &'static str,
+        Extension,
+        Vec<u8>,
+        Vec<u8>,
+    ),
+}
+
+/// How far a narrow arg was extended, in `--check-extension` mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Extension {
+    Sign(u32),
+    Zero(u32),
+    Garbage,
+}
+
+impl std::fmt::Display for Extension {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Extension::Sign(bits) => write!(f, "sign-extended to {bits} bits"),
+            Extension::Zero(bits) => write!(f, "zero-extended to {bits} bits"),
+            Extension::Garbage => write!(f, "not extended at all"),
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -104,8 +204,14 @@ pub enum LinkError {
 pub enum RunError {
     #[error("test loading error (dynamic linking failed)\n{0}")]
     LoadError(#[from] libloading::Error),
-    #[error("wrong number of tests reported! \nExpected {0} \nGot (caller_in: {1}, caller_out: {2}, callee_in: {3}, callee_out: {4})")]
-    TestCountMismatch(usize, usize, usize, usize, usize),
+    #[error("wrong number of tests reported! \nExpected {0} \nGot (caller_in: {1}, caller_out: {2}, callee_in: {3}, callee_out: {4}, caller_postcall: {5}, callee_postcall: {6})")]
+    TestCountMismatch(usize, usize, usize, usize, usize, usize, usize),
+    #[error("io error\n{0}")]
+    Io(#[from] std::io::Error),
+    #[error("test runner failed \n{} \n{}",
+        String::from_utf8_lossy(&.0.stdout),
+        String::from_utf8_lossy(&.0.stderr))]
+    Runner(std::process::Output),
 }
 
 impl Serialize for BuildError {
@@ -186,12 +292,17 @@ pub struct TestKey {
     pub convention: String,
     pub caller_id: String,
     pub callee_id: String,
+    /// The flag profile each side was built with (see `--flag-matrix`).
+    pub caller_profile: FlagProfile,
+    pub callee_profile: FlagProfile,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct TestRules {
     pub run: TestRunMode,
     pub check: TestCheckMode,
+    /// Why the test is skipped, if `run` is `Skip`.
+    pub skip_reason: Option<String>,
 }
 
 /// How far the test should be executed
@@ -244,6 +355,27 @@ pub struct TestRunResults {
     pub check: Option<CheckOutput>,
 }
 
+impl TestRunResults {
+    /// Why the test didn't make it to the check step, if a step refused or failed.
+    ///
+    /// Only the first line of the error is kept, since build errors carry the
+    /// whole compiler output.
+    pub fn stop_reason(&self) -> Option<String> {
+        let error = if let Some(Err(e)) = &self.source {
+            e.to_string()
+        } else if let Some(Err(e)) = &self.build {
+            e.to_string()
+        } else if let Some(Err(e)) = &self.link {
+            e.to_string()
+        } else if let Some(Err(e)) = &self.run {
+            e.to_string()
+        } else {
+            return None;
+        };
+        Some(error.lines().next().unwrap_or_default().trim().to_owned())
+    }
+}
+
 impl Default for TestRunResults {
     fn default() -> Self {
         Self {
@@ -272,6 +404,8 @@ pub struct BuildOutput {
 #[derive(Debug, Serialize)]
 pub struct LinkOutput {
     pub test_bin: PathBuf,
+    /// What to run `test_bin` under, if it can't be loaded in-process.
+    pub runner: Option<PathBuf>,
 }
 
 #[derive(Debug, Serialize)]
@@ -286,6 +420,10 @@ pub struct RunOutput {
     pub callee_inputs: WriteBuffer,
     #[serde(skip)]
     pub callee_outputs: WriteBuffer,
+    #[serde(skip)]
+    pub caller_postcall: WriteBuffer,
+    #[serde(skip)]
+    pub callee_postcall: WriteBuffer,
 }
 
 pub type Functions = LinkedHashMap<String, LinkedHashMap<String, LinkedHashMap<String, String>>>;
@@ -335,6 +473,11 @@ impl FullReport {
 
             let be_detailed = test.results.ran_to >= TestRunMode::Check;
             if !be_detailed {
+                if let Some(reason) = test.results.stop_reason() {
+                    write!(f, " ({reason})")?;
+                } else if test.rules.run == TestRunMode::Link {
+                    write!(f, " (linked, but not run)")?;
+                }
                 writeln!(f)?;
                 continue;
             }